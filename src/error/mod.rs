@@ -9,6 +9,7 @@ pub enum Error {
     UnknownCharacter(String, Span),
     Syntax(String, Span),
     InvalidCall(String, Span),
+    Domain(String, Span),
 }
 
 
@@ -18,6 +19,7 @@ impl Error {
             Self::UnknownCharacter(_, span) => *span,
             Self::Syntax(_, span) => *span,
             Self::InvalidCall(_, span) => *span,
+            Self::Domain(_, span) => *span,
         }
     }
 
@@ -26,6 +28,7 @@ impl Error {
             Self::UnknownCharacter(details, _) => Self::UnknownCharacter(details, span),
             Self::Syntax(details, _) => Self::Syntax(details, span),
             Self::InvalidCall(details, _) => Self::InvalidCall(details, span),
+            Self::Domain(details, _) => Self::Domain(details, span),
         }
     }
 
@@ -45,6 +48,10 @@ impl Error {
             Self::InvalidCall(details, span) => {
                 print!("invalid function call");
                 self.print_details(details, span, src_lines);
+            },
+            Self::Domain(details, span) => {
+                print!("domain");
+                self.print_details(details, span, src_lines);
             }
         }
     }
@@ -19,7 +19,7 @@ impl Position {
         self.index += text.len();
         if text.contains('\n') {
             self.line += 1;
-            self.column = text.split('\n').last().unwrap().chars().count() + 1;
+            self.column = text.split('\n').next_back().unwrap().chars().count() + 1;
         } else {
             self.column += text.chars().count();
         }
@@ -31,7 +31,7 @@ impl Position {
         self.index += text.len();
         if text.contains('\n') {
             self.line += 1;
-            self.column = text.split('\n').last().unwrap().chars().count() + 1;
+            self.column = text.split('\n').next_back().unwrap().chars().count() + 1;
         } else {
             self.column += text.chars().count();
         }
@@ -54,7 +54,7 @@ impl Position {
         self.index -= text.len();
         if text.contains('\n') {
             self.line += 1;
-            self.column = text.split('\n').last().unwrap().chars().count() + 1;
+            self.column = text.split('\n').next_back().unwrap().chars().count() + 1;
         } else {
             self.column += text.chars().count();
         }
@@ -66,7 +66,7 @@ impl Position {
         self.index += text.len();
         if text.contains('\n') {
             self.line += 1;
-            self.column = text.split('\n').last().unwrap().chars().count() + 1;
+            self.column = text.split('\n').next_back().unwrap().chars().count() + 1;
         } else {
             self.column += text.chars().count();
         }
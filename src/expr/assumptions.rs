@@ -0,0 +1,458 @@
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_traits::Signed;
+
+use super::{number::Number, trace, Expr};
+
+
+/// The sign an expression is known to have.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Sign {
+    Zero,
+    Positive,
+    NonNegative,
+    Negative,
+    NonPositive,
+}
+
+impl Sign {
+    fn of(v: f64) -> Option<Self> {
+        match v {
+            v if v.is_nan() => None,
+            v if v > 0.0 => Some(Sign::Positive),
+            v if v < 0.0 => Some(Sign::Negative),
+            _ => Some(Sign::Zero),
+        }
+    }
+
+    fn from_parts(direction: i8, strict: bool) -> Self {
+        match (direction, strict) {
+            (0, _) => Sign::Zero,
+            (d, true) if d > 0 => Sign::Positive,
+            (_, true) => Sign::Negative,
+            (d, false) if d > 0 => Sign::NonNegative,
+            (_, false) => Sign::NonPositive,
+        }
+    }
+
+    fn direction(self) -> i8 {
+        match self {
+            Sign::Zero => 0,
+            Sign::Positive | Sign::NonNegative => 1,
+            Sign::Negative | Sign::NonPositive => -1,
+        }
+    }
+
+    /// Whether the expression is known not to be zero.
+    pub fn is_strict(self) -> bool {
+        matches!(self, Sign::Positive | Sign::Negative)
+    }
+
+    pub fn is_nonnegative(self) -> bool {
+        self.direction() >= 0
+    }
+
+    pub fn is_nonpositive(self) -> bool {
+        self.direction() <= 0
+    }
+
+    fn neg(self) -> Self {
+        Sign::from_parts(-self.direction(), self.is_strict())
+    }
+
+    fn mul(self, rhs: Self) -> Self {
+        Sign::from_parts(self.direction() * rhs.direction(), self.is_strict() && rhs.is_strict())
+    }
+
+    /// The sign of a sum, or `None` when the terms have opposite signs.
+    fn add(self, rhs: Self) -> Option<Self> {
+        match (self, rhs) {
+            (Sign::Zero, s) | (s, Sign::Zero) => Some(s),
+            (a, b) if a.direction() == b.direction() => Some(Sign::from_parts(a.direction(), a.is_strict() || b.is_strict())),
+            _ => None,
+        }
+    }
+}
+
+
+/// What `assume[...]` has established about variables.
+#[derive(Clone, Default)]
+pub struct Assumptions {
+    /// Relations between a variable and a constant, with the variable on the left, like `x > 0`.
+    relations: Vec<Expr>,
+    /// Variables known to be integers.
+    integers: Vec<String>,
+}
+
+/// The interval a variable is confined to, as its ends and whether they are excluded, along with
+/// single values it may not take.
+struct Bounds {
+    lower: Option<(f64, bool)>,
+    upper: Option<(f64, bool)>,
+    excluded: Vec<f64>,
+}
+
+impl Bounds {
+    /// Confines the values to above `bound`.
+    fn above(&mut self, bound: (f64, bool)) {
+        if self.lower.is_none_or(|(l, strict)| bound.0 > l || (bound.0 == l && bound.1 && !strict)) {
+            self.lower = Some(bound);
+        }
+    }
+
+    /// Confines the values to below `bound`.
+    fn below(&mut self, bound: (f64, bool)) {
+        if self.upper.is_none_or(|(u, strict)| bound.0 < u || (bound.0 == u && bound.1 && !strict)) {
+            self.upper = Some(bound);
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        match (self.lower, self.upper) {
+            (Some((l, _)), Some((u, _))) if l > u => true,
+            (Some((l, a)), Some((u, b))) if l == u => a || b || self.excluded.contains(&l),
+            _ => false,
+        }
+    }
+}
+
+/// The sides of a relation.
+fn sides(relation: &Expr) -> Option<(&Expr, &Expr)> {
+    match relation {
+        Expr::Equals { left, right }
+        | Expr::NotEquals { left, right }
+        | Expr::GreaterThan { left, right }
+        | Expr::LessThan { left, right }
+        | Expr::GreaterThanEq { left, right }
+        | Expr::LessThanEq { left, right } => Some((left, right)),
+        _ => None,
+    }
+}
+
+/// The same relation with its sides swapped, like `0 < x` for `x > 0`.
+fn flip(relation: Expr) -> Expr {
+    match relation {
+        Expr::GreaterThan { left, right } => Expr::LessThan { left: right, right: left },
+        Expr::LessThan { left, right } => Expr::GreaterThan { left: right, right: left },
+        Expr::GreaterThanEq { left, right } => Expr::LessThanEq { left: right, right: left },
+        Expr::LessThanEq { left, right } => Expr::GreaterThanEq { left: right, right: left },
+        Expr::Equals { left, right } => Expr::Equals { left: right, right: left },
+        Expr::NotEquals { left, right } => Expr::NotEquals { left: right, right: left },
+        other => other,
+    }
+}
+
+impl Assumptions {
+    /// Records a relation between a variable and a constant, like `x > 0` or `1 <= x`, returning
+    /// whether it had that form.
+    pub fn assume(&mut self, relation: Expr) -> bool {
+        let Some((left, _)) = sides(&relation) else { return false };
+        let relation = if matches!(left, Expr::Variable(_)) { relation } else { flip(relation) };
+
+        let Some((Expr::Variable(_), constant)) = sides(&relation) else { return false };
+        if constant.to_f64().is_none_or(f64::is_nan) {
+            return false;
+        }
+
+        if !self.relations.contains(&relation) {
+            self.relations.push(relation);
+        }
+        true
+    }
+
+    pub fn assume_integer(&mut self, var: String) {
+        if !self.integers.contains(&var) {
+            self.integers.push(var);
+        }
+    }
+
+    /// Drops everything assumed about `var`, returning whether there was anything.
+    pub fn forget(&mut self, var: &str) -> bool {
+        let count = self.relations.len() + self.integers.len();
+        self.relations.retain(|r| !r.contains_var(var));
+        self.integers.retain(|x| x != var);
+        count != self.relations.len() + self.integers.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.relations.clear();
+        self.integers.clear();
+    }
+
+    /// Every assumption, as it would be written.
+    pub fn facts(&self) -> Vec<String> {
+        self.relations.iter()
+            .map(|r| r.to_string())
+            .chain(self.integers.iter().map(|x| format!("{} ∈ ℤ", x)))
+            .collect()
+    }
+
+    /// Whether anything is assumed about `var`.
+    pub fn constrains(&self, var: &str) -> bool {
+        self.relations.iter().any(|r| r.contains_var(var)) || self.integers.iter().any(|x| x == var)
+    }
+
+    /// The values the relations about `var` leave it.
+    fn bounds(&self, var: &str) -> Bounds {
+        let mut bounds = Bounds { lower: None, upper: None, excluded: Vec::new() };
+        for relation in &self.relations {
+            let Some((Expr::Variable(x), c)) = sides(relation) else { continue };
+            let Some(c) = c.to_f64().filter(|_| x == var) else { continue };
+            match relation {
+                Expr::Equals { .. } => {
+                    bounds.above((c, false));
+                    bounds.below((c, false));
+                },
+                Expr::NotEquals { .. } => bounds.excluded.push(c),
+                Expr::GreaterThan { .. } => bounds.above((c, true)),
+                Expr::GreaterThanEq { .. } => bounds.above((c, false)),
+                Expr::LessThan { .. } => bounds.below((c, true)),
+                Expr::LessThanEq { .. } => bounds.below((c, false)),
+                _ => {},
+            }
+        }
+        bounds
+    }
+
+    /// A variable no value of which satisfies every relation assumed about it, if there is one.
+    pub fn contradiction(&self) -> Option<String> {
+        self.relations.iter()
+            .filter_map(|r| match sides(r) {
+                Some((Expr::Variable(x), _)) => Some(x.clone()),
+                _ => None,
+            })
+            .find(|x| self.bounds(x).is_empty())
+    }
+
+    fn var_sign(&self, var: &str) -> Option<Sign> {
+        let Bounds { lower, upper, excluded } = self.bounds(var);
+        let nonzero = excluded.contains(&0.0);
+        let nonnegative = lower.is_some_and(|(l, _)| l >= 0.0);
+        let nonpositive = upper.is_some_and(|(u, _)| u <= 0.0);
+        let positive = lower.is_some_and(|(l, strict)| l > 0.0 || (l == 0.0 && (strict || nonzero)));
+        let negative = upper.is_some_and(|(u, strict)| u < 0.0 || (u == 0.0 && (strict || nonzero)));
+
+        if nonnegative && nonpositive {
+            Some(Sign::Zero)
+        } else if positive {
+            Some(Sign::Positive)
+        } else if negative {
+            Some(Sign::Negative)
+        } else if nonnegative {
+            Some(Sign::NonNegative)
+        } else if nonpositive {
+            Some(Sign::NonPositive)
+        } else {
+            None
+        }
+    }
+
+    /// The sign of `expr` for every value of its variables allowed by the assumptions, if it can be told.
+    pub fn sign(&self, expr: &Expr) -> Option<Sign> {
+        if let Some(n) = Number::from_expr(expr) {
+            return Sign::of(n.to_f64());
+        }
+
+        match expr {
+            Expr::Variable(var) => self.var_sign(var),
+            Expr::Infinity => Some(Sign::Positive),
+            Expr::Negation(x) => Some(self.sign(x)?.neg()),
+            Expr::Sum { left, right } => self.sign(left)?.add(self.sign(right)?),
+            Expr::Difference { left, right } => self.sign(left)?.add(self.sign(right)?.neg()),
+            Expr::Product { left, right } => Some(self.sign(left)?.mul(self.sign(right)?)),
+            Expr::Ratio { numerator, denominator } => {
+                let d = self.sign(denominator).filter(|d| d.is_strict())?;
+                Some(self.sign(numerator)?.mul(d))
+            },
+            Expr::Power { base, exp } => match (exp.as_integer(), self.sign(base)) {
+                (Some(k), b) if k.is_even() => match b {
+                    Some(b) if b.is_strict() => Some(Sign::Positive),
+                    _ if k.is_negative() => None,
+                    _ => Some(Sign::NonNegative),
+                },
+                (Some(k), Some(b)) if k.is_negative() => b.is_strict().then_some(b),
+                (Some(_), b) => b,
+                (None, Some(Sign::Positive)) => Some(Sign::Positive),
+                (None, Some(Sign::NonNegative)) if self.sign(exp) == Some(Sign::Positive) => Some(Sign::NonNegative),
+                _ => None,
+            },
+            Expr::Root { index, radicand } => {
+                let r = self.sign(radicand)?;
+                match index.as_integer() {
+                    Some(k) if k.is_odd() => Some(r),
+                    _ => r.is_nonnegative().then_some(r),
+                }
+            },
+            Expr::Function { name, args } if args.len() == 1 => match name.as_str() {
+                "abs" => Some(match self.sign(&args[0]) {
+                    Some(s) if s.is_strict() => Sign::Positive,
+                    Some(Sign::Zero) => Sign::Zero,
+                    _ => Sign::NonNegative,
+                }),
+                "exp" | "cosh" => Some(Sign::Positive),
+                _ if expr.variables().is_empty() => Sign::of(expr.to_f64()?),
+                _ => None,
+            },
+            Expr::Factorial(_) | Expr::DoubleFactorial(_) => Some(Sign::Positive),
+            Expr::Gamma(x) => (self.sign(x)? == Sign::Positive).then_some(Sign::Positive),
+            _ if expr.variables().is_empty() => Sign::of(expr.to_f64()?),
+            _ => None,
+        }
+    }
+
+    /// Whether `expr` is an integer for every value of its variables allowed by the assumptions.
+    pub fn is_integer(&self, expr: &Expr) -> bool {
+        match expr {
+            Expr::Integer(_) => true,
+            Expr::Variable(var) => self.integers.contains(var),
+            Expr::Negation(x) | Expr::Factorial(x) => self.is_integer(x),
+            Expr::Sum { left, right } | Expr::Difference { left, right } | Expr::Product { left, right } => {
+                self.is_integer(left) && self.is_integer(right)
+            },
+            Expr::Power { base, exp } => {
+                self.is_integer(base) && self.is_integer(exp) && self.sign(exp).is_some_and(Sign::is_nonnegative)
+            },
+            Expr::Binomial { n, k } => self.is_integer(n) && self.is_integer(k),
+            _ => false,
+        }
+    }
+
+    /// Whether the values given to variables satisfy every relation assumed about them.
+    pub fn allows(&self, point: &[(String, Expr)]) -> bool {
+        self.relations.iter().all(|relation| {
+            let relation = point.iter().fold(relation.clone(), |r, (var, value)| r.substitute(var, value));
+            Assumptions::default().decide(&relation).unwrap_or(true)
+        })
+    }
+
+    /// Whether a relation holds, fails or cannot be told under the assumptions.
+    fn decide(&self, relation: &Expr) -> Option<bool> {
+        let (left, right) = sides(relation)?;
+        let sign = self.sign(&Expr::difference(left.clone().boxed(), right.clone().boxed()).simplify())?;
+        match relation {
+            Expr::Equals { .. } if sign == Sign::Zero => Some(true),
+            Expr::Equals { .. } => sign.is_strict().then_some(false),
+            Expr::NotEquals { .. } => Some(!self.decide(&Expr::equals(left.clone().boxed(), right.clone().boxed()))?),
+            Expr::GreaterThan { .. } if sign == Sign::Positive => Some(true),
+            Expr::GreaterThan { .. } => sign.is_nonpositive().then_some(false),
+            Expr::GreaterThanEq { .. } if sign.is_nonnegative() => Some(true),
+            Expr::GreaterThanEq { .. } => (sign == Sign::Negative).then_some(false),
+            Expr::LessThan { .. } if sign == Sign::Negative => Some(true),
+            Expr::LessThan { .. } => sign.is_nonnegative().then_some(false),
+            Expr::LessThanEq { .. } if sign.is_nonpositive() => Some(true),
+            Expr::LessThanEq { .. } => (sign == Sign::Positive).then_some(false),
+            _ => None,
+        }
+    }
+}
+
+
+fn abs(x: Expr) -> Expr {
+    Expr::function("abs".to_string(), vec![x])
+}
+
+impl Expr {
+    /// Applies simplifications that only hold under `assumptions`, like `|x| = x` for `x >= 0`, along
+    /// with those that hold for every real value, like `√(x²) = |x|`.
+    ///
+    /// Expects an already simplified expression.
+    pub fn refine(self, assumptions: &Assumptions) -> Expr {
+        trace::scope(self, "apply assumptions", |expr| expr.refine_node(assumptions))
+    }
+
+    fn refine_node(self, assumptions: &Assumptions) -> Expr {
+        let two = || Expr::integer(BigInt::from(2));
+
+        match self.map_children(|x| x.refine(assumptions)) {
+            Expr::Function { name, args } if name == "abs" && args.len() == 1 => match assumptions.sign(&args[0]) {
+                Some(s) if s.is_nonnegative() => trace::step("|u| = u for u ≥ 0", args[0].clone()),
+                Some(_) => trace::step("|u| = -u for u ≤ 0", Expr::negation(args[0].clone().boxed()).simplify()),
+                None => abs(args[0].clone()),
+            },
+            // `ᵏ√(b^(k j)) = b^j`, or `|b|^j` for even `k`
+            Expr::Root { index, radicand } => match (index.as_integer(), &*radicand) {
+                (Some(k), Expr::Power { base, exp }) if exp.as_integer().is_some_and(|e| e.is_multiple_of(k)) => {
+                    let j = Expr::integer(exp.as_integer().unwrap() / k);
+                    let (name, base) = match k.is_odd() {
+                        true => ("ᵏ√(b^(k j)) = b^j for odd k", base.clone().into_inner()),
+                        false => ("ᵏ√(b^(k j)) = |b|^j for even k", abs(base.clone().into_inner()).refine(assumptions)),
+                    };
+                    trace::step(name, Expr::power(base.boxed(), j.boxed()).simplify())
+                },
+                _ => Expr::root(index, radicand),
+            },
+            // `(b^p)^q = b^(p q)` for `b >= 0`, and `|b|^(p q)` for even integer `p`
+            Expr::Power { base, exp: q } => match base.into_inner() {
+                Expr::Power { base: b, exp: p } if assumptions.sign(&b).is_some_and(Sign::is_nonnegative) => {
+                    trace::step("(bᵖ)^q = b^(p q) for b ≥ 0", Expr::power(b, Expr::product(p, q).simplify().boxed()).simplify())
+                },
+                Expr::Power { base: b, exp: p } if p.as_integer().is_some_and(|p| p.is_even()) => {
+                    let power = Expr::power(abs(b.into_inner()).refine(assumptions).boxed(), Expr::product(p, q).simplify().boxed()).simplify();
+                    trace::step("(bᵖ)^q = |b|^(p q) for even p", power)
+                },
+                // `(-1)^(2 m) = 1` and `(-1)^(2 m + 1) = -1` for integer `m`
+                Expr::Integer(b) if b == BigInt::from(-1) => {
+                    let half = |e: Expr| assumptions.is_integer(&Expr::ratio(e.boxed(), two().boxed()).simplify());
+                    if half(q.clone().into_inner()) {
+                        trace::step("(-1)^(2 m) = 1", Expr::integer(BigInt::from(1)))
+                    } else if half(Expr::difference(q.clone(), Expr::integer(BigInt::from(1)).boxed())) {
+                        trace::step("(-1)^(2 m + 1) = -1", Expr::integer(b))
+                    } else {
+                        Expr::power(Expr::integer(b).boxed(), q)
+                    }
+                },
+                base => Expr::power(base.boxed(), q),
+            },
+            relation if sides(&relation).is_some() => match assumptions.decide(&relation) {
+                Some(holds) => trace::step("decide from assumptions", Expr::Boolean(holds)),
+                None => relation,
+            },
+            other => other,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{session::Session, testing::{error, eval_in, run}};
+
+    fn assuming(facts: &str) -> Session {
+        let mut session = Session::new();
+        eval_in(&mut session, facts);
+        session
+    }
+
+    #[test]
+    fn refine_under_assumptions() {
+        let mut session = assuming("assume[x > 0]");
+        assert_eq!(eval_in(&mut session, "sqrt[x^2]"), "x");
+        assert_eq!(eval_in(&mut session, "abs[x - 0]"), "x");
+        let mut session = assuming("assume[y, integer, positive]");
+        assert_eq!(eval_in(&mut session, "(-1)^(2y)"), "1");
+        assert_eq!(eval_in(&mut session, "sqrt[y^2]"), "y");
+        assert_eq!(eval_in(&mut Session::new(), "sqrt[x^2]"), "abs[x]");
+    }
+
+    #[test]
+    fn contradictions_are_rejected() {
+        assert_eq!(error("assume[z > 2, z < 1]"), "the assumptions about z contradict each other");
+        let mut session = assuming("assume[x > 0]");
+        assert_eq!(run(&mut session, "assume[x < -1]"), Err("the assumptions about x contradict each other".to_string()));
+        assert_eq!(eval_in(&mut session, "abs[x]"), "x");
+    }
+
+    #[test]
+    fn malformed_assumptions() {
+        assert_eq!(error("assume[z, blue]"), "unknown property 'blue', expected integer, positive, negative, nonnegative, nonpositive or nonzero");
+        assert_eq!(error("assume[z + 1 > 0]"), "expected a relation between an unbound variable and a constant, like x > 0");
+    }
+
+    #[test]
+    fn nsolve_keeps_allowed_roots() {
+        let mut session = assuming("assume[w >= 0]");
+        let solution = eval_in(&mut session, "nsolve:w:-1[w^2 - 4]");
+        assert!(solution.starts_with("w = 2 "), "{}", solution);
+        let mut session = assuming("assume[v > 5]");
+        assert_eq!(run(&mut session, "nsolve:v:1[v^2 - 4]"), Err("no root of ((v ^ 2) - 4) found near 1 satisfies the assumptions about v".to_string()));
+    }
+}
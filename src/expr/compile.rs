@@ -0,0 +1,331 @@
+use std::collections::HashMap;
+
+use num_traits::ToPrimitive;
+
+use super::{functions::{self, ELEMENTARY}, number, special, Expr};
+
+
+/// Points evaluated together by [`CompiledFn::eval`], each instruction running over all of them
+/// before the next so that the loop over the points is tight.
+const LANES: usize = 256;
+
+/// An operation of the bytecode, on the values of earlier operations while lowering and on registers
+/// once they are allocated.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum Op {
+    /// A constant, by its bits.
+    Const(u64),
+    /// The variable at this position of those compiled for.
+    Var(usize),
+    Neg(usize),
+    Add(usize, usize),
+    Sub(usize, usize),
+    Mul(usize, usize),
+    Div(usize, usize),
+    Pow(usize, usize),
+    Powi(usize, i32),
+    /// `Root(index, radicand)`.
+    Root(usize, usize),
+    Gamma(usize),
+    /// The elementary function at this position of [`ELEMENTARY`].
+    Call(usize, usize),
+}
+
+impl Op {
+    fn operands(self) -> Vec<usize> {
+        let mut operands = match self {
+            Op::Const(_) | Op::Var(_) => vec![],
+            Op::Neg(a) | Op::Powi(a, _) | Op::Gamma(a) | Op::Call(_, a) => vec![a],
+            Op::Add(a, b) | Op::Sub(a, b) | Op::Mul(a, b) | Op::Div(a, b) | Op::Pow(a, b) | Op::Root(a, b) => vec![a, b],
+        };
+        operands.dedup();
+        operands
+    }
+
+    fn map(self, f: impl Fn(usize) -> usize) -> Op {
+        match self {
+            Op::Const(_) | Op::Var(_) => self,
+            Op::Neg(a) => Op::Neg(f(a)),
+            Op::Add(a, b) => Op::Add(f(a), f(b)),
+            Op::Sub(a, b) => Op::Sub(f(a), f(b)),
+            Op::Mul(a, b) => Op::Mul(f(a), f(b)),
+            Op::Div(a, b) => Op::Div(f(a), f(b)),
+            Op::Pow(a, b) => Op::Pow(f(a), f(b)),
+            Op::Powi(a, n) => Op::Powi(f(a), n),
+            Op::Root(a, b) => Op::Root(f(a), f(b)),
+            Op::Gamma(a) => Op::Gamma(f(a)),
+            Op::Call(name, a) => Op::Call(name, f(a)),
+        }
+    }
+
+    /// Evaluates the operation at a single point, with `value` giving the values of its operands.
+    fn apply(self, value: impl Fn(usize) -> f64, args: &[f64]) -> f64 {
+        match self {
+            Op::Const(bits) => f64::from_bits(bits),
+            Op::Var(i) => args[i],
+            Op::Neg(a) => -value(a),
+            Op::Add(a, b) => value(a) + value(b),
+            Op::Sub(a, b) => value(a) - value(b),
+            Op::Mul(a, b) => value(a) * value(b),
+            Op::Div(a, b) => value(a) / value(b),
+            Op::Pow(a, b) => value(a).powf(value(b)),
+            Op::Powi(a, n) => value(a).powi(n),
+            Op::Root(a, b) => number::real_root(value(a), value(b)),
+            Op::Gamma(a) => special::gamma(value(a)),
+            Op::Call(name, a) => function(name)(value(a)),
+        }
+    }
+}
+
+fn function(index: usize) -> fn(f64) -> f64 {
+    functions::float(ELEMENTARY[index]).unwrap()
+}
+
+/// An instruction, computing `op` from registers into the register `dst`.
+#[derive(Clone, Copy)]
+struct Instr {
+    op: Op,
+    dst: usize,
+}
+
+/// An expression lowered to bytecode for evaluating it at many points.
+pub struct CompiledFn {
+    code: Vec<Instr>,
+    registers: usize,
+    output: usize,
+    arity: usize,
+}
+
+/// Lowers an expression to operations on values numbered by the operation that computes them, so
+/// that an operation repeated on the same values is computed once.
+struct Builder<'a> {
+    vars: &'a [&'a str],
+    ops: Vec<Op>,
+    numbers: HashMap<Op, usize>,
+    /// The values of the subexpressions lowered so far, by address, as equal subexpressions share
+    /// an allocation.
+    lowered: HashMap<*const Expr, usize>,
+}
+
+impl Builder<'_> {
+    fn constant(&self, value: usize) -> Option<f64> {
+        match self.ops[value] {
+            Op::Const(bits) => Some(f64::from_bits(bits)),
+            _ => None,
+        }
+    }
+
+    /// Numbers the value of `op`, folding it into a constant when its operands are all constants.
+    fn push(&mut self, op: Op) -> usize {
+        let operands = op.operands();
+        let op = match !operands.is_empty() && operands.iter().all(|&v| self.constant(v).is_some()) {
+            true => Op::Const(op.apply(|v| self.constant(v).unwrap(), &[]).to_bits()),
+            false => op,
+        };
+
+        *self.numbers.entry(op).or_insert_with(|| {
+            self.ops.push(op);
+            self.ops.len() - 1
+        })
+    }
+
+    fn lower(&mut self, expr: &Expr) -> Option<usize> {
+        if let Some(&value) = self.lowered.get(&(expr as *const Expr)) {
+            return Some(value);
+        }
+
+        let value = match expr {
+            Expr::Integer(_) | Expr::Decimal(_) | Expr::Infinity | Expr::Factorization { .. } => self.push(Op::Const(expr.to_f64()?.to_bits())),
+            Expr::Variable(name) => self.push(Op::Var(self.vars.iter().position(|v| v == name)?)),
+            Expr::Negation(x) => {
+                let x = self.lower(x)?;
+                self.push(Op::Neg(x))
+            },
+            Expr::Sum { left, right } => self.binary(left, right, Op::Add)?,
+            Expr::Difference { left, right } => self.binary(left, right, Op::Sub)?,
+            Expr::Product { left, right } => self.binary(left, right, Op::Mul)?,
+            Expr::Ratio { numerator, denominator } => self.binary(numerator, denominator, Op::Div)?,
+            Expr::Power { base, exp } => match exp.as_integer().and_then(ToPrimitive::to_i32) {
+                Some(n) => {
+                    let base = self.lower(base)?;
+                    self.push(Op::Powi(base, n))
+                },
+                None => self.binary(base, exp, Op::Pow)?,
+            },
+            Expr::Root { index, radicand } => self.binary(index, radicand, Op::Root)?,
+            Expr::Factorial(x) => {
+                let x = self.lower(x)?;
+                self.gamma_of_successor(x)
+            },
+            Expr::Gamma(x) => {
+                let x = self.lower(x)?;
+                self.push(Op::Gamma(x))
+            },
+            Expr::Binomial { n, k } => {
+                let (n, k) = (self.lower(n)?, self.lower(k)?);
+                let n_minus_k = self.push(Op::Sub(n, k));
+                let (a, b, c) = (self.gamma_of_successor(n), self.gamma_of_successor(k), self.gamma_of_successor(n_minus_k));
+                let denominator = self.push(Op::Mul(b, c));
+                self.push(Op::Div(a, denominator))
+            },
+            Expr::Function { name, args } if args.len() == 1 => {
+                let name = ELEMENTARY.iter().position(|f| f == name)?;
+                let x = self.lower(&args[0])?;
+                self.push(Op::Call(name, x))
+            },
+            _ => return None,
+        };
+
+        self.lowered.insert(expr, value);
+        Some(value)
+    }
+
+    fn binary(&mut self, a: &Expr, b: &Expr, op: fn(usize, usize) -> Op) -> Option<usize> {
+        let (a, b) = (self.lower(a)?, self.lower(b)?);
+        Some(self.push(op(a, b)))
+    }
+
+    /// `Γ(x + 1)`, as factorials and binomials are evaluated.
+    fn gamma_of_successor(&mut self, x: usize) -> usize {
+        let one = self.push(Op::Const(1f64.to_bits()));
+        let successor = self.push(Op::Add(x, one));
+        self.push(Op::Gamma(successor))
+    }
+
+    /// Drops the operations the output does not depend on and assigns registers to the rest,
+    /// reusing those of values that are no longer needed.
+    fn allocate(self, output: usize) -> CompiledFn {
+        let mut last_use = vec![None; self.ops.len()];
+        let mut live = vec![false; self.ops.len()];
+        live[output] = true;
+        for i in (0..self.ops.len()).rev() {
+            if !live[i] {
+                continue;
+            }
+            for v in self.ops[i].operands() {
+                live[v] = true;
+                last_use[v].get_or_insert(i);
+            }
+        }
+
+        let mut registers = vec![0; self.ops.len()];
+        let mut free = Vec::new();
+        let mut count = 0;
+        let mut code = Vec::new();
+        for (i, op) in self.ops.iter().enumerate().filter(|(i, _)| live[*i]) {
+            op.operands().into_iter().filter(|&v| last_use[v] == Some(i)).for_each(|v| free.push(registers[v]));
+            registers[i] = free.pop().unwrap_or_else(|| {
+                count += 1;
+                count - 1
+            });
+            code.push(Instr { op: op.map(|v| registers[v]), dst: registers[i] });
+        }
+
+        CompiledFn { code, registers: count, output: registers[output], arity: self.vars.len() }
+    }
+}
+
+fn unary(regs: &mut [f64], dst: usize, a: usize, n: usize, f: impl Fn(f64) -> f64) {
+    for j in 0..n {
+        regs[dst * LANES + j] = f(regs[a * LANES + j]);
+    }
+}
+
+fn binary(regs: &mut [f64], dst: usize, a: usize, b: usize, n: usize, f: impl Fn(f64, f64) -> f64) {
+    for j in 0..n {
+        regs[dst * LANES + j] = f(regs[a * LANES + j], regs[b * LANES + j]);
+    }
+}
+
+impl CompiledFn {
+    /// Evaluates at a single point, given the value of each variable in the order they were
+    /// compiled for.
+    pub fn call(&self, args: &[f64]) -> f64 {
+        assert_eq!(args.len(), self.arity, "expected a value for each variable");
+        let mut regs = vec![0.0; self.registers];
+        for instr in &self.code {
+            let value = instr.op.apply(|r| regs[r], args);
+            regs[instr.dst] = value;
+        }
+        regs[self.output]
+    }
+
+    /// Evaluates at many points, where `args[i][j]` is the value of the `i`th variable at the `j`th
+    /// point, writing the value at each point to `out`.
+    pub fn eval(&self, args: &[&[f64]], out: &mut [f64]) {
+        assert_eq!(args.len(), self.arity, "expected values for each variable");
+        assert!(args.iter().all(|x| x.len() >= out.len()), "expected a value of each variable at every point");
+
+        let mut regs = vec![0.0; self.registers * LANES];
+        for start in (0..out.len()).step_by(LANES) {
+            let n = LANES.min(out.len() - start);
+            for &Instr { op, dst } in &self.code {
+                match op {
+                    Op::Const(bits) => regs[dst * LANES..dst * LANES + n].fill(f64::from_bits(bits)),
+                    Op::Var(i) => regs[dst * LANES..dst * LANES + n].copy_from_slice(&args[i][start..start + n]),
+                    Op::Neg(a) => unary(&mut regs, dst, a, n, |x| -x),
+                    Op::Add(a, b) => binary(&mut regs, dst, a, b, n, |x, y| x + y),
+                    Op::Sub(a, b) => binary(&mut regs, dst, a, b, n, |x, y| x - y),
+                    Op::Mul(a, b) => binary(&mut regs, dst, a, b, n, |x, y| x * y),
+                    Op::Div(a, b) => binary(&mut regs, dst, a, b, n, |x, y| x / y),
+                    Op::Pow(a, b) => binary(&mut regs, dst, a, b, n, f64::powf),
+                    Op::Powi(a, k) => unary(&mut regs, dst, a, n, |x| x.powi(k)),
+                    Op::Root(a, b) => binary(&mut regs, dst, a, b, n, number::real_root),
+                    Op::Gamma(a) => unary(&mut regs, dst, a, n, special::gamma),
+                    Op::Call(name, a) => unary(&mut regs, dst, a, n, function(name)),
+                }
+            }
+            out[start..start + n].copy_from_slice(&regs[self.output * LANES..self.output * LANES + n]);
+        }
+    }
+}
+
+impl Expr {
+    /// Compiles the expression to bytecode evaluating it as a float, with the values of `vars` as
+    /// arguments.
+    ///
+    /// Returns `None` if the expression has other variables or anything [`Expr::evaluate`] cannot
+    /// evaluate.
+    pub fn compile(&self, vars: &[&str]) -> Option<CompiledFn> {
+        let mut builder = Builder { vars, ops: Vec::new(), numbers: HashMap::new(), lowered: HashMap::new() };
+        let output = builder.lower(self)?;
+        Some(builder.allocate(output))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LANES;
+    use crate::{session::Session, testing::parse};
+
+    #[test]
+    fn compiled_matches_tree_walk() {
+        for input in ["sin[x]^2 + cos[x]", "exp[-(x^2)] / (1 + x)", "sqrt[x] ln[x] - x^3", "abs[x - 1]^(1/3)"] {
+            let expr = Session::new().simplify(parse(input));
+            let Some(compiled) = expr.compile(&["x"]) else { panic!("cannot compile {}", expr) };
+            for x in [0.25, 0.5, 1.0, 2.0, 3.75] {
+                let (Some(walked), compiled) = (expr.evaluate(&[("x", x)]), compiled.call(&[x])) else { panic!("cannot evaluate {}", expr) };
+                assert!((walked - compiled).abs() <= 1e-12 * walked.abs().max(1.0), "{} at {}: {} != {}", expr, x, walked, compiled);
+            }
+        }
+    }
+
+    #[test]
+    fn batches_match_single_calls() {
+        let expr = parse("x y + sin[x] - y^2");
+        let Some(compiled) = expr.compile(&["x", "y"]) else { panic!("cannot compile {}", expr) };
+        // More points than fit in one batch, so that a partial batch is left over.
+        let xs: Vec<f64> = (0..LANES + 37).map(|i| i as f64 * 0.01).collect();
+        let ys: Vec<f64> = (0..LANES + 37).map(|i| 2.0 - i as f64 * 0.005).collect();
+        let mut out = vec![0.0; xs.len()];
+        compiled.eval(&[&xs, &ys], &mut out);
+        for ((x, y), value) in xs.iter().zip(&ys).zip(&out) {
+            assert_eq!(*value, compiled.call(&[*x, *y]), "at ({}, {})", x, y);
+        }
+    }
+
+    #[test]
+    fn unknown_variables_do_not_compile() {
+        assert!(parse("x + y").compile(&["x"]).is_none());
+    }
+}
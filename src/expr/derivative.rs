@@ -0,0 +1,105 @@
+use num_bigint::BigInt;
+use num_traits::{One, Zero};
+
+use super::{functions, Expr};
+
+
+impl Expr {
+    /// Differentiates the expression with respect to `var`.
+    ///
+    /// Functions without a known definition are differentiated through the chain rule into prime
+    /// notation, so `f[x^2]` becomes `2x f'[x^2]`. Returns `None` when there is no derivative to give,
+    /// like for factorials of `var` or inequalities.
+    pub fn differentiate(&self, var: &str) -> Option<Expr> {
+        if !self.contains_var(var) {
+            return Some(Expr::integer(BigInt::zero()));
+        }
+
+        Some(match self {
+            Expr::Variable(_) => Expr::integer(BigInt::one()),
+            Expr::Negation(node) => Expr::negation(node.differentiate(var)?.boxed()),
+            Expr::Sum { left, right } => Expr::sum(left.differentiate(var)?.boxed(), right.differentiate(var)?.boxed()),
+            Expr::Difference { left, right } => Expr::difference(left.differentiate(var)?.boxed(), right.differentiate(var)?.boxed()),
+            Expr::Product { left, right } => Expr::sum(
+                Expr::product(left.differentiate(var)?.boxed(), right.clone()).boxed(),
+                Expr::product(left.clone(), right.differentiate(var)?.boxed()).boxed(),
+            ),
+            Expr::Ratio { numerator, denominator } => Expr::ratio(
+                Expr::difference(
+                    Expr::product(numerator.differentiate(var)?.boxed(), denominator.clone()).boxed(),
+                    Expr::product(numerator.clone(), denominator.differentiate(var)?.boxed()).boxed(),
+                ).boxed(),
+                Expr::power(denominator.clone(), Expr::integer(BigInt::from(2)).boxed()).boxed(),
+            ),
+            Expr::Power { base, exp } => if exp.contains_var(var) {
+                // d/dx b^e = b^e (e' ln b + e b' / b)
+                let ln = Expr::function("ln".to_string(), vec![base.clone().into_inner()]);
+                Expr::product(
+                    self.clone().boxed(),
+                    Expr::sum(
+                        Expr::product(exp.differentiate(var)?.boxed(), ln.boxed()).boxed(),
+                        Expr::ratio(Expr::product(exp.clone(), base.differentiate(var)?.boxed()).boxed(), base.clone()).boxed(),
+                    ).boxed(),
+                )
+            } else {
+                Expr::product(
+                    Expr::product(
+                        exp.clone(),
+                        Expr::power(base.clone(), Expr::difference(exp.clone(), Expr::integer(BigInt::one()).boxed()).boxed()).boxed(),
+                    ).boxed(),
+                    base.differentiate(var)?.boxed(),
+                )
+            },
+            Expr::Root { index, radicand } => Expr::power(
+                radicand.clone(),
+                Expr::ratio(Expr::integer(BigInt::one()).boxed(), index.clone()).boxed(),
+            ).differentiate(var)?,
+            Expr::Function { name, args } if args.len() == 1 && functions::is_elementary(name) => Expr::product(
+                args[0].differentiate(var)?.boxed(),
+                functions::derivative(name, &args[0])?.boxed(),
+            ),
+            Expr::Function { name, args } if args.len() == 1 => Expr::product(
+                args[0].differentiate(var)?.boxed(),
+                Expr::derivative(name.clone(), 1, args.clone()).boxed(),
+            ),
+            Expr::Derivative { name, order, args } if args.is_empty() => Expr::derivative(name.clone(), order + 1, vec![]),
+            Expr::Derivative { name, order, args } if args.len() == 1 => Expr::product(
+                args[0].differentiate(var)?.boxed(),
+                Expr::derivative(name.clone(), order + 1, args.clone()).boxed(),
+            ),
+            Expr::IndexedSum { var: index, from, to, body } if index != var && !from.contains_var(var) && !to.contains_var(var) => {
+                Expr::indexedsum(index.clone(), from.clone(), to.clone(), body.differentiate(var)?.boxed())
+            },
+            Expr::Matrix { rows } => Expr::matrix(rows.iter()
+                .map(|row| row.iter().map(|x| x.differentiate(var)).collect::<Option<_>>())
+                .collect::<Option<_>>()?),
+            Expr::Equals { left, right } => Expr::equals(left.differentiate(var)?.boxed(), right.differentiate(var)?.boxed()),
+            _ => return None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{session::Session, testing::{eval, eval_in}};
+
+    #[test]
+    fn derivatives() {
+        assert_eq!(eval("diff:x[x^3 sin[x]]"), "(3(x ^ 2)sin[x] + (x ^ 3)cos[x])");
+        assert_eq!(eval("diff:x[f[x^2]]"), "2xf'[(x ^ 2)]");
+        assert_eq!(eval("diff:x[f'[x]]"), "f''[x]");
+    }
+
+    #[test]
+    fn prime_notation() {
+        assert_eq!(eval("f'[x]"), "f'[x]");
+        assert_eq!(eval("f''"), "f''");
+    }
+
+    #[test]
+    fn bound_variable_is_not_substituted() {
+        let mut session = Session::new();
+        eval_in(&mut session, "x := 3");
+        assert_eq!(eval_in(&mut session, "diff:x[x^2]"), "2x");
+    }
+}
@@ -0,0 +1,540 @@
+use std::{collections::{HashMap, HashSet}, time::{Duration, Instant}};
+
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::{ToPrimitive, Zero};
+
+use super::{number::Number, rules::Rule, Expr};
+use crate::{lexer::Lexer, parser::Parser, session::Session};
+
+
+/// Rewrites made by every e-graph simplification, as a pattern and its replacement, where every
+/// variable is a wildcard.
+const RULES: &[(&str, &str)] = &[
+    ("a + b", "b + a"),
+    ("a * b", "b * a"),
+    ("(a + b) + c", "a + (b + c)"),
+    ("a + (b + c)", "(a + b) + c"),
+    ("(a * b) * c", "a * (b * c)"),
+    ("a * (b * c)", "(a * b) * c"),
+
+    ("a + 0", "a"),
+    ("a * 1", "a"),
+    ("a * 0", "0"),
+    ("a ^ 1", "a"),
+    ("-(-a)", "a"),
+    ("a + -a", "0"),
+    ("-a", "-1 * a"),
+    ("-1 * a", "-a"),
+
+    ("a * (b + c)", "a * b + a * c"),
+    ("a * b + a * c", "a * (b + c)"),
+    ("a + a", "2 * a"),
+
+    ("a * a", "a ^ 2"),
+    ("a ^ b * a ^ c", "a ^ (b + c)"),
+    ("a * a ^ b", "a ^ (b + 1)"),
+    ("a / b", "a * b ^ -1"),
+    ("a * b ^ -1", "a / b"),
+    ("sqrt[a] ^ 2", "a"),
+
+    ("sin[a] ^ 2 + cos[a] ^ 2", "1"),
+    ("sin[a] / cos[a]", "tan[a]"),
+    ("exp[a] * exp[b]", "exp[a + b]"),
+    ("ln[exp[a]]", "a"),
+];
+
+/// Exponents up to this size are folded when both the base and exponent are constants.
+const MAX_FOLDED_EXPONENT: u32 = 64;
+
+type Id = usize;
+
+/// The operator of a node, with anything besides arithmetic kept as the index of its shape.
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum Op {
+    Integer(BigInt),
+    /// The bits of a decimal, so that nodes can be hashed.
+    Decimal(u64),
+    Variable(String),
+    Neg,
+    Add,
+    Mul,
+    Div,
+    Pow,
+    Other(usize),
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct Node {
+    op: Op,
+    children: Vec<Id>,
+}
+
+/// A set of equivalent nodes, along with the rational value they all take, if known.
+struct Class {
+    nodes: Vec<Node>,
+    constant: Option<BigRational>,
+}
+
+enum Pattern {
+    Wildcard(String),
+    Node(Op, Vec<Pattern>),
+}
+
+/// The wildcards bound by a match, to the classes they matched.
+type Subst = Vec<(String, Id)>;
+
+/// How extraction ranks the equivalent forms of an expression. Ties are broken by node count.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Cost {
+    /// The fewest nodes.
+    Size,
+    /// The shallowest tree.
+    Depth,
+    /// The fewest roots and fractional powers.
+    Radicals,
+}
+
+impl Cost {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "size" => Some(Cost::Size),
+            "depth" => Some(Cost::Depth),
+            "radicals" => Some(Cost::Radicals),
+            _ => None,
+        }
+    }
+}
+
+/// Bounds on saturation, which stops early once any is reached.
+pub struct Limits {
+    pub nodes: usize,
+    pub iterations: usize,
+    pub time: Duration,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self { nodes: 10_000, iterations: 30, time: Duration::from_millis(500) }
+    }
+}
+
+/// Equivalence classes of expressions sharing their subexpressions.
+#[derive(Default)]
+struct EGraph {
+    parents: Vec<Id>,
+    classes: HashMap<Id, Class>,
+    memo: HashMap<Node, Id>,
+    /// Expressions with their children replaced, standing for the operators of [`Op::Other`].
+    shapes: Vec<Expr>,
+}
+
+fn shape(expr: &Expr) -> Expr {
+    expr.clone().map_children(|_| Expr::integer(BigInt::zero()))
+}
+
+impl EGraph {
+    fn find(&self, mut id: Id) -> Id {
+        while self.parents[id] != id {
+            id = self.parents[id];
+        }
+        id
+    }
+
+    fn canonical(&self, node: &Node) -> Node {
+        Node { op: node.op.clone(), children: node.children.iter().map(|&c| self.find(c)).collect() }
+    }
+
+    fn node_count(&self) -> usize {
+        self.classes.values().map(|c| c.nodes.len()).sum()
+    }
+
+    fn intern(&mut self, expr: &Expr) -> usize {
+        let shape = shape(expr);
+        match self.shapes.iter().position(|s| *s == shape) {
+            Some(i) => i,
+            None => {
+                self.shapes.push(shape);
+                self.shapes.len() - 1
+            },
+        }
+    }
+
+    fn constant(&self, id: Id) -> Option<&BigRational> {
+        self.classes[&self.find(id)].constant.as_ref()
+    }
+
+    /// The value of a node whose children all have known values.
+    fn fold(&self, node: &Node) -> Option<BigRational> {
+        let arg = |i: usize| self.constant(node.children[i]).cloned();
+        match &node.op {
+            Op::Integer(n) => Some(BigRational::from_integer(n.clone())),
+            Op::Neg => Some(-arg(0)?),
+            Op::Add => Some(arg(0)? + arg(1)?),
+            Op::Mul => Some(arg(0)? * arg(1)?),
+            Op::Div => arg(1).filter(|d| !d.is_zero()).and_then(|d| Some(arg(0)? / d)),
+            Op::Pow => {
+                let (base, exp) = (arg(0)?, arg(1)?);
+                let e = exp.is_integer().then(|| exp.to_integer().to_i32()).flatten().filter(|e| e.unsigned_abs() <= MAX_FOLDED_EXPONENT)?;
+                (!base.is_zero() || e > 0).then(|| base.pow(e))
+            },
+            _ => None,
+        }
+    }
+
+    fn add(&mut self, node: Node) -> Id {
+        let node = self.canonical(&node);
+        if let Some(&id) = self.memo.get(&node) {
+            return self.find(id);
+        }
+
+        let id = self.parents.len();
+        self.parents.push(id);
+        let constant = self.fold(&node);
+        let is_literal = matches!(node.op, Op::Integer(_));
+        self.classes.insert(id, Class { nodes: vec![node.clone()], constant: constant.clone() });
+        self.memo.insert(node, id);
+
+        if let Some(c) = constant.filter(|_| !is_literal) {
+            let literal = self.add_constant(&c);
+            self.union(id, literal);
+        }
+        self.find(id)
+    }
+
+    fn add_constant(&mut self, c: &BigRational) -> Id {
+        let integer = |n: &BigInt| Node { op: Op::Integer(n.clone()), children: vec![] };
+        if c.is_integer() {
+            return self.add(integer(c.numer()));
+        }
+        let (n, d) = (self.add(integer(c.numer())), self.add(integer(c.denom())));
+        self.add(Node { op: Op::Div, children: vec![n, d] })
+    }
+
+    fn add_expr(&mut self, expr: &Expr) -> Id {
+        let (op, children): (Op, Vec<&Expr>) = match expr {
+            Expr::Integer(n) => (Op::Integer(n.clone()), vec![]),
+            Expr::Decimal(v) => (Op::Decimal(v.to_bits()), vec![]),
+            Expr::Variable(name) => (Op::Variable(name.clone()), vec![]),
+            Expr::Negation(x) if matches!(**x, Expr::Integer(_)) => (Op::Integer(-x.as_integer().unwrap()), vec![]),
+            Expr::Negation(x) => (Op::Neg, vec![x]),
+            Expr::Sum { left, right } => (Op::Add, vec![left, right]),
+            Expr::Difference { left, right } => {
+                let (l, r) = (self.add_expr(left), self.add_expr(right));
+                let r = self.add(Node { op: Op::Neg, children: vec![r] });
+                return self.add(Node { op: Op::Add, children: vec![l, r] });
+            },
+            Expr::Product { left, right } => (Op::Mul, vec![left, right]),
+            Expr::Ratio { numerator, denominator } => (Op::Div, vec![numerator, denominator]),
+            Expr::Power { base, exp } => (Op::Pow, vec![base, exp]),
+            other => (Op::Other(self.intern(other)), other.children()),
+        };
+
+        let children = children.into_iter().map(|x| self.add_expr(x)).collect();
+        self.add(Node { op, children })
+    }
+
+    fn pattern(&mut self, expr: &Expr, wildcards: &[String]) -> Pattern {
+        let (op, children): (Op, Vec<&Expr>) = match expr {
+            Expr::Variable(name) if wildcards.contains(name) => return Pattern::Wildcard(name.clone()),
+            Expr::Integer(n) => (Op::Integer(n.clone()), vec![]),
+            Expr::Decimal(v) => (Op::Decimal(v.to_bits()), vec![]),
+            Expr::Variable(name) => (Op::Variable(name.clone()), vec![]),
+            Expr::Negation(x) if matches!(**x, Expr::Integer(_)) => (Op::Integer(-x.as_integer().unwrap()), vec![]),
+            Expr::Negation(x) => (Op::Neg, vec![x]),
+            Expr::Sum { left, right } => (Op::Add, vec![left, right]),
+            Expr::Difference { left, right } => return Pattern::Node(Op::Add, vec![
+                self.pattern(left, wildcards),
+                Pattern::Node(Op::Neg, vec![self.pattern(right, wildcards)]),
+            ]),
+            Expr::Product { left, right } => (Op::Mul, vec![left, right]),
+            Expr::Ratio { numerator, denominator } => (Op::Div, vec![numerator, denominator]),
+            Expr::Power { base, exp } => (Op::Pow, vec![base, exp]),
+            other => (Op::Other(self.intern(other)), other.children()),
+        };
+
+        Pattern::Node(op, children.into_iter().map(|x| self.pattern(x, wildcards)).collect())
+    }
+
+    fn union(&mut self, a: Id, b: Id) -> bool {
+        let (a, b) = (self.find(a), self.find(b));
+        if a == b {
+            return false;
+        }
+
+        self.parents[b] = a;
+        let merged = self.classes.remove(&b).unwrap();
+        let class = self.classes.get_mut(&a).unwrap();
+        class.nodes.extend(merged.nodes);
+        if class.constant.is_none() {
+            class.constant = merged.constant;
+        }
+        true
+    }
+
+    /// Restores the invariants after unions, merging classes whose nodes became identical and
+    /// folding those whose value became known.
+    fn rebuild(&mut self) {
+        loop {
+            self.merge_congruent();
+
+            let folded: Vec<(Id, BigRational)> = self.classes
+                .iter()
+                .filter(|(_, class)| class.constant.is_none())
+                .filter_map(|(&id, class)| class.nodes.iter().find_map(|n| self.fold(n)).map(|c| (id, c)))
+                .collect();
+            if folded.is_empty() {
+                break;
+            }
+            for (id, c) in folded {
+                let id = self.find(id);
+                self.classes.get_mut(&id).unwrap().constant = Some(c.clone());
+                let literal = self.add_constant(&c);
+                self.union(id, literal);
+            }
+        }
+
+        let ids: Vec<Id> = self.classes.keys().copied().collect();
+        for id in ids {
+            let mut seen = HashSet::new();
+            let nodes = self.classes[&id].nodes.iter().map(|n| self.canonical(n)).filter(|n| seen.insert(n.clone())).collect();
+            self.classes.get_mut(&id).unwrap().nodes = nodes;
+        }
+    }
+
+    fn merge_congruent(&mut self) {
+        loop {
+            let mut memo: HashMap<Node, Id> = HashMap::new();
+            let mut congruent = Vec::new();
+            for (&id, class) in &self.classes {
+                for node in &class.nodes {
+                    if let Some(other) = memo.insert(self.canonical(node), id) {
+                        if other != id {
+                            congruent.push((other, id));
+                        }
+                    }
+                }
+            }
+
+            if congruent.is_empty() {
+                self.memo = memo;
+                return;
+            }
+            for (a, b) in congruent {
+                self.union(a, b);
+            }
+        }
+    }
+
+    fn ematch(&self, pattern: &Pattern, id: Id, subst: Subst) -> Vec<Subst> {
+        let id = self.find(id);
+        match pattern {
+            Pattern::Wildcard(name) => match subst.iter().find(|(n, _)| n == name) {
+                Some(&(_, bound)) if self.find(bound) == id => vec![subst],
+                Some(_) => vec![],
+                None => {
+                    let mut subst = subst;
+                    subst.push((name.clone(), id));
+                    vec![subst]
+                },
+            },
+            Pattern::Node(op, children) => self.classes[&id].nodes
+                .iter()
+                .filter(|node| node.op == *op && node.children.len() == children.len())
+                .flat_map(|node| children.iter()
+                    .zip(&node.children)
+                    .fold(vec![subst.clone()], |substs, (p, &c)| substs.into_iter().flat_map(|s| self.ematch(p, c, s)).collect()))
+                .collect(),
+        }
+    }
+
+    fn instantiate(&mut self, pattern: &Pattern, subst: &Subst) -> Id {
+        match pattern {
+            Pattern::Wildcard(name) => subst.iter().find(|(n, _)| n == name).map(|&(_, id)| id).unwrap(),
+            Pattern::Node(op, children) => {
+                let children = children.iter().map(|p| self.instantiate(p, subst)).collect();
+                self.add(Node { op: op.clone(), children })
+            },
+        }
+    }
+
+    /// Applies `rules` until nothing changes or a limit is reached.
+    fn saturate(&mut self, rules: &[(Pattern, Pattern)], limits: &Limits) {
+        let start = Instant::now();
+        for _ in 0..limits.iterations {
+            let mut matches = Vec::new();
+            for (i, (lhs, _)) in rules.iter().enumerate() {
+                for &id in self.classes.keys() {
+                    matches.extend(self.ematch(lhs, id, Vec::new()).into_iter().map(|s| (i, id, s)));
+                }
+            }
+
+            let size = self.node_count();
+            let mut changed = false;
+            for (i, id, subst) in matches {
+                let rewritten = self.instantiate(&rules[i].1, &subst);
+                changed |= self.union(id, rewritten);
+                if self.node_count() > limits.nodes || start.elapsed() > limits.time {
+                    break;
+                }
+            }
+            self.rebuild();
+
+            if !changed && self.node_count() == size || self.node_count() > limits.nodes || start.elapsed() > limits.time {
+                break;
+            }
+        }
+    }
+
+    fn is_radical(&self, node: &Node) -> bool {
+        match &node.op {
+            Op::Other(i) => matches!(self.shapes[*i], Expr::Root { .. }),
+            Op::Pow => self.constant(node.children[1]).is_some_and(|e| !e.is_integer()),
+            _ => false,
+        }
+    }
+
+    /// The cost of each class's cheapest node, as the measure `cost` ranks by and then the node count.
+    fn costs(&self, cost: Cost) -> HashMap<Id, ((usize, usize), Node)> {
+        let mut best: HashMap<Id, ((usize, usize), Node)> = HashMap::new();
+        loop {
+            let mut changed = false;
+            for (&id, class) in &self.classes {
+                for node in &class.nodes {
+                    let Some(children) = node.children.iter().map(|c| best.get(&self.find(*c)).map(|(k, _)| *k)).collect::<Option<Vec<_>>>() else { continue };
+                    let size = 1 + children.iter().map(|k| k.1).sum::<usize>();
+                    let primary = match cost {
+                        Cost::Size => size,
+                        Cost::Depth => 1 + children.iter().map(|k| k.0).max().unwrap_or(0),
+                        Cost::Radicals => self.is_radical(node) as usize + children.iter().map(|k| k.0).sum::<usize>(),
+                    };
+                    if best.get(&id).is_none_or(|(k, _)| (primary, size) < *k) {
+                        best.insert(id, ((primary, size), node.clone()));
+                        changed = true;
+                    }
+                }
+            }
+            if !changed {
+                return best;
+            }
+        }
+    }
+
+    fn extract(&self, id: Id, best: &HashMap<Id, ((usize, usize), Node)>) -> Expr {
+        let node = &best[&self.find(id)].1;
+        let mut children = node.children.iter().map(|&c| self.extract(c, best));
+        let mut child = || children.next().unwrap().boxed();
+        match &node.op {
+            Op::Integer(n) => Expr::integer(n.clone()),
+            Op::Decimal(bits) => Expr::decimal(f64::from_bits(*bits)),
+            Op::Variable(name) => Expr::variable(name.clone()),
+            Op::Neg => Expr::negation(child()),
+            Op::Add => Expr::sum(child(), child()),
+            Op::Mul => Expr::product(child(), child()),
+            Op::Div => Expr::ratio(child(), child()),
+            Op::Pow => Expr::power(child(), child()),
+            Op::Other(i) => self.shapes[*i].clone().map_children(|_| child().into_inner()),
+        }
+    }
+}
+
+/// The cost of an expression as ranked by `cost`, along with its node count.
+fn measure(expr: &Expr, cost: Cost) -> (usize, usize) {
+    let children: Vec<(usize, usize)> = expr.children().into_iter().map(|x| measure(x, cost)).collect();
+    let size = 1 + children.iter().map(|k| k.1).sum::<usize>();
+    let is_radical = match expr {
+        Expr::Root { .. } => true,
+        Expr::Power { exp, .. } => Number::from_expr(exp).is_some_and(|e| !matches!(e, Number::Rational(r) if r.is_integer())),
+        _ => false,
+    };
+    let primary = match cost {
+        Cost::Size => size,
+        Cost::Depth => 1 + children.iter().map(|k| k.0).max().unwrap_or(0),
+        Cost::Radicals => is_radical as usize + children.iter().map(|k| k.0).sum::<usize>(),
+    };
+    (primary, size)
+}
+
+fn parse(src: &str) -> Expr {
+    Lexer::new(src).tokenize().ok()
+        .and_then(|tokens| Parser::new(tokens).parse().pop()?.ok())
+        .and_then(|node| Expr::convert(node, &Session::default()).ok())
+        .unwrap_or_else(|| panic!("invalid builtin rule '{}'", src))
+}
+
+fn builtin_rules() -> Vec<(Expr, Expr)> {
+    thread_local! {
+        static RULES_PARSED: Vec<(Expr, Expr)> = RULES.iter().map(|(lhs, rhs)| (parse(lhs), parse(rhs))).collect();
+    }
+    RULES_PARSED.with(Vec::clone)
+}
+
+/// Finds the cheapest form of an expression according to `cost`, among those reachable from any of
+/// the equivalent `forms` by the builtin rewrites and the user's `rules`.
+pub fn simplify(forms: &[Expr], rules: &[Rule], cost: Cost, limits: &Limits) -> Expr {
+    let mut egraph = EGraph::default();
+    let root = egraph.add_expr(&forms[0]);
+    for form in &forms[1..] {
+        let id = egraph.add_expr(form);
+        egraph.union(root, id);
+    }
+    egraph.rebuild();
+
+    let builtin = builtin_rules();
+    let rules: Vec<(Pattern, Pattern)> = builtin
+        .iter()
+        .map(|(lhs, rhs)| (lhs, rhs))
+        .chain(rules.iter().map(Rule::sides))
+        .map(|(lhs, rhs)| {
+            let wildcards = lhs.variables();
+            (egraph.pattern(lhs, &wildcards), egraph.pattern(rhs, &wildcards))
+        })
+        .collect();
+    egraph.saturate(&rules, limits);
+
+    // Extracted forms can leave terms in an unusual order, like `y2`, which the greedy passes tidy up.
+    let extracted = egraph.extract(root, &egraph.costs(cost));
+    let tidied = extracted.clone().simplify();
+    if measure(&tidied, cost) <= measure(&extracted, cost) { tidied } else { extracted }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{builtin_rules, measure, simplify, Cost, Limits};
+    use crate::{expr::rules::Rule, testing::parse};
+
+    /// Limits without a time limit, which would make the results depend on the load.
+    fn limits() -> Limits {
+        Limits { nodes: 2_000, iterations: 10, time: Duration::MAX }
+    }
+
+    fn cheapest(input: &str, cost: Cost) -> String {
+        format!("{:#}", simplify(&[parse(input)], &[], cost, &limits()))
+    }
+
+    #[test]
+    fn builtin_rules_parse() {
+        assert_eq!(builtin_rules().len(), super::RULES.len());
+    }
+
+    #[test]
+    fn extraction() {
+        assert_eq!(cheapest("x*y + x*z", Cost::Size), "x(y + z)");
+        assert_eq!(cheapest("exp[a]*exp[b]", Cost::Size), "exp[(a + b)]");
+        assert_eq!(cheapest("sin[t]/cos[t]", Cost::Size), "tan[t]");
+        assert_eq!(cheapest("sqrt[u]^2 + 0", Cost::Radicals), "u");
+        // Which of the balanced sums comes out depends on the order classes are visited in.
+        let balanced = simplify(&[parse("((a + b) + c) + d")], &[], Cost::Depth, &limits());
+        assert_eq!(measure(&balanced, Cost::Depth), (3, 7), "{}", balanced);
+    }
+
+    #[test]
+    fn equivalent_forms_and_user_rules_are_searched() {
+        let forms = [parse("x^2 + 2x + 1"), parse("(x + 1)^2")];
+        assert_eq!(format!("{:#}", simplify(&forms, &[], Cost::Size, &limits())), "((x + 1) ^ 2)");
+        let Some(rule) = Rule::new(parse("f[a]"), parse("a")) else { unreachable!() };
+        assert_eq!(format!("{:#}", simplify(&[parse("f[y] + y")], &[rule], Cost::Size, &limits())), "2y");
+    }
+}
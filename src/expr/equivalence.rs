@@ -0,0 +1,215 @@
+use num_bigint::BigInt;
+use num_rational::BigRational;
+
+use super::{assumptions::{Assumptions, Sign}, matrix, number::Number, polynomial, Expr};
+
+
+/// Agreeing sample points needed before two expressions are taken to be equal.
+const SAMPLES: usize = 24;
+
+/// Points drawn before giving up on finding enough samples, as some fall outside the domain or the
+/// assumptions.
+const MAX_DRAWS: usize = 400;
+
+/// Relative difference below which two floats are taken to be equal, so that floats near zero are
+/// only equal to zero itself.
+const TOLERANCE: f64 = 1e-9;
+
+/// Whether two expressions are equal.
+#[derive(Clone, PartialEq)]
+pub enum Equivalence {
+    Equal,
+    /// Not equal, with values of the variables where they differ when there are any.
+    NotEqual(Vec<(String, Expr)>),
+    Unknown,
+}
+
+/// A value of an expression at a sample point.
+enum Value {
+    Exact(BigRational),
+    Float(f64),
+}
+
+impl Value {
+    fn of(expr: &Expr) -> Option<Self> {
+        match Number::from_expr(expr) {
+            Some(Number::Rational(r)) => Some(Value::Exact(r)),
+            _ => expr.to_f64().filter(|v| v.is_finite()).map(Value::Float),
+        }
+    }
+
+    fn to_f64(&self) -> f64 {
+        match self {
+            Value::Exact(r) => Number::Rational(r.clone()).to_f64(),
+            Value::Float(v) => *v,
+        }
+    }
+
+    fn agrees(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Exact(a), Value::Exact(b)) => a == b,
+            (a, b) => {
+                let (a, b) = (a.to_f64(), b.to_f64());
+                (a - b).abs() <= TOLERANCE * a.abs().max(b.abs())
+            },
+        }
+    }
+}
+
+/// A small linear congruential generator, so the same points are tried every run.
+struct Lcg(u64);
+
+impl Lcg {
+    fn next(&mut self, below: u64) -> u64 {
+        self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        (self.0 >> 33) % below
+    }
+
+    /// A small integer in `-10..=10`.
+    fn integer(&mut self) -> BigInt {
+        BigInt::from(self.next(21) as i64 - 10)
+    }
+
+    /// A rational `p / q` with `|p| <= 30` and `q <= 7`, so that exact arithmetic stays cheap.
+    fn rational(&mut self) -> BigRational {
+        BigRational::new(BigInt::from(self.next(61) as i64 - 30), BigInt::from(self.next(7) + 1))
+    }
+}
+
+fn canonical(expr: &Expr, assumptions: &Assumptions) -> Expr {
+    polynomial::expand(&expr.clone().simplify().refine(assumptions).simplify())
+}
+
+impl Expr {
+    /// Decides whether the expression equals `other` for every value of its variables allowed by
+    /// `assumptions`.
+    ///
+    /// Canonical simplified forms are compared first, then their difference if it is a number, and
+    /// failing that both are evaluated at pseudo-random points, exactly wherever the values are
+    /// rational. Agreement at every point is taken as equality.
+    pub fn equivalent(&self, other: &Expr, assumptions: &Assumptions) -> Equivalence {
+        let (a, b) = (canonical(self, assumptions), canonical(other, assumptions));
+        if a == b {
+            return Equivalence::Equal;
+        }
+
+        if !matrix::is_scalar(&a) || !matrix::is_scalar(&b) {
+            return match (a, b) {
+                (Expr::Matrix { rows: x }, Expr::Matrix { rows: y }) if matrix::dims(&x) == matrix::dims(&y) => x.iter()
+                    .flatten()
+                    .zip(y.iter().flatten())
+                    .map(|(x, y)| x.equivalent(y, assumptions))
+                    .fold(Equivalence::Equal, |acc, e| match (acc, e) {
+                        (Equivalence::NotEqual(point), _) | (_, Equivalence::NotEqual(point)) => Equivalence::NotEqual(point),
+                        (Equivalence::Equal, Equivalence::Equal) => Equivalence::Equal,
+                        _ => Equivalence::Unknown,
+                    }),
+                (Expr::Matrix { .. }, Expr::Matrix { .. }) => Equivalence::NotEqual(Vec::new()),
+                _ => Equivalence::Unknown,
+            };
+        }
+
+        let difference = canonical(&Expr::difference(a.clone().boxed(), b.clone().boxed()), assumptions);
+        // A constant difference decides it, however small, where sampling would take it for rounding.
+        if let Some(d) = Number::from_expr(&difference) {
+            return match d.is_zero() {
+                true => Equivalence::Equal,
+                false => Equivalence::NotEqual(Vec::new()),
+            };
+        }
+
+        // So does one without variables that can be bounded away from zero, where both sides would
+        // agree to within the tolerance when sampled.
+        if difference.variables().is_empty() {
+            let sign = assumptions.sign(&difference);
+            if sign.is_some_and(Sign::is_strict) || difference.enclose(&[]).is_some_and(|d| d.lo() > 0.0 || d.hi() < 0.0) {
+                return Equivalence::NotEqual(Vec::new());
+            }
+            if matches!(sign, Some(Sign::Zero)) {
+                return Equivalence::Equal;
+            }
+        }
+
+        let vars = Expr::difference(a.clone().boxed(), b.clone().boxed()).variables();
+
+        let mut random = Lcg(vars.len() as u64 + 1);
+        let mut agreeing = 0;
+        for _ in 0..MAX_DRAWS {
+            let point: Vec<(String, Expr)> = vars.iter()
+                .map(|var| {
+                    let value = if assumptions.is_integer(&Expr::variable(var.clone())) {
+                        Expr::integer(random.integer())
+                    } else {
+                        Number::Rational(random.rational()).into_expr()
+                    };
+                    (var.clone(), value)
+                })
+                .collect();
+            if !assumptions.allows(&point) {
+                continue;
+            }
+
+            let at = |expr: &Expr| Value::of(&point.iter().fold(expr.clone(), |e, (var, value)| e.substitute(var, value)).simplify());
+            let (Some(x), Some(y)) = (at(&a), at(&b)) else { continue };
+            if !x.agrees(&y) {
+                return Equivalence::NotEqual(point);
+            }
+
+            agreeing += 1;
+            if agreeing == SAMPLES || vars.is_empty() {
+                return Equivalence::Equal;
+            }
+        }
+
+        Equivalence::Unknown
+    }
+}
+
+impl std::fmt::Display for Equivalence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Equivalence::Equal => write!(f, "equal"),
+            Equivalence::NotEqual(point) if point.is_empty() => write!(f, "not equal"),
+            Equivalence::NotEqual(point) => write!(
+                f,
+                "not equal (counterexample {})",
+                point.iter().map(|(var, value)| format!("{} = {}", var, value)).collect::<Vec<_>>().join(", "),
+            ),
+            Equivalence::Unknown => write!(f, "unknown"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{session::Session, testing::{eval, eval_in}};
+
+    #[test]
+    fn identities_are_equal() {
+        assert_eq!(eval("equiv[(x+1)^2, x^2+2x+1]"), "equal");
+        assert_eq!(eval("equiv[sin[x]^2+cos[x]^2, 1]"), "equal");
+        assert_eq!(eval("equiv[sin[2x], 2sin[x]cos[x]]"), "equal");
+        assert_eq!(eval("equiv[exp[x]exp[-x], 1]"), "equal");
+        assert_eq!(eval("equiv[ln[6], ln[2]+ln[3]]"), "equal");
+    }
+
+    #[test]
+    fn differences_are_found() {
+        assert_eq!(eval("equiv[x, x+1]"), "not equal");
+        assert_eq!(eval("equiv[sin[x], 0]"), "not equal (counterexample x = (-23 / 2))");
+        assert_eq!(eval("equiv[mat[[1,2]], mat[[1,3]]]"), "not equal");
+    }
+
+    #[test]
+    fn tiny_constant_differences_are_not_rounding() {
+        assert_eq!(eval("equiv[exp[-40], 0]"), "not equal");
+        assert_eq!(eval("equiv[x + exp[-30], x]"), "not equal");
+    }
+
+    #[test]
+    fn assumptions_restrict_the_samples() {
+        let mut session = Session::new();
+        eval_in(&mut session, "assume[x >= 0]");
+        assert_eq!(eval_in(&mut session, "equiv[abs[x], x]"), "equal");
+    }
+}
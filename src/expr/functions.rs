@@ -0,0 +1,92 @@
+use num_traits::{One, Signed, Zero};
+
+use super::{number::Number, Expr};
+
+
+/// Functions with a known definition, as opposed to user functions that only ever appear symbolically.
+pub const ELEMENTARY: [&str; 11] = ["sin", "cos", "tan", "exp", "ln", "sinh", "cosh", "atan", "asin", "acos", "abs"];
+
+pub fn is_elementary(name: &str) -> bool {
+    ELEMENTARY.contains(&name)
+}
+
+fn call(name: &str, arg: Expr) -> Expr {
+    Expr::function(name.to_string(), vec![arg])
+}
+
+/// The float implementation of an elementary function.
+pub fn float(name: &str) -> Option<fn(f64) -> f64> {
+    Some(match name {
+        "sin" => f64::sin,
+        "cos" => f64::cos,
+        "tan" => f64::tan,
+        "exp" => f64::exp,
+        "ln" => f64::ln,
+        "sinh" => f64::sinh,
+        "cosh" => f64::cosh,
+        "atan" => f64::atan,
+        "asin" => f64::asin,
+        "acos" => f64::acos,
+        "abs" => f64::abs,
+        _ => return None,
+    })
+}
+
+pub fn eval(name: &str, x: f64) -> Option<f64> {
+    float(name).map(|f| f(x))
+}
+
+/// Exact values at special points and cancellations against the inverse function, like `sin[0]` or
+/// `exp[ln[x]]`.
+pub fn exact(name: &str, arg: &Expr) -> Option<Expr> {
+    let zero = matches!(arg, Expr::Integer(n) if n.is_zero());
+    let one = matches!(arg, Expr::Integer(n) if n.is_one());
+
+    match (name, arg) {
+        ("sin" | "tan" | "sinh" | "atan" | "asin", _) if zero => Some(Expr::integer(0.into())),
+        ("cos" | "cosh" | "exp", _) if zero => Some(Expr::integer(1.into())),
+        ("ln" | "acos", _) if one => Some(Expr::integer(0.into())),
+        ("exp", Expr::Function { name: inner, args }) if inner == "ln" => Some(args[0].clone()),
+        ("ln", Expr::Function { name: inner, args }) if inner == "exp" => Some(args[0].clone()),
+        ("sin" | "tan" | "sinh" | "atan" | "asin", Expr::Negation(x)) => Some(Expr::negation(call(name, x.clone().into_inner()).boxed())),
+        ("cos" | "cosh" | "abs", Expr::Negation(x)) => Some(call(name, x.clone().into_inner())),
+        ("abs", Expr::Function { name: inner, .. }) if inner == "abs" => Some(arg.clone()),
+        ("abs", _) => match Number::from_expr(arg)? {
+            Number::Rational(r) => Some(Number::Rational(r.abs()).into_expr()),
+            Number::Decimal(v) => Some(Expr::decimal(v.abs())),
+        },
+        _ => None,
+    }
+}
+
+/// The derivative of an elementary function, evaluated at `arg`.
+pub fn derivative(name: &str, arg: &Expr) -> Option<Expr> {
+    let one = || Expr::integer(1.into());
+    let arg = || arg.clone().boxed();
+
+    Some(match name {
+        "sin" => call("cos", arg().into_inner()),
+        "cos" => Expr::negation(call("sin", arg().into_inner()).boxed()),
+        "tan" => Expr::ratio(one().boxed(), Expr::power(call("cos", arg().into_inner()).boxed(), Expr::integer(2.into()).boxed()).boxed()),
+        "exp" => call("exp", arg().into_inner()),
+        "ln" => Expr::ratio(one().boxed(), arg()),
+        "sinh" => call("cosh", arg().into_inner()),
+        "cosh" => call("sinh", arg().into_inner()),
+        "atan" => Expr::ratio(
+            one().boxed(),
+            Expr::sum(one().boxed(), Expr::power(arg(), Expr::integer(2.into()).boxed()).boxed()).boxed(),
+        ),
+        "asin" | "acos" => {
+            let d = Expr::ratio(
+                one().boxed(),
+                Expr::root(
+                    Expr::integer(2.into()).boxed(),
+                    Expr::difference(one().boxed(), Expr::power(arg(), Expr::integer(2.into()).boxed()).boxed()).boxed(),
+                ).boxed(),
+            );
+            if name == "asin" { d } else { Expr::negation(d.boxed()) }
+        },
+        "abs" => Expr::ratio(call("abs", arg().into_inner()).boxed(), arg()),
+        _ => return None,
+    })
+}
@@ -0,0 +1,161 @@
+use std::{cell::{OnceCell, RefCell}, collections::HashMap, hash::{BuildHasher, Hash, Hasher, RandomState}, rc::{Rc, Weak}};
+
+use super::{trace, Expr};
+
+
+/// The table is swept of expressions that are no longer alive once it holds this many more than
+/// after the last sweep.
+const SWEEP_THRESHOLD: usize = 4096;
+
+struct Entry {
+    expr: Expr,
+    /// The simplified form, filled in the first time it is asked for.
+    simplified: OnceCell<Expr>,
+}
+
+/// A shared subexpression, hash-consed so that equal subexpressions alive at the same time are the
+/// same allocation and compare by address.
+#[derive(Clone)]
+pub struct Interned(Rc<Entry>);
+
+/// Every interned expression still alive, bucketed by hash.
+#[derive(Default)]
+struct Table {
+    buckets: HashMap<u64, Vec<Weak<Entry>>>,
+    hasher: RandomState,
+    len: usize,
+    swept_len: usize,
+}
+
+thread_local! {
+    static TABLE: RefCell<Table> = RefCell::new(Table::default());
+}
+
+impl Table {
+    fn sweep(&mut self) {
+        self.buckets.retain(|_, bucket| {
+            bucket.retain(|entry| entry.strong_count() > 0);
+            !bucket.is_empty()
+        });
+        self.len = self.buckets.values().map(Vec::len).sum();
+        self.swept_len = self.len;
+    }
+}
+
+impl Interned {
+    pub fn new(expr: Expr) -> Self {
+        TABLE.with(|table| {
+            let mut table = table.borrow_mut();
+            let hash = table.hasher.hash_one(&expr);
+            if let Some(entry) = table.buckets.get(&hash).and_then(|bucket| bucket.iter().filter_map(Weak::upgrade).find(|e| e.expr == expr)) {
+                return Interned(entry);
+            }
+
+            let entry = Rc::new(Entry { expr, simplified: OnceCell::new() });
+            table.buckets.entry(hash).or_default().push(Rc::downgrade(&entry));
+            table.len += 1;
+            if table.len > table.swept_len + SWEEP_THRESHOLD {
+                table.sweep();
+            }
+            Interned(entry)
+        })
+    }
+
+    /// A copy of the expression, sharing its children.
+    pub fn into_inner(self) -> Expr {
+        self.0.expr.clone()
+    }
+
+    /// Simplifies the expression, reusing the result from the last time it was simplified.
+    ///
+    /// Results are not reused while a derivation is being traced, so that each step is recorded.
+    pub fn simplify(&self) -> Expr {
+        if trace::is_recording() {
+            return self.0.expr.clone().simplify();
+        }
+        self.0.simplified.get_or_init(|| self.0.expr.clone().simplify()).clone()
+    }
+}
+
+impl std::ops::Deref for Interned {
+    type Target = Expr;
+
+    fn deref(&self) -> &Expr {
+        &self.0.expr
+    }
+}
+
+impl AsRef<Expr> for Interned {
+    fn as_ref(&self) -> &Expr {
+        &self.0.expr
+    }
+}
+
+impl PartialEq for Interned {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl std::fmt::Display for Interned {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.expr.fmt(f)
+    }
+}
+
+/// Hashes the root of the expression along with the addresses of its children, which are interned,
+/// so hashing does not walk the whole tree.
+impl Hash for Expr {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            Expr::Integer(n) => n.hash(state),
+            Expr::Decimal(v) => if *v == 0.0 { 0 } else { v.to_bits() }.hash(state),
+            Expr::Variable(name) => name.hash(state),
+            Expr::Boolean(b) => b.hash(state),
+            Expr::Factorization { negative, factors } => (negative, factors).hash(state),
+            Expr::IndexedSum { var, .. } | Expr::IndexedProduct { var, .. } => var.hash(state),
+            // Arguments and entries are not interned, so only their number is hashed.
+            Expr::Function { name, args } => return (name, args.len()).hash(state),
+            Expr::Derivative { name, order, args } => return (name, order, args.len()).hash(state),
+            Expr::Matrix { rows } => return super::matrix::dims(rows).hash(state),
+            _ => (),
+        }
+        for child in self.children() {
+            std::ptr::hash(child, state);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use num_bigint::BigInt;
+
+    use super::{Interned, TABLE};
+    use crate::{expr::Expr, testing::parse};
+
+    #[test]
+    fn equal_expressions_share_an_allocation() {
+        let (a, b) = (Interned::new(parse("sin[x] + 1")), Interned::new(parse("sin[x] + 1")));
+        assert!(Rc::ptr_eq(&a.0, &b.0));
+        assert!(a != Interned::new(parse("sin[x] + 2")));
+    }
+
+    #[test]
+    fn simplified_forms_are_reused() {
+        let a = Interned::new(parse("2 + 3"));
+        assert_eq!(a.simplify().to_string(), "5");
+        assert!(a.0.simplified.get().is_some());
+        assert_eq!(Interned::new(parse("2 + 3")).0.simplified.get().map(ToString::to_string), Some("5".to_string()));
+    }
+
+    #[test]
+    fn dead_expressions_are_swept() {
+        for n in 0..3 * super::SWEEP_THRESHOLD {
+            drop(Interned::new(Expr::Integer(BigInt::from(n))));
+        }
+        TABLE.with(|table| assert!(table.borrow().len <= 2 * super::SWEEP_THRESHOLD));
+    }
+}
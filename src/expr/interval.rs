@@ -0,0 +1,422 @@
+use std::f64::consts::{FRAC_PI_2, PI, TAU};
+
+use num_bigint::BigInt;
+use num_traits::{FromPrimitive, Signed, ToPrimitive};
+
+use super::{functions, number::Number, Expr};
+
+
+/// Units in the last place by which the results of library functions are widened, as unlike
+/// arithmetic and square roots they are not guaranteed to be correctly rounded.
+const FUNCTION_ULPS: u32 = 4;
+
+/// Magnitude beyond which the phase of a periodic function is not worth tracking, as the spacing of
+/// floats there is a sizeable part of a period.
+const MAX_PHASE: f64 = 1e15;
+
+/// Results smaller than this may have lost bits to underflow, so their rounding error is not known
+/// exactly.
+const MIN_EXACT_ERROR: f64 = 1e-290;
+
+/// A closed interval of reals, possibly unbounded, like `[1, 2]`.
+#[derive(Clone, Copy, PartialEq)]
+pub struct Interval {
+    lo: f64,
+    hi: f64,
+}
+
+/// The bounds of a rounded result `r`, given an error whose sign is that of the exact result minus
+/// `r`.
+fn bounds(r: f64, error: f64) -> (f64, f64) {
+    match r {
+        f64::INFINITY => (f64::MAX, f64::INFINITY),
+        f64::NEG_INFINITY => (f64::NEG_INFINITY, f64::MIN),
+        _ if error > 0.0 => (r, r.next_up()),
+        _ if error < 0.0 => (r.next_down(), r),
+        _ if error == 0.0 => (r, r),
+        _ => (r.next_down(), r.next_up()),
+    }
+}
+
+fn add(a: f64, b: f64) -> (f64, f64) {
+    let s = a + b;
+    let b_part = s - a;
+    bounds(s, (a - (s - b_part)) + (b - b_part))
+}
+
+/// The product, taking zero times infinity to be zero as an endpoint only stands for finite values
+/// approaching it.
+fn mul(a: f64, b: f64) -> (f64, f64) {
+    if a == 0.0 || b == 0.0 {
+        return (0.0, 0.0);
+    }
+    let p = a * b;
+    match p.abs() < MIN_EXACT_ERROR {
+        true => (p.next_down(), p.next_up()),
+        false => bounds(p, a.mul_add(b, -p)),
+    }
+}
+
+fn div(a: f64, b: f64) -> (f64, f64) {
+    let q = a / b;
+    if a == 0.0 || q.is_infinite() || a.is_infinite() || b.is_infinite() {
+        return bounds(q, 0.0);
+    }
+    match q.abs() < MIN_EXACT_ERROR {
+        true => (q.next_down(), q.next_up()),
+        false => bounds(q, (-q).mul_add(b, a) * b.signum()),
+    }
+}
+
+/// `x ^ n` for `x >= 0`, rounded down or up.
+fn pow(x: f64, n: u64, up: bool) -> f64 {
+    let pick = |(lo, hi): (f64, f64)| if up { hi } else { lo };
+    let (mut result, mut base, mut n) = (1.0, x, n);
+    while n > 0 {
+        if n % 2 == 1 {
+            result = pick(mul(result, base));
+        }
+        base = pick(mul(base, base));
+        n /= 2;
+    }
+    result
+}
+
+/// The `n`th root of `x >= 0`, rounded down or up, checked by raising it back to the power `n`.
+fn root(x: f64, n: u64, up: bool) -> f64 {
+    if x == 0.0 || x.is_infinite() {
+        return x;
+    }
+    let mut r = x.powf((n as f64).recip());
+    if up {
+        while pow(r, n, false) < x {
+            r = r.next_up();
+        }
+    } else {
+        while pow(r, n, true) > x {
+            r = r.next_down();
+        }
+    }
+    r
+}
+
+/// Evaluates an elementary function at a point, widened to account for its rounding unless the
+/// result is exact.
+fn eval(name: &str, x: f64) -> (f64, f64) {
+    let v = functions::eval(name, x).unwrap();
+    let exact = name == "abs"
+        || (x == 0.0 && matches!(name, "sin" | "tan" | "sinh" | "atan" | "asin" | "cos" | "cosh" | "exp"))
+        || (x == 1.0 && matches!(name, "ln" | "acos"));
+    if exact || v.is_nan() {
+        return (v, v);
+    }
+    (0..FUNCTION_ULPS).fold((v, v), |(lo, hi), _| (lo.next_down(), hi.next_up()))
+}
+
+/// Whether a point of the form `offset + k * period` may lie in `x`, allowing for the rounding of
+/// the point.
+fn hits(x: Interval, offset: f64, period: f64) -> bool {
+    let k = ((x.lo - offset) / period).floor();
+    (-1..=2).map(|i| offset + (k + i as f64) * period).any(|point| {
+        let slack = 4.0 * f64::EPSILON * point.abs().max(1.0);
+        point + slack >= x.lo && point - slack <= x.hi
+    })
+}
+
+impl Interval {
+    /// The interval from `lo` to `hi`, or `None` if it is empty.
+    pub fn new(lo: f64, hi: f64) -> Option<Self> {
+        (lo <= hi).then_some(Self { lo, hi })
+    }
+
+    pub fn lo(&self) -> f64 {
+        self.lo
+    }
+
+    pub fn hi(&self) -> f64 {
+        self.hi
+    }
+
+    fn point(v: f64) -> Self {
+        Self { lo: v, hi: v }
+    }
+
+    fn entire() -> Self {
+        Self { lo: f64::NEG_INFINITY, hi: f64::INFINITY }
+    }
+
+    /// The smallest interval containing the integer.
+    fn integer(n: &BigInt) -> Self {
+        let v = n.to_f64().unwrap_or(f64::NAN);
+        match BigInt::from_f64(v) {
+            Some(m) if m == *n => Self::point(v),
+            Some(m) if m < *n => Self { lo: v, hi: v.next_up() },
+            Some(_) => Self { lo: v.next_down(), hi: v },
+            None => Self::from(bounds(v, f64::NAN)),
+        }
+    }
+
+    fn contains_zero(&self) -> bool {
+        self.lo <= 0.0 && self.hi >= 0.0
+    }
+
+    /// The smallest interval containing both.
+    fn hull(self, other: Self) -> Self {
+        Self { lo: self.lo.min(other.lo), hi: self.hi.max(other.hi) }
+    }
+
+    /// The part of the interval inside `[lo, hi]`, or `None` if they do not meet.
+    fn clamp(self, lo: f64, hi: f64) -> Option<Self> {
+        Self::new(self.lo.max(lo), self.hi.min(hi))
+    }
+
+    fn abs(self) -> Self {
+        match self {
+            _ if self.lo >= 0.0 => self,
+            _ if self.hi <= 0.0 => -self,
+            _ => Self { lo: 0.0, hi: self.hi.max(-self.lo) },
+        }
+    }
+
+    /// Applies a function that increases over the interval to each end.
+    fn increasing(self, name: &str) -> Self {
+        Self { lo: eval(name, self.lo).0, hi: eval(name, self.hi).1 }
+    }
+
+    fn decreasing(self, name: &str) -> Self {
+        Self { lo: eval(name, self.hi).0, hi: eval(name, self.lo).1 }
+    }
+
+    /// The reciprocal, which is unbounded on the side of zero when the interval touches it, or
+    /// `None` for the interval holding only zero.
+    fn recip(self) -> Option<Self> {
+        Some(match self {
+            _ if self.lo == 0.0 && self.hi == 0.0 => return None,
+            _ if self.lo < 0.0 && self.hi > 0.0 => Self::entire(),
+            _ if self.lo == 0.0 => Self { lo: div(1.0, self.hi).0, hi: f64::INFINITY },
+            _ if self.hi == 0.0 => Self { lo: f64::NEG_INFINITY, hi: div(1.0, self.lo).1 },
+            _ => Self { lo: div(1.0, self.hi).0, hi: div(1.0, self.lo).1 },
+        })
+    }
+
+    /// The quotient, or `None` when dividing by the interval holding only zero.
+    fn div(self, other: Self) -> Option<Self> {
+        if other.contains_zero() {
+            return Some(self * other.recip()?);
+        }
+
+        let quotients = [div(self.lo, other.lo), div(self.lo, other.hi), div(self.hi, other.lo), div(self.hi, other.hi)];
+        if quotients.iter().any(|(lo, hi)| lo.is_nan() || hi.is_nan()) {
+            return Some(Self::entire());
+        }
+        Some(Self {
+            lo: quotients.iter().map(|q| q.0).fold(f64::INFINITY, f64::min),
+            hi: quotients.iter().map(|q| q.1).fold(f64::NEG_INFINITY, f64::max),
+        })
+    }
+
+    /// Raises the interval to an integer power.
+    fn powi(self, n: &BigInt) -> Option<Self> {
+        let k = n.magnitude().to_u64()?;
+        let power = if k % 2 == 0 {
+            let x = self.abs();
+            Self { lo: pow(x.lo, k, false), hi: pow(x.hi, k, true) }
+        } else {
+            let signed = |v: f64, up: bool| if v >= 0.0 { pow(v, k, up) } else { -pow(-v, k, !up) };
+            Self { lo: signed(self.lo, false), hi: signed(self.hi, true) }
+        };
+        match n.is_negative() {
+            true => power.recip(),
+            false => Some(power),
+        }
+    }
+
+    /// Raises the non-negative part of the interval to any power, as `exp[exp * ln[self]]`.
+    fn pow(self, exp: Self) -> Option<Self> {
+        let base = self.clamp(0.0, f64::INFINITY)?;
+        if base.hi == 0.0 {
+            return (exp.lo > 0.0).then_some(Self::point(0.0));
+        }
+        (exp * base.function("ln")?).function("exp")
+    }
+
+    /// The `n`th root, keeping the sign for odd `n` and taking the non-negative part of the interval
+    /// for even `n`.
+    fn root(self, n: &BigInt) -> Option<Self> {
+        let n = n.to_u64().filter(|&n| n > 0)?;
+        let signed = |v: f64, up: bool| if v >= 0.0 { root(v, n, up) } else { -root(-v, n, !up) };
+        let x = if n % 2 == 0 { self.clamp(0.0, f64::INFINITY)? } else { self };
+        Some(Self { lo: signed(x.lo, false), hi: signed(x.hi, true) })
+    }
+
+    /// Evaluates the elementary function `name`, or returns `None` if the interval lies outside its
+    /// domain.
+    fn function(self, name: &str) -> Option<Self> {
+        Some(match name {
+            "sin" | "cos" => {
+                let offset = if name == "sin" { FRAC_PI_2 } else { 0.0 };
+                if self.hi - self.lo >= TAU || self.lo.abs().max(self.hi.abs()) > MAX_PHASE {
+                    return Some(Self { lo: -1.0, hi: 1.0 });
+                }
+                let mut y = Self::from(eval(name, self.lo)).hull(Self::from(eval(name, self.hi)));
+                if hits(self, offset, TAU) {
+                    y.hi = 1.0;
+                }
+                if hits(self, offset + PI, TAU) {
+                    y.lo = -1.0;
+                }
+                y.clamp(-1.0, 1.0)?
+            },
+            "tan" => {
+                if self.hi - self.lo >= PI || self.lo.abs().max(self.hi.abs()) > MAX_PHASE || hits(self, FRAC_PI_2, PI) {
+                    return Some(Self::entire());
+                }
+                self.increasing(name)
+            },
+            "exp" => self.increasing(name).clamp(0.0, f64::INFINITY)?,
+            "sinh" => self.increasing(name),
+            "cosh" => self.abs().increasing(name).clamp(1.0, f64::INFINITY)?,
+            "atan" => self.increasing(name).clamp(-FRAC_PI_2.next_up(), FRAC_PI_2.next_up())?,
+            "ln" => {
+                let x = self.clamp(0.0, f64::INFINITY).filter(|x| x.hi > 0.0)?;
+                Self { lo: if x.lo == 0.0 { f64::NEG_INFINITY } else { eval(name, x.lo).0 }, hi: eval(name, x.hi).1 }
+            },
+            "asin" => self.clamp(-1.0, 1.0)?.increasing(name),
+            "acos" => self.clamp(-1.0, 1.0)?.decreasing(name).clamp(0.0, PI.next_up())?,
+            "abs" => self.abs(),
+            _ => return None,
+        })
+    }
+}
+
+impl From<(f64, f64)> for Interval {
+    fn from((lo, hi): (f64, f64)) -> Self {
+        Self { lo, hi }
+    }
+}
+
+impl std::ops::Neg for Interval {
+    type Output = Interval;
+    fn neg(self) -> Interval {
+        Interval { lo: -self.hi, hi: -self.lo }
+    }
+}
+
+impl std::ops::Add for Interval {
+    type Output = Interval;
+    fn add(self, other: Interval) -> Interval {
+        Interval { lo: add(self.lo, other.lo).0, hi: add(self.hi, other.hi).1 }
+    }
+}
+
+impl std::ops::Sub for Interval {
+    type Output = Interval;
+    fn sub(self, other: Interval) -> Interval {
+        self + -other
+    }
+}
+
+impl std::ops::Mul for Interval {
+    type Output = Interval;
+    fn mul(self, other: Interval) -> Interval {
+        let products = [mul(self.lo, other.lo), mul(self.lo, other.hi), mul(self.hi, other.lo), mul(self.hi, other.hi)];
+        Interval {
+            lo: products.iter().map(|p| p.0).fold(f64::INFINITY, f64::min),
+            hi: products.iter().map(|p| p.1).fold(f64::NEG_INFINITY, f64::max),
+        }
+    }
+}
+
+impl Expr {
+    /// Bounds the values the expression takes while each variable ranges over its interval in
+    /// `vars`, with every rounding made outwards so that the result is guaranteed to contain them.
+    ///
+    /// Returns `None` if the expression has other variables, anything but arithmetic, roots, powers
+    /// and elementary functions, or is undefined over the whole of the intervals.
+    pub fn enclose(&self, vars: &[(String, Interval)]) -> Option<Interval> {
+        Some(match self {
+            Expr::Integer(n) => Interval::integer(n),
+            Expr::Decimal(v) if v.is_nan() => return None,
+            Expr::Decimal(v) => Interval::point(*v),
+            Expr::Factorization { .. } => match Number::from_expr(self)? {
+                Number::Rational(r) => Interval::integer(r.numer()).div(Interval::integer(r.denom()))?,
+                Number::Decimal(v) => Interval::point(v),
+            },
+            Expr::Variable(name) => vars.iter().find(|(n, _)| n == name)?.1,
+            Expr::Negation(x) => -x.enclose(vars)?,
+            Expr::Sum { left, right } => left.enclose(vars)? + right.enclose(vars)?,
+            Expr::Difference { left, right } => left.enclose(vars)? - right.enclose(vars)?,
+            Expr::Product { left, right } => left.enclose(vars)? * right.enclose(vars)?,
+            Expr::Ratio { numerator, denominator } => numerator.enclose(vars)?.div(denominator.enclose(vars)?)?,
+            Expr::Power { base, exp } => match Number::from_expr(exp) {
+                Some(Number::Rational(r)) => base.enclose(vars)?.root(r.denom())?.powi(r.numer())?,
+                Some(Number::Decimal(v)) if v.fract() == 0.0 => base.enclose(vars)?.powi(&BigInt::from_f64(v)?)?,
+                _ => base.enclose(vars)?.pow(exp.enclose(vars)?)?,
+            },
+            Expr::Root { index, radicand } => match Number::from_expr(index)? {
+                Number::Rational(r) if r.is_integer() => radicand.enclose(vars)?.root(r.numer())?,
+                _ => return None,
+            },
+            Expr::Function { name, args } if args.len() == 1 => args[0].enclose(vars)?.function(name)?,
+            _ => return None,
+        })
+    }
+}
+
+impl std::fmt::Display for Interval {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let end = |v: f64| match v {
+            f64::INFINITY => "∞".to_string(),
+            f64::NEG_INFINITY => "-∞".to_string(),
+            v => v.to_string(),
+        };
+        write!(f, "[{}, {}]", end(self.lo), end(self.hi))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Interval;
+    use crate::{session::Session, testing::{error, eval, eval_in, parse}};
+
+    fn over(input: &str, lo: f64, hi: f64) -> Interval {
+        let Some(bounds) = parse(input).simplify().enclose(&[("x".to_string(), Interval::new(lo, hi).unwrap())]) else {
+            panic!("cannot bound {}", input)
+        };
+        bounds
+    }
+
+    #[test]
+    fn enclosures_contain_the_exact_range() {
+        for (input, lo, hi, min, max) in [
+            ("exp[x]", 0.0, 1.0, 1.0, std::f64::consts::E),
+            ("sqrt[x]", 0.0, 2.0, 0.0, std::f64::consts::SQRT_2),
+            ("x^2 - x", 0.0, 1.0, -0.25, 0.0),
+            ("1/x", 1.0, 3.0, 1.0 / 3.0, 1.0),
+        ] {
+            let bounds = over(input, lo, hi);
+            assert!(bounds.lo() <= min && max <= bounds.hi(), "{} over [{}, {}]: [{}, {}]", input, lo, hi, bounds.lo(), bounds.hi());
+        }
+    }
+
+    #[test]
+    fn printed_bounds() {
+        assert_eq!(eval("interval:x:0:1[x^2 + 1]"), "[1, 2]");
+        assert_eq!(eval("interval:x:-1:2[x^2]"), "[0, 4]");
+        assert_eq!(eval("interval:x:0:1:y:1:2[x y]"), "[0, 2]");
+    }
+
+    #[test]
+    fn invalid_intervals() {
+        assert_eq!(error("interval:x:2:1[x]"), "the lower bound 2 is above the upper bound 1");
+        assert_eq!(error("interval:x:0[x]"), "expected triples of parameters like x:1:2, got 2");
+        assert_eq!(error("interval:x:0:1[y]"), "cannot bound y over the given intervals");
+    }
+
+    #[test]
+    fn bound_variables_are_not_substituted() {
+        let mut session = Session::new();
+        eval_in(&mut session, "x := 5; y := 3");
+        assert_eq!(eval_in(&mut session, "interval:x:0:1:y:1:2[x y]"), "[0, 2]");
+    }
+}
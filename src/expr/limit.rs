@@ -0,0 +1,318 @@
+use std::cmp::Ordering;
+
+use num_bigint::BigInt;
+use num_integer::Integer;
+
+use super::{functions, number::Number, Expr};
+
+
+/// Orders tried, in turn, when looking for the leading term of a series.
+const SERIES_ORDERS: [i64; 3] = [1, 4, 8];
+
+/// How many times L'Hôpital's rule is applied to a single quotient before giving up.
+const MAX_LHOPITAL_DEPTH: usize = 8;
+
+/// The point at which a sign is sampled when it cannot be read off exactly.
+const SAMPLE_POINT: f64 = 1e-9;
+
+/// The side from which the limit point is approached.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Direction {
+    Both,
+    Above,
+    Below,
+}
+
+/// A limit in the extended reals.
+#[derive(Clone, PartialEq)]
+enum Value {
+    Finite(Expr),
+    PosInf,
+    NegInf,
+}
+
+fn int(n: i64) -> Expr {
+    Expr::integer(BigInt::from(n))
+}
+
+/// Whether `∞` appears anywhere in the expression.
+pub fn contains_infinity(expr: &Expr) -> bool {
+    *expr == Expr::Infinity || expr.children().iter().any(|x| contains_infinity(x))
+}
+
+fn is_infinite(expr: &Expr) -> bool {
+    match expr {
+        Expr::Infinity => true,
+        Expr::Negation(node) => is_infinite(node),
+        _ => false,
+    }
+}
+
+/// The sign of a constant expression, or `None` if it is zero or cannot be told.
+fn sign(expr: &Expr) -> Option<i8> {
+    match Number::from_expr(expr) {
+        Some(n) if n.is_zero() => None,
+        Some(n) => Some(if n.is_negative() { -1 } else { 1 }),
+        None => match expr.to_f64()? {
+            v if v.is_nan() || v == 0.0 => None,
+            v => Some(if v < 0.0 { -1 } else { 1 }),
+        },
+    }
+}
+
+/// The sign of the expression just above `t = 0`, found by sampling it there.
+fn sign_near_zero(expr: &Expr, t: &str) -> Option<i8> {
+    sign(&Expr::decimal(expr.clone().substitute(t, &Expr::decimal(SAMPLE_POINT)).to_f64()?))
+}
+
+/// Whether a constant expression stands for a real number, rather than something like `1 / 0`.
+fn is_defined(expr: &Expr) -> bool {
+    let zero = |x: &Expr| Number::from_expr(x).is_some_and(|n| n.is_zero());
+    let ok = match expr {
+        Expr::Infinity => false,
+        Expr::Decimal(v) => v.is_finite(),
+        Expr::Ratio { denominator, .. } => !zero(denominator),
+        Expr::Power { base, exp } => !(zero(base) && Number::from_expr(exp).is_some_and(|n| n.is_negative())),
+        Expr::Function { name, args } if name == "ln" => Number::from_expr(&args[0]).is_none_or(|n| !n.is_zero() && !n.is_negative()),
+        _ => true,
+    };
+    ok && expr.children().iter().all(|x| is_defined(x))
+}
+
+impl Value {
+    fn infinite(sign: i8) -> Self {
+        if sign < 0 { Value::NegInf } else { Value::PosInf }
+    }
+
+    fn is_zero(&self) -> bool {
+        matches!(self, Value::Finite(x) if Number::from_expr(x).is_some_and(|n| n.is_zero()))
+    }
+
+    fn is_infinite(&self) -> bool {
+        !matches!(self, Value::Finite(_))
+    }
+
+    fn sign(&self) -> Option<i8> {
+        match self {
+            Value::Finite(x) => sign(x),
+            Value::PosInf => Some(1),
+            Value::NegInf => Some(-1),
+        }
+    }
+
+    fn into_expr(self) -> Expr {
+        match self {
+            Value::Finite(x) => x.simplify(),
+            Value::PosInf => Expr::Infinity,
+            Value::NegInf => Expr::negation(Expr::Infinity.boxed()),
+        }
+    }
+
+    fn neg(self) -> Self {
+        match self {
+            Value::Finite(x) => Value::Finite(Expr::negation(x.boxed()).simplify()),
+            Value::PosInf => Value::NegInf,
+            Value::NegInf => Value::PosInf,
+        }
+    }
+
+    /// The sum of two limits, or `None` for `∞ - ∞`.
+    fn add(self, rhs: Self) -> Option<Self> {
+        match (self, rhs) {
+            (Value::Finite(a), Value::Finite(b)) => Some(Value::Finite(Expr::sum(a.boxed(), b.boxed()).simplify())),
+            (Value::Finite(_), inf) | (inf, Value::Finite(_)) => Some(inf),
+            (a, b) => (a == b).then_some(a),
+        }
+    }
+
+    /// The product of two limits, or `None` for `0 ∞` and infinities times a value of unknown sign.
+    fn mul(self, rhs: Self) -> Option<Self> {
+        match (self, rhs) {
+            (Value::Finite(a), Value::Finite(b)) => Some(Value::Finite(Expr::product(a.boxed(), b.boxed()).simplify())),
+            (a, b) => Some(Value::infinite(a.sign()? * b.sign()?)),
+        }
+    }
+}
+
+impl Expr {
+    /// The limit of the expression as `var` approaches `point`, which may be `∞` or `-∞`.
+    ///
+    /// Returns `None` when the limit cannot be found, or when a two-sided limit has different limits
+    /// from either side.
+    pub fn limit(&self, var: &str, point: &Expr, direction: Direction) -> Option<Expr> {
+        if direction == Direction::Both && !is_infinite(point) {
+            let above = self.limit(var, point, Direction::Above)?;
+            let below = self.limit(var, point, Direction::Below)?;
+            return (above == below).then_some(above);
+        }
+
+        // Every limit is taken as `t` approaches `0` from above, reusing `var` as `t`.
+        let t = Expr::variable(var.to_string());
+        let value = match (point, direction) {
+            (Expr::Infinity, _) => Expr::ratio(int(1).boxed(), t.boxed()),
+            (Expr::Negation(node), _) if **node == Expr::Infinity => Expr::negation(Expr::ratio(int(1).boxed(), t.boxed()).boxed()),
+            (_, Direction::Below) => Expr::difference(point.clone().boxed(), t.boxed()),
+            _ => Expr::sum(point.clone().boxed(), t.boxed()),
+        };
+
+        let expr = resolve_abs(self.clone().substitute(var, &value).simplify(), var);
+        limit_at_zero(&expr, var, 0).map(Value::into_expr)
+    }
+}
+
+/// Replaces `abs[u]` by `u` or `-u` as the sign of `u` just above `t = 0` says, since neither
+/// series nor derivatives see through it.
+fn resolve_abs(expr: Expr, t: &str) -> Expr {
+    match expr.map_children(|x| resolve_abs(x, t)) {
+        Expr::Function { name, args } if name == "abs" && args.len() == 1 && args[0].contains_var(t) => match sign_near_zero(&args[0], t) {
+            Some(-1) => Expr::negation(args[0].clone().boxed()).simplify(),
+            Some(_) => args[0].clone(),
+            None => Expr::function(name, args),
+        },
+        expr => expr,
+    }
+}
+
+/// The limit of `expr` as `t` approaches `0` from above.
+fn limit_at_zero(expr: &Expr, t: &str, depth: usize) -> Option<Value> {
+    if !expr.contains_var(t) && is_defined(expr) {
+        return Some(Value::Finite(expr.clone()));
+    }
+
+    leading_term(expr, t).or_else(|| limit_parts(expr, t, depth))
+}
+
+/// Reads the limit off the leading term `c t^k` of the series of `expr` in `t`.
+fn leading_term(expr: &Expr, t: &str) -> Option<Value> {
+    if contains_infinity(expr) {
+        return None;
+    }
+
+    let (exp, coeff) = SERIES_ORDERS.iter().find_map(|&order| expr.laurent(t, &int(0), order)?.leading())?;
+    Some(match exp.cmp(&0) {
+        Ordering::Less => Value::infinite(sign(&coeff.simplify())?),
+        Ordering::Equal => Value::Finite(coeff),
+        Ordering::Greater => Value::Finite(int(0)),
+    })
+}
+
+/// Combines the limits of the parts of `expr`, resolving indeterminate quotients with L'Hôpital's rule.
+fn limit_parts(expr: &Expr, t: &str, depth: usize) -> Option<Value> {
+    let lim = |x: &Expr| limit_at_zero(x, t, depth);
+
+    match expr {
+        Expr::Infinity => Some(Value::PosInf),
+        Expr::Negation(node) => Some(lim(node)?.neg()),
+        Expr::Sum { left, right } => lim(left)?.add(lim(right)?),
+        Expr::Difference { left, right } => lim(left)?.add(lim(right)?.neg()),
+        Expr::Product { left, right } => match (lim(left)?, lim(right)?) {
+            // `0 ∞` is rewritten as `∞ / (1 / 0)`, which keeps things like `ln[t]` from piling up under
+            // repeated differentiation.
+            (l, r) if l.is_zero() && r.is_infinite() => quotient(right, &Expr::ratio(int(1).boxed(), left.clone()).simplify(), t, depth),
+            (l, r) if r.is_zero() && l.is_infinite() => quotient(left, &Expr::ratio(int(1).boxed(), right.clone()).simplify(), t, depth),
+            (l, r) => l.mul(r),
+        },
+        Expr::Ratio { numerator, denominator } => quotient(numerator, denominator, t, depth),
+        Expr::Power { base, exp } if !exp.contains_var(t) => power(lim(base)?, exp, base, t, depth),
+        Expr::Power { base, exp } => lim(&Expr::function("exp".to_string(), vec![
+            Expr::product(exp.clone(), Expr::function("ln".to_string(), vec![base.clone().into_inner()]).boxed()).simplify(),
+        ])),
+        Expr::Root { index, radicand } => match (index.as_integer(), lim(radicand)?) {
+            (_, Value::Finite(x)) => Some(Value::Finite(Expr::root(index.clone(), x.boxed()).simplify())),
+            (Some(_), Value::PosInf) => Some(Value::PosInf),
+            (Some(n), Value::NegInf) if n.is_odd() => Some(Value::NegInf),
+            _ => None,
+        },
+        Expr::Function { name, args } if args.len() == 1 && functions::is_elementary(name) => function(name, lim(&args[0])?),
+        _ => None,
+    }
+}
+
+/// The limit of `numerator / denominator`, differentiating both while the quotient is `0 / 0` or
+/// `∞ / ∞`.
+fn quotient(numerator: &Expr, denominator: &Expr, t: &str, depth: usize) -> Option<Value> {
+    let (n, d) = (limit_at_zero(numerator, t, depth)?, limit_at_zero(denominator, t, depth)?);
+
+    match (n, d) {
+        (n, d) if (n.is_zero() && d.is_zero()) || (n.is_infinite() && d.is_infinite()) => {
+            if depth >= MAX_LHOPITAL_DEPTH {
+                return None;
+            }
+
+            let ratio = Expr::ratio(numerator.differentiate(t)?.boxed(), denominator.differentiate(t)?.boxed()).simplify();
+            limit_at_zero(&ratio, t, depth + 1)
+        },
+        (n, d) if d.is_zero() => Some(Value::infinite(n.sign()? * sign_near_zero(denominator, t)?)),
+        (Value::Finite(n), Value::Finite(d)) => Some(Value::Finite(Expr::ratio(n.boxed(), d.boxed()).simplify())),
+        (Value::Finite(_), _) => Some(Value::Finite(int(0))),
+        (n, d) => Some(Value::infinite(n.sign()? * d.sign()?)),
+    }
+}
+
+/// The limit of `base ^ exp` for an exponent that does not depend on `t`, where `base` tends to `b`.
+fn power(b: Value, exp: &Expr, base: &Expr, t: &str, depth: usize) -> Option<Value> {
+    let exp = exp.clone().simplify();
+    let exp_sign = sign(&exp);
+
+    match b {
+        Value::Finite(b) if !Number::from_expr(&b).is_some_and(|n| n.is_zero()) => Some(Value::Finite(Expr::power(b.boxed(), exp.boxed()).simplify())),
+        Value::Finite(_) => match exp_sign? {
+            1 => Some(Value::Finite(int(0))),
+            _ => quotient(&int(1), &Expr::power(base.clone().boxed(), Expr::negation(exp.boxed()).boxed()).simplify(), t, depth),
+        },
+        _ if exp_sign? < 0 => Some(Value::Finite(int(0))),
+        Value::PosInf => Some(Value::PosInf),
+        Value::NegInf => match exp.as_integer() {
+            Some(n) if n.is_odd() => Some(Value::NegInf),
+            Some(_) => Some(Value::PosInf),
+            None => None,
+        },
+    }
+}
+
+/// The limit of an elementary function whose argument tends to `arg`.
+fn function(name: &str, arg: Value) -> Option<Value> {
+    match (name, arg) {
+        ("ln", arg) if arg.is_zero() => Some(Value::NegInf),
+        (_, Value::Finite(x)) => {
+            let value = Expr::function(name.to_string(), vec![x]).simplify();
+            is_defined(&value).then_some(Value::Finite(value))
+        },
+        ("exp" | "ln" | "sinh" | "cosh" | "abs", Value::PosInf) => Some(Value::PosInf),
+        ("exp", Value::NegInf) => Some(Value::Finite(int(0))),
+        ("sinh", Value::NegInf) => Some(Value::NegInf),
+        ("cosh" | "abs", Value::NegInf) => Some(Value::PosInf),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{session::Session, testing::{error, eval, eval_in}};
+
+    #[test]
+    fn finite_limits() {
+        assert_eq!(eval("lim:x:0[sin[x]/x]"), "1");
+        assert_eq!(eval("lim:x:0[(1 - cos[x])/x^2]"), "(1 / 2)");
+    }
+
+    #[test]
+    fn limits_at_infinity() {
+        assert_eq!(eval("lim:x:inf[sqrt[x^2+x]-x]"), "(1 / 2)");
+        assert_eq!(eval("lim:x:inf[1/x]"), "0");
+    }
+
+    #[test]
+    fn one_sided_limits() {
+        assert_eq!(eval("lim:x:0:+[abs[x]/x]"), "1");
+        assert_eq!(eval("lim:x:0:-[abs[x]/x]"), "-1");
+        assert_eq!(error("lim:x:0[abs[x]/x]"), "the limit from below is -1 but the limit from above is 1");
+    }
+
+    #[test]
+    fn bound_variable_is_not_substituted() {
+        let mut session = Session::new();
+        eval_in(&mut session, "x := 3");
+        assert_eq!(eval_in(&mut session, "lim:x:0[sin[x]/x]"), "1");
+    }
+}
@@ -0,0 +1,402 @@
+use num_bigint::BigInt;
+use num_traits::{One, Signed, ToPrimitive, Zero};
+
+use super::{number::Number, polynomial, Expr};
+use crate::prelude::*;
+
+
+/// The entries of a matrix, row by row.
+pub type Rows = Vec<Vec<Expr>>;
+
+pub fn dims(rows: &Rows) -> (usize, usize) {
+    (rows.len(), rows.first().map_or(0, |r| r.len()))
+}
+
+/// Whether the expression is free of matrices, so it can scale one.
+pub fn is_scalar(expr: &Expr) -> bool {
+    !matches!(expr, Expr::Matrix { .. }) && expr.children().iter().all(|x| is_scalar(x))
+}
+
+pub fn identity(n: usize) -> Rows {
+    (0..n)
+        .map(|i| (0..n).map(|j| Expr::integer(if i == j { BigInt::one() } else { BigInt::zero() })).collect())
+        .collect()
+}
+
+pub fn map(rows: Rows, mut f: impl FnMut(Expr) -> Expr) -> Rows {
+    rows.into_iter().map(|row| row.into_iter().map(&mut f).collect()).collect()
+}
+
+pub fn transpose(rows: &Rows) -> Rows {
+    let (n, m) = dims(rows);
+    (0..m).map(|j| (0..n).map(|i| rows[i][j].clone()).collect()).collect()
+}
+
+/// The elementwise sum, or `None` if the dimensions differ.
+pub fn add(a: &Rows, b: &Rows) -> Option<Rows> {
+    if dims(a) != dims(b) {
+        return None;
+    }
+
+    Some(a.iter()
+        .zip(b)
+        .map(|(x, y)| x.iter().zip(y).map(|(x, y)| Expr::sum(x.clone().boxed(), y.clone().boxed()).simplify()).collect())
+        .collect())
+}
+
+pub fn scale(rows: Rows, c: &Expr) -> Rows {
+    map(rows, |x| Expr::product(c.clone().boxed(), x.boxed()).simplify())
+}
+
+/// The matrix product, or `None` if the columns of `a` do not match the rows of `b`.
+pub fn mul(a: &Rows, b: &Rows) -> Option<Rows> {
+    let ((n, k), (k2, m)) = (dims(a), dims(b));
+    if k != k2 {
+        return None;
+    }
+
+    Some((0..n)
+        .map(|i| (0..m)
+            .map(|j| (0..k)
+                .map(|l| Expr::product(a[i][l].clone().boxed(), b[l][j].clone().boxed()))
+                .reduce(|acc, x| Expr::sum(acc.boxed(), x.boxed()))
+                .unwrap_or(Expr::integer(BigInt::zero()))
+                .simplify())
+            .collect())
+        .collect())
+}
+
+/// Raises a square matrix to a non-negative power by repeated squaring.
+pub fn pow(rows: &Rows, mut k: u64) -> Option<Rows> {
+    let (n, m) = dims(rows);
+    if n != m {
+        return None;
+    }
+
+    let mut result = identity(n);
+    let mut base = rows.clone();
+    while k > 0 {
+        if k & 1 == 1 {
+            result = mul(&result, &base)?;
+        }
+        k >>= 1;
+        if k > 0 {
+            base = mul(&base, &base)?;
+        }
+    }
+    Some(result)
+}
+
+/// Rejects an operation that cannot be carried out on its matrix operands, like adding matrices of
+/// different sizes or taking the sine of one.
+///
+/// Operations on scalars pass without their operands being simplified.
+pub fn check(expr: &Expr, span: Span) -> Result<()> {
+    let operand = |x: &Expr| if is_scalar(x) { x.clone() } else { x.clone().simplify() };
+    match expr {
+        Expr::Sum { left, right } | Expr::Difference { left, right } => match (operand(left), operand(right)) {
+            (Expr::Matrix { rows: a }, Expr::Matrix { rows: b }) if dims(&a) != dims(&b) => {
+                let ((n, m), (k, l)) = (dims(&a), dims(&b));
+                err!(Domain, "cannot add a {}x{} matrix and a {}x{} matrix", span; n, m, k, l)
+            },
+            (l, r) if is_scalar(&l) != is_scalar(&r) => err!(Domain, "cannot add a matrix and a scalar", span),
+            _ => Ok(()),
+        },
+        Expr::Product { left, right } => match (operand(left), operand(right)) {
+            (Expr::Matrix { rows: a }, Expr::Matrix { rows: b }) if dims(&a).1 != dims(&b).0 => {
+                let ((n, m), (k, l)) = (dims(&a), dims(&b));
+                err!(Domain, "cannot multiply a {}x{} matrix by a {}x{} matrix", span; n, m, k, l)
+            },
+            _ => Ok(()),
+        },
+        Expr::Ratio { denominator, .. } if !is_scalar(denominator) => err!(Domain, "cannot divide by a matrix", span),
+        Expr::Power { exp, .. } if !is_scalar(exp) => err!(Domain, "cannot raise to a matrix power", span),
+        Expr::Power { base, exp } if !is_scalar(base) => {
+            let Expr::Matrix { rows } = operand(base) else { return Ok(()) };
+            let (n, m) = dims(&rows);
+            if n != m {
+                return err!(Domain, "cannot raise a {}x{} matrix to a power", span; n, m);
+            }
+            let Expr::Integer(e) = exp.clone().simplify() else {
+                return err!(Domain, "matrices can only be raised to integer powers", span);
+            };
+            if e.abs().to_u64().is_none_or(|e| e > MAX_POWER) {
+                return err!(InvalidCall, "matrix powers above {} are not supported", span; MAX_POWER);
+            }
+            if e.is_negative() {
+                match det(&rows) {
+                    None => return err!(InvalidCall, "symbolic matrices above {}x{} are not supported", span; MAX_SYMBOLIC_SIZE, MAX_SYMBOLIC_SIZE),
+                    Some(det) if is_zero(&det) => return err!(Domain, "matrix is singular", span),
+                    Some(_) => {},
+                }
+            }
+            Ok(())
+        },
+        Expr::Function { name, args } if !args.iter().all(is_scalar) => err!(Domain, "cannot apply {} to a matrix", span; name),
+        _ => Ok(()),
+    }
+}
+
+/// Writes the matrix over several lines, with columns aligned between tall brackets.
+pub fn fmt_box(rows: &Rows, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let cells: Vec<Vec<String>> = rows.iter().map(|row| row.iter().map(|x| x.to_string()).collect()).collect();
+    let (n, m) = dims(rows);
+    let widths: Vec<usize> = (0..m).map(|j| cells.iter().map(|row| row[j].chars().count()).max().unwrap_or(0)).collect();
+
+    for (i, row) in cells.iter().enumerate() {
+        let (open, close) = match i {
+            _ if n == 1 => ('[', ']'),
+            0 => ('⎡', '⎤'),
+            i if i == n - 1 => ('⎣', '⎦'),
+            _ => ('⎢', '⎥'),
+        };
+        let line = row.iter().zip(&widths).map(|(x, w)| format!("{:>w$}", x, w = w)).collect::<Vec<_>>().join("  ");
+        if i > 0 {
+            writeln!(f)?;
+        }
+        write!(f, "{} {} {}", open, line, close)?;
+    }
+
+    Ok(())
+}
+
+
+/// Symbolic determinants and inverses of matrices larger than this are not expanded.
+pub const MAX_SYMBOLIC_SIZE: usize = 6;
+
+/// Matrices are only raised to powers up to this by repeated multiplication.
+pub const MAX_POWER: u64 = 1 << 16;
+
+fn is_zero(expr: &Expr) -> bool {
+    Number::from_expr(expr).is_some_and(|n| n.is_zero())
+}
+
+fn is_numeric(rows: &Rows) -> bool {
+    rows.iter().flatten().all(Expr::is_numeric)
+}
+
+fn sub(a: &Expr, b: &Expr) -> Expr {
+    Expr::difference(a.clone().boxed(), b.clone().boxed())
+}
+
+fn mul_entries(a: &Expr, b: &Expr) -> Expr {
+    Expr::product(a.clone().boxed(), b.clone().boxed())
+}
+
+fn div(a: &Expr, b: &Expr) -> Expr {
+    Expr::ratio(a.clone().boxed(), b.clone().boxed())
+}
+
+fn minor(rows: &Rows, row: usize, col: usize) -> Rows {
+    rows.iter()
+        .enumerate()
+        .filter(|(i, _)| *i != row)
+        .map(|(_, r)| r.iter().enumerate().filter(|(j, _)| *j != col).map(|(_, x)| x.clone()).collect())
+        .collect()
+}
+
+fn trace(rows: &Rows) -> Expr {
+    (0..rows.len())
+        .map(|i| rows[i][i].clone())
+        .reduce(|acc, x| Expr::sum(acc.boxed(), x.boxed()))
+        .unwrap_or(Expr::integer(BigInt::zero()))
+        .simplify()
+}
+
+/// The reduced row echelon form, along with the column of each pivot.
+///
+/// Symbolic entries that do not simplify to zero are taken to be non-zero.
+pub fn rref(rows: &Rows) -> (Rows, Vec<usize>) {
+    let mut a = rows.clone();
+    let (n, m) = dims(rows);
+    let mut pivots = Vec::new();
+
+    for c in 0..m {
+        let r = pivots.len();
+        if r == n {
+            break;
+        }
+        let Some(p) = (r..n).find(|&i| !is_zero(&a[i][c])) else { continue };
+        a.swap(r, p);
+
+        let pivot = a[r][c].clone();
+        a[r] = a[r].iter().map(|x| div(x, &pivot).simplify()).collect();
+        for i in (0..n).filter(|&i| i != r) {
+            if is_zero(&a[i][c]) {
+                continue;
+            }
+            let factor = a[i][c].clone();
+            a[i] = a[i].iter().zip(&a[r]).map(|(x, y)| sub(x, &mul_entries(&factor, y)).simplify()).collect();
+        }
+        pivots.push(c);
+    }
+
+    (a, pivots)
+}
+
+pub fn rank(rows: &Rows) -> usize {
+    rref(rows).1.len()
+}
+
+/// A basis of the nullspace as the columns of a matrix, or the zero vector if it is trivial.
+pub fn nullspace(rows: &Rows) -> Rows {
+    let (reduced, pivots) = rref(rows);
+    let m = dims(rows).1;
+    let basis: Vec<Vec<Expr>> = (0..m)
+        .filter(|c| !pivots.contains(c))
+        .map(|free| (0..m)
+            .map(|c| match pivots.iter().position(|p| *p == c) {
+                Some(k) => Expr::negation(reduced[k][free].clone().boxed()).simplify(),
+                None => Expr::integer(if c == free { BigInt::one() } else { BigInt::zero() }),
+            })
+            .collect())
+        .collect();
+
+    if basis.is_empty() {
+        vec![vec![Expr::integer(BigInt::zero())]; m]
+    } else {
+        transpose(&basis)
+    }
+}
+
+/// The determinant of a square matrix, by elimination for numeric entries and cofactor expansion
+/// otherwise.
+///
+/// Returns `None` for symbolic matrices above [`MAX_SYMBOLIC_SIZE`].
+pub fn det(rows: &Rows) -> Option<Expr> {
+    let n = rows.len();
+    if is_numeric(rows) {
+        let mut a = rows.clone();
+        let mut det = Expr::integer(BigInt::one());
+        for c in 0..n {
+            let Some(p) = (c..n).find(|&i| !is_zero(&a[i][c])) else { return Some(Expr::integer(BigInt::zero())) };
+            if p != c {
+                a.swap(c, p);
+                det = Expr::negation(det.boxed());
+            }
+            det = mul_entries(&det, &a[c][c]).simplify();
+            for i in c + 1..n {
+                let factor = div(&a[i][c], &a[c][c]).simplify();
+                a[i] = a[i].iter().zip(&a[c]).map(|(x, y)| sub(x, &mul_entries(&factor, y)).simplify()).collect();
+            }
+        }
+        return Some(det);
+    }
+
+    (n <= MAX_SYMBOLIC_SIZE).then(|| cofactor_det(rows))
+}
+
+fn cofactor_det(rows: &Rows) -> Expr {
+    match rows.len() {
+        0 => Expr::integer(BigInt::one()),
+        1 => rows[0][0].clone(),
+        _ => rows[0].iter()
+            .enumerate()
+            .filter(|(_, x)| !is_zero(x))
+            .map(|(j, x)| {
+                let term = mul_entries(x, &cofactor_det(&minor(rows, 0, j)));
+                if j % 2 == 0 { term } else { Expr::negation(term.boxed()) }
+            })
+            .reduce(|acc, x| Expr::sum(acc.boxed(), x.boxed()))
+            .unwrap_or(Expr::integer(BigInt::zero())),
+    }
+    .simplify()
+}
+
+/// The inverse of a square matrix with non-zero determinant `det`.
+pub fn inverse(rows: &Rows, det: &Expr) -> Rows {
+    let n = rows.len();
+    if is_numeric(rows) {
+        let augmented: Rows = rows.iter().zip(identity(n)).map(|(row, id)| row.iter().cloned().chain(id).collect()).collect();
+        return rref(&augmented).0.into_iter().map(|row| row[n..].to_vec()).collect();
+    }
+
+    // The adjugate over the determinant, transposing as the cofactors are read.
+    (0..n)
+        .map(|i| (0..n)
+            .map(|j| {
+                let cofactor = cofactor_det(&minor(rows, j, i));
+                let cofactor = if (i + j) % 2 == 0 { cofactor } else { Expr::negation(cofactor.boxed()) };
+                div(&cofactor, det).simplify()
+            })
+            .collect())
+        .collect()
+}
+
+/// The coefficients of the characteristic polynomial `det(x I - A)`, from the constant term up, by
+/// the Faddeev-LeVerrier recurrence.
+pub fn charpoly(rows: &Rows) -> Vec<Expr> {
+    let n = rows.len();
+    let mut coeffs = vec![Expr::integer(BigInt::zero()); n + 1];
+    coeffs[n] = Expr::integer(BigInt::one());
+
+    let mut m = map(identity(n), |_| Expr::integer(BigInt::zero()));
+    for k in 1..=n {
+        let c = coeffs[n - k + 1].clone();
+        m = add(&mul(rows, &m).unwrap(), &map(identity(n), |x| mul_entries(&c, &x).simplify())).unwrap();
+        let t = trace(&mul(rows, &m).unwrap());
+        coeffs[n - k] = polynomial::expand(&Expr::negation(div(&t, &Expr::integer(BigInt::from(k))).boxed()));
+    }
+
+    coeffs
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{session::Session, testing::{error, eval, eval_in, run}};
+
+    #[test]
+    fn arithmetic() {
+        assert_eq!(eval("mat[[1,2],[3,4]] + mat[[1,0],[0,1]]"), "⎡ 2  2 ⎤\n⎣ 3  5 ⎦");
+        assert_eq!(eval("mat[[1,2],[3,4]] * vec[1,1]"), "⎡ 3 ⎤\n⎣ 7 ⎦");
+        assert_eq!(eval("2*mat[[1,2]] - mat[[1,1]]"), "[ 1  3 ]");
+        assert_eq!(eval("mat[[1,1],[0,1]]^3"), "⎡ 1  3 ⎤\n⎣ 0  1 ⎦");
+        assert_eq!(eval("transpose[mat[[1,2]]]"), "⎡ 1 ⎤\n⎣ 2 ⎦");
+    }
+
+    #[test]
+    fn invalid_operations_are_errors() {
+        assert_eq!(error("mat[[1,2]] + mat[[1],[2]]"), "cannot add a 1x2 matrix and a 2x1 matrix");
+        assert_eq!(error("mat[[1,2]] * mat[[1,2]]"), "cannot multiply a 1x2 matrix by a 1x2 matrix");
+        assert_eq!(error("mat[[1,2]] + 1"), "cannot add a matrix and a scalar");
+        assert_eq!(error("1/mat[[1,2],[3,4]]"), "cannot divide by a matrix");
+        assert_eq!(error("sin[mat[[1]]]"), "cannot apply sin to a matrix");
+        assert_eq!(error("mat[[1,2]]^2"), "cannot raise a 1x2 matrix to a power");
+        assert_eq!(error("mat[[1,2],[3,4]]^(1/2)"), "matrices can only be raised to integer powers");
+        assert_eq!(error("mat[[1,2],[2,4]]^-1"), "matrix is singular");
+    }
+
+    #[test]
+    fn determinants_and_inverses() {
+        assert_eq!(eval("det[mat[[1,2],[3,4]]]"), "-2");
+        assert_eq!(eval("det[mat[[a,b],[c,d]]]"), "(ad + -bc)");
+        assert_eq!(eval("inv[mat[[2,0],[0,4]]]"), "⎡ (1 / 2)        0 ⎤\n⎣       0  (1 / 4) ⎦");
+        assert_eq!(error("inv[mat[[1,2],[2,4]]]"), "matrix is singular");
+        assert_eq!(error("det[mat[[1,2]]]"), "expected a square matrix, got 1x2");
+        assert_eq!(error("det[3]"), "expected a matrix");
+    }
+
+    #[test]
+    fn elimination() {
+        assert_eq!(eval("rank[mat[[1,2],[2,4]]]"), "1");
+        assert_eq!(eval("rref[mat[[1,2],[3,4]]]"), "⎡ 1  0 ⎤\n⎣ 0  1 ⎦");
+        assert_eq!(eval("nullspace[mat[[1,2],[2,4]]]"), "⎡ -2 ⎤\n⎣  1 ⎦");
+    }
+
+    #[test]
+    fn eigenvalues() {
+        assert_eq!(eval("charpoly[mat[[1,2],[3,4]]]"), "((-2 + -5x) + (x ^ 2))");
+        assert_eq!(eval("charpoly:t[mat[[2,0],[0,3]]]"), "((6 + -5t) + (t ^ 2))");
+        assert_eq!(eval("eigenvals[mat[[2,1],[1,2]]]"), "⎡ 1 ⎤\n⎣ 3 ⎦");
+        assert_eq!(eval("eigenvals[mat[[0,-1],[1,0]]]"), "⎡ -i ⎤\n⎣  i ⎦");
+        assert_eq!(eval("eigenvals[mat[[0,-1],[4,0]]]"), "⎡ -2i ⎤\n⎣  2i ⎦");
+    }
+
+    #[test]
+    fn bound_matrices_are_checked() {
+        let mut session = Session::new();
+        eval_in(&mut session, "A := mat[[1,2],[3,4]]");
+        assert_eq!(eval_in(&mut session, "A^-1 * A"), "⎡ 1  0 ⎤\n⎣ 0  1 ⎦");
+        assert_eq!(run(&mut session, "A + 1"), Err("cannot add a matrix and a scalar".to_string()));
+    }
+}
@@ -1,118 +1,728 @@
+use num_bigint::BigInt;
+use num_traits::{FromPrimitive, One, Signed, ToPrimitive, Zero};
 use proc_macros::FieldConstructor;
 
-use crate::{lexer::token::TokenType, parser::node::Node};
+use crate::{lexer::token::TokenType, parser::node::Node, session::Session};
+use interned::Interned;
+use limit::Direction;
+use number::Number;
 use crate::prelude::*;
 
+pub mod assumptions;
+pub mod compile;
+pub mod derivative;
+pub mod egraph;
+pub mod equivalence;
+pub mod functions;
+pub mod interned;
+pub mod interval;
+pub mod limit;
+pub mod matrix;
+pub mod modular;
+pub mod nsolve;
+pub mod number;
+pub mod number_theory;
+pub mod polynomial;
+pub mod quadrature;
+pub mod radical;
+pub mod rational;
+pub mod rules;
+pub mod series;
 pub mod simplify;
+pub mod special;
+pub mod summation;
+pub mod trace;
 
-#[derive(Clone, FieldConstructor)]
+/// `divisors` refuses integers with more divisors than this.
+const MAX_DIVISORS: u64 = 100_000;
+
+/// `series` refuses orders above this, as composing series takes time growing with the cube of the
+/// order, which is seconds for nested functions like `exp[sin[x]]` at this order.
+const MAX_SERIES_ORDER: i64 = 50;
+
+
+#[derive(Clone, PartialEq, FieldConstructor)]
 pub enum Expr {
-    Integer(i64),
+    Integer(BigInt),
     Decimal(f64),
     Variable(String),
+    /// Positive infinity, written `inf`. Negative infinity is its negation.
+    Infinity,
+    Boolean(bool),
+    /// The outcome of `equiv[a, b]`.
+    Equivalence(equivalence::Equivalence),
+    /// The bounds found by `interval:x:1:2[...]`.
+    Interval(interval::Interval),
+    /// The roots found by `nsolve:x:1[...]`.
+    Solution(nsolve::Solution),
+    /// The value found by `nint:x:0:1[...]`, with its error estimate.
+    Integral(quadrature::Integral),
+    /// A non-zero integer kept as its prime factorization, like `-2³·3`.
+    Factorization {
+        negative: bool,
+        factors: Vec<(BigInt, u32)>,
+    },
 
-    Negation(Box<Expr>),
+    Negation(Interned),
 
     Sum {
-        left: Box<Expr>,
-        right: Box<Expr>,
+        left: Interned,
+        right: Interned,
     },
     Difference {
-        left: Box<Expr>,
-        right: Box<Expr>,
+        left: Interned,
+        right: Interned,
     },
     Product {
-        left: Box<Expr>,
-        right: Box<Expr>,
+        left: Interned,
+        right: Interned,
     },
     Ratio {
-        numerator: Box<Expr>,
-        denominator: Box<Expr>,
+        numerator: Interned,
+        denominator: Interned,
     },
     Power {
-        base: Box<Expr>,
-        exp: Box<Expr>,
+        base: Interned,
+        exp: Interned,
     },
     Root {
-        index: Box<Expr>,
-        radicand: Box<Expr>,
+        index: Interned,
+        radicand: Interned,
+    },
+
+    Factorial(Interned),
+    DoubleFactorial(Interned),
+    Gamma(Interned),
+    Binomial {
+        n: Interned,
+        k: Interned,
+    },
+
+    /// The sum of `body` over integer values of `var` from `from` to `to`, inclusive.
+    IndexedSum {
+        var: String,
+        from: Interned,
+        to: Interned,
+        body: Interned,
+    },
+    /// The product of `body` over integer values of `var` from `from` to `to`, inclusive.
+    IndexedProduct {
+        var: String,
+        from: Interned,
+        to: Interned,
+        body: Interned,
+    },
+
+    Function {
+        name: String,
+        args: Vec<Expr>,
+    },
+
+    /// A matrix, with vectors being matrices of a single column.
+    Matrix {
+        rows: Vec<Vec<Expr>>,
+    },
+    Derivative {
+        name: String,
+        order: usize,
+        args: Vec<Expr>,
+    },
+
+    /// The error term `O(base ^ exp)` of a truncated series.
+    Order {
+        base: Interned,
+        exp: Interned,
     },
 
     Equals {
-        left: Box<Expr>,
-        right: Box<Expr>,
+        left: Interned,
+        right: Interned,
     },
     NotEquals {
-        left: Box<Expr>,
-        right: Box<Expr>,
+        left: Interned,
+        right: Interned,
     },
     GreaterThan {
-        left: Box<Expr>,
-        right: Box<Expr>,
+        left: Interned,
+        right: Interned,
     },
     LessThan {
-        left: Box<Expr>,
-        right: Box<Expr>,
+        left: Interned,
+        right: Interned,
     },
     GreaterThanEq {
-        left: Box<Expr>,
-        right: Box<Expr>,
+        left: Interned,
+        right: Interned,
     },
     LessThanEq {
-        left: Box<Expr>,
-        right: Box<Expr>,
+        left: Interned,
+        right: Interned,
     },
 }
 
 
 impl Expr {
-    pub fn boxed(self) -> Box<Self> {
-        Box::new(self)
+    pub fn boxed(self) -> Interned {
+        Interned::new(self)
+    }
+
+    pub fn children(&self) -> Vec<&Expr> {
+        match self {
+            Expr::Integer(_)
+            | Expr::Decimal(_)
+            | Expr::Variable(_)
+            | Expr::Infinity
+            | Expr::Boolean(_)
+            | Expr::Equivalence(_)
+            | Expr::Interval(_)
+            | Expr::Solution(_)
+            | Expr::Integral(_)
+            | Expr::Factorization { .. } => vec![],
+            Expr::Negation(node) | Expr::Factorial(node) | Expr::DoubleFactorial(node) | Expr::Gamma(node) => vec![node],
+            Expr::Sum { left, right }
+            | Expr::Difference { left, right }
+            | Expr::Product { left, right }
+            | Expr::Equals { left, right }
+            | Expr::NotEquals { left, right }
+            | Expr::GreaterThan { left, right }
+            | Expr::LessThan { left, right }
+            | Expr::GreaterThanEq { left, right }
+            | Expr::LessThanEq { left, right } => vec![left, right],
+            Expr::Ratio { numerator, denominator } => vec![numerator, denominator],
+            Expr::Power { base, exp } => vec![base, exp],
+            Expr::Root { index, radicand } => vec![index, radicand],
+            Expr::Binomial { n, k } => vec![n, k],
+            Expr::Order { base, exp } => vec![base, exp],
+            Expr::IndexedSum { from, to, body, .. } | Expr::IndexedProduct { from, to, body, .. } => vec![from, to, body],
+            Expr::Function { args, .. } | Expr::Derivative { args, .. } => args.iter().collect(),
+            Expr::Matrix { rows } => rows.iter().flatten().collect(),
+        }
+    }
+
+    pub fn as_integer(&self) -> Option<&BigInt> {
+        match self {
+            Expr::Integer(n) => Some(n),
+            _ => None,
+        }
+    }
+
+    /// Rebuilds the expression with `f` applied to each of its direct children.
+    pub fn map_children<F: FnMut(Expr) -> Expr>(self, mut f: F) -> Expr {
+        match self {
+            Expr::Integer(_)
+            | Expr::Decimal(_)
+            | Expr::Variable(_)
+            | Expr::Infinity
+            | Expr::Boolean(_)
+            | Expr::Equivalence(_)
+            | Expr::Interval(_)
+            | Expr::Solution(_)
+            | Expr::Integral(_)
+            | Expr::Factorization { .. } => self,
+            Expr::Negation(node) => Expr::negation(f(node.into_inner()).boxed()),
+            Expr::Sum { left, right } => Expr::sum(f(left.into_inner()).boxed(), f(right.into_inner()).boxed()),
+            Expr::Difference { left, right } => Expr::difference(f(left.into_inner()).boxed(), f(right.into_inner()).boxed()),
+            Expr::Product { left, right } => Expr::product(f(left.into_inner()).boxed(), f(right.into_inner()).boxed()),
+            Expr::Ratio { numerator, denominator } => Expr::ratio(f(numerator.into_inner()).boxed(), f(denominator.into_inner()).boxed()),
+            Expr::Power { base, exp } => Expr::power(f(base.into_inner()).boxed(), f(exp.into_inner()).boxed()),
+            Expr::Root { index, radicand } => Expr::root(f(index.into_inner()).boxed(), f(radicand.into_inner()).boxed()),
+            Expr::Factorial(node) => Expr::factorial(f(node.into_inner()).boxed()),
+            Expr::DoubleFactorial(node) => Expr::doublefactorial(f(node.into_inner()).boxed()),
+            Expr::Gamma(node) => Expr::gamma(f(node.into_inner()).boxed()),
+            Expr::Binomial { n, k } => Expr::binomial(f(n.into_inner()).boxed(), f(k.into_inner()).boxed()),
+            Expr::Order { base, exp } => Expr::order(f(base.into_inner()).boxed(), f(exp.into_inner()).boxed()),
+            Expr::IndexedSum { var, from, to, body } => Expr::indexedsum(var, f(from.into_inner()).boxed(), f(to.into_inner()).boxed(), f(body.into_inner()).boxed()),
+            Expr::IndexedProduct { var, from, to, body } => Expr::indexedproduct(var, f(from.into_inner()).boxed(), f(to.into_inner()).boxed(), f(body.into_inner()).boxed()),
+            Expr::Function { name, args } => Expr::function(name, args.into_iter().map(f).collect()),
+            Expr::Matrix { rows } => Expr::matrix(matrix::map(rows, f)),
+            Expr::Derivative { name, order, args } => Expr::derivative(name, order, args.into_iter().map(f).collect()),
+            Expr::Equals { left, right } => Expr::equals(f(left.into_inner()).boxed(), f(right.into_inner()).boxed()),
+            Expr::NotEquals { left, right } => Expr::notequals(f(left.into_inner()).boxed(), f(right.into_inner()).boxed()),
+            Expr::GreaterThan { left, right } => Expr::greaterthan(f(left.into_inner()).boxed(), f(right.into_inner()).boxed()),
+            Expr::LessThan { left, right } => Expr::lessthan(f(left.into_inner()).boxed(), f(right.into_inner()).boxed()),
+            Expr::GreaterThanEq { left, right } => Expr::greaterthaneq(f(left.into_inner()).boxed(), f(right.into_inner()).boxed()),
+            Expr::LessThanEq { left, right } => Expr::lessthaneq(f(left.into_inner()).boxed(), f(right.into_inner()).boxed()),
+        }
+    }
+
+    /// Replaces every occurrence of the variable `var` with `value`.
+    pub fn substitute(self, var: &str, value: &Expr) -> Expr {
+        match self {
+            Expr::Variable(s) if s == var => value.clone(),
+            Expr::IndexedSum { var: index, from, to, body } if index == var => {
+                Expr::indexedsum(index, from.into_inner().substitute(var, value).boxed(), to.into_inner().substitute(var, value).boxed(), body)
+            },
+            Expr::IndexedProduct { var: index, from, to, body } if index == var => {
+                Expr::indexedproduct(index, from.into_inner().substitute(var, value).boxed(), to.into_inner().substitute(var, value).boxed(), body)
+            },
+            other => other.map_children(|x| x.substitute(var, value)),
+        }
+    }
+
+    /// Replaces every subexpression structurally equal to one of the patterns with its replacement.
+    ///
+    /// All rules are applied at once, so `x -> y, y -> x` swaps the two variables.
+    pub fn replace(self, rules: &[(Expr, Expr)]) -> Expr {
+        match rules.iter().find(|(pattern, _)| *pattern == self) {
+            Some((_, replacement)) => replacement.clone(),
+            None => self.map_children(|x| x.replace(rules)),
+        }
+    }
+
+    /// The names of every variable in the expression, in order of first appearance.
+    pub fn variables(&self) -> Vec<String> {
+        let mut vars = Vec::new();
+        self.collect_variables(&mut vars);
+        vars
+    }
+
+    fn collect_variables(&self, vars: &mut Vec<String>) {
+        match self {
+            Expr::Variable(s) => if !vars.contains(s) {
+                vars.push(s.clone());
+            },
+            Expr::IndexedSum { var, from, to, body } | Expr::IndexedProduct { var, from, to, body } => {
+                from.collect_variables(vars);
+                to.collect_variables(vars);
+                let mut inner = Vec::new();
+                body.collect_variables(&mut inner);
+                inner.into_iter().filter(|x| x != var).for_each(|x| if !vars.contains(&x) {
+                    vars.push(x);
+                });
+            },
+            other => other.children().iter().for_each(|x| x.collect_variables(vars)),
+        }
+    }
+
+    /// Whether the expression depends on the variable `var`.
+    ///
+    /// Prime symbols like `y'` carry no arguments, so they are assumed to depend on every variable.
+    pub fn contains_var(&self, var: &str) -> bool {
+        match self {
+            Expr::Variable(s) => s == var,
+            Expr::Derivative { args, .. } if args.is_empty() => true,
+            Expr::IndexedSum { var: index, from, to, body } | Expr::IndexedProduct { var: index, from, to, body } => {
+                from.contains_var(var) || to.contains_var(var) || (index != var && body.contains_var(var))
+            },
+            other => other.children().iter().any(|x| x.contains_var(var)),
+        }
     }
     
-    pub fn convert(value: Node) -> Result<Self> {
+    pub fn convert(value: Node, session: &Session) -> Result<Self> {
         Ok(match value {
-            Node::Constant { token } => if let TokenType::Decimal(v) = token.ty {
-                if v.fract() < 2e-6 {
-                    Expr::Integer(v.round() as i64)
-                } else {
-                    Expr::Decimal(v)
-                }
-            } else { unreachable!() },
-            Node::Variable { name } => Expr::Variable(format!("{}", name.ty)),
-            Node::BinaryOp { token, left, right } => match token.ty {
-                TokenType::Add => Expr::Sum { left: Box::new(Expr::convert(*left)?), right: Box::new(Expr::convert(*right)?) },
-                TokenType::Sub => Expr::Difference { left: Box::new(Expr::convert(*left)?), right: Box::new(Expr::convert(*right)?) },
-                TokenType::Mul => Expr::Product { left: Box::new(Expr::convert(*left)?), right: Box::new(Expr::convert(*right)?) },
-                TokenType::Div => Expr::Ratio { numerator: Box::new(Expr::convert(*left)?), denominator: Box::new(Expr::convert(*right)?) },
-                TokenType::Pow => Expr::Power { base: Box::new(Expr::convert(*left)?), exp: Box::new(Expr::convert(*right)?) },
-                TokenType::GreaterThan => Expr::GreaterThan { left: Box::new(Expr::convert(*left)?), right: Box::new(Expr::convert(*right)?) },
-                TokenType::LessThan => Expr::LessThan { left: Box::new(Expr::convert(*left)?), right: Box::new(Expr::convert(*right)?) },
-                TokenType::GreaterThanEq => Expr::GreaterThanEq { left: Box::new(Expr::convert(*left)?), right: Box::new(Expr::convert(*right)?) },
-                TokenType::LessThanEq => Expr::LessThanEq { left: Box::new(Expr::convert(*left)?), right: Box::new(Expr::convert(*right)?) },
-                TokenType::Equals => Expr::Equals { left: Box::new(Expr::convert(*left)?), right: Box::new(Expr::convert(*right)?) },
-                TokenType::NotEquals => Expr::NotEquals { left: Box::new(Expr::convert(*left)?), right: Box::new(Expr::convert(*right)?) },
+            Node::Constant { token } => match token.ty {
+                TokenType::Integer(n) => Expr::Integer(n),
+                TokenType::Decimal(v) if v.fract() == 0.0 => Expr::Integer(BigInt::from_f64(v).unwrap()),
+                TokenType::Decimal(v) => Expr::Decimal(v),
+                _ => unreachable!(),
+            },
+            Node::Variable { name } => match format!("{}", name.ty).as_str() {
+                "inf" => Expr::Infinity,
+                name => session.substitute(Expr::Variable(name.to_string())),
+            },
+            Node::Sign { token } => return err!(Syntax, "expected an expression", token.span),
+            Node::Text { token } => return err!(Syntax, "strings are only allowed as the file of plot:...[...]", token.span),
+            Node::List { span, .. } => return err!(Syntax, "lists are only allowed as the rows of mat[...]", span),
+            Node::BinaryOp { token, left, right } => {
+                let expr = match token.ty {
+                    TokenType::Add => Expr::Sum { left: Interned::new(Expr::convert(*left, session)?), right: Interned::new(Expr::convert(*right, session)?) },
+                    TokenType::Sub => Expr::Difference { left: Interned::new(Expr::convert(*left, session)?), right: Interned::new(Expr::convert(*right, session)?) },
+                    TokenType::Mul => Expr::Product { left: Interned::new(Expr::convert(*left, session)?), right: Interned::new(Expr::convert(*right, session)?) },
+                    TokenType::Div => Expr::Ratio { numerator: Interned::new(Expr::convert(*left, session)?), denominator: Interned::new(Expr::convert(*right, session)?) },
+                    TokenType::Pow => Expr::Power { base: Interned::new(Expr::convert(*left, session)?), exp: Interned::new(Expr::convert(*right, session)?) },
+                    TokenType::GreaterThan => Expr::GreaterThan { left: Interned::new(Expr::convert(*left, session)?), right: Interned::new(Expr::convert(*right, session)?) },
+                    TokenType::LessThan => Expr::LessThan { left: Interned::new(Expr::convert(*left, session)?), right: Interned::new(Expr::convert(*right, session)?) },
+                    TokenType::GreaterThanEq => Expr::GreaterThanEq { left: Interned::new(Expr::convert(*left, session)?), right: Interned::new(Expr::convert(*right, session)?) },
+                    TokenType::LessThanEq => Expr::LessThanEq { left: Interned::new(Expr::convert(*left, session)?), right: Interned::new(Expr::convert(*right, session)?) },
+                    TokenType::Equals => Expr::Equals { left: Interned::new(Expr::convert(*left, session)?), right: Interned::new(Expr::convert(*right, session)?) },
+                    TokenType::NotEquals => Expr::NotEquals { left: Interned::new(Expr::convert(*left, session)?), right: Interned::new(Expr::convert(*right, session)?) },
+                    TokenType::Arrow => return err!(Syntax, "'->' is only allowed in rule[...]", token.span),
+                    _ => unreachable!(),
+                };
+                matrix::check(&expr, token.span)?;
+                expr
+            },
+            Node::UnaryOp { token, node } => Expr::Negation(Interned::new(Expr::convert(*node, session)?)),
+            Node::Assign { span, .. } => return err!(Syntax, "assignments are only allowed as a whole statement", span),
+            Node::Derivative { name, order, args, span } => Expr::Derivative {
+                name: format!("{}", name.ty),
+                order,
+                args: args.into_iter().map(|x| Expr::convert(x, session)).collect::<Result<_>>()?,
+            },
+            Node::PostfixOp { token, node, span } => match token.ty {
+                TokenType::Bang => {
+                    let operand = Expr::convert(*node, session)?;
+                    if let Expr::Integer(n) = operand.clone().simplify() {
+                        if n.is_negative() { return err!(Domain, "factorial is undefined for negative integer {}", span; n) };
+                    }
+                    Expr::Factorial(Interned::new(operand))
+                },
+                TokenType::DoubleBang => {
+                    let operand = Expr::convert(*node, session)?;
+                    if let Expr::Integer(n) = operand.clone().simplify() {
+                        if n < BigInt::from(-1) { return err!(Domain, "double factorial is undefined for integer {}", span; n) };
+                    }
+                    Expr::DoubleFactorial(Interned::new(operand))
+                },
                 _ => unreachable!(),
             },
-            Node::UnaryOp { token, node } => Expr::Negation(Box::new(Expr::convert(*node)?)),
             Node::Call { name, params, args, span } => if let TokenType::Identifier(name) = name.ty {
                 match name.as_str() {
+                    "assume" => return err!(Syntax, "assumptions are only allowed as a whole statement", span),
+                    "rule" => return err!(Syntax, "rules are only allowed as a whole statement", span),
+                    "plot" => return err!(Syntax, "plots are only allowed as a whole statement", span),
                     "sqrt" => {
                         if args.len() != 1 { return err!(InvalidCall, "expected 1 argument, got {}", span; args.len()) };
                         if !params.is_empty() { return err!(InvalidCall, "expected 0 parameters, got {}", span; params.len()) };
-                        Expr::Root { index: Box::new(Expr::Integer(2)), radicand: Box::new(Expr::convert(args[0].clone())?) }
+                        Expr::Root { index: Interned::new(Expr::Integer(BigInt::from(2))), radicand: Interned::new(Expr::convert(args[0].clone(), session)?) }
                     },
                     "cbrt" => {
                         if args.len() != 1 { return err!(InvalidCall, "expected 1 argument, got {}", span; args.len()) };
                         if !params.is_empty() { return err!(InvalidCall, "expected 0 parameters, got {}", span; params.len()) };
-                        Expr::Root { index: Box::new(Expr::Integer(3)), radicand: Box::new(Expr::convert(args[0].clone())?) }
+                        Expr::Root { index: Interned::new(Expr::Integer(BigInt::from(3))), radicand: Interned::new(Expr::convert(args[0].clone(), session)?) }
                     },
                     "root" => {
                         if args.len() != 1 { return err!(InvalidCall, "expected 1 argument, got {}", span; args.len()) };
                         if params.len() != 1 { return err!(InvalidCall, "expected 1 parameters, got {}", span; params.len()) };
-                        Expr::Root { index: Box::new(Expr::convert(params[0].clone())?), radicand: Box::new(Expr::convert(args[0].clone())?) }
+                        Expr::Root { index: Interned::new(Expr::convert(params[0].clone(), session)?), radicand: Interned::new(Expr::convert(args[0].clone(), session)?) }
+                    },
+                    "factorial" => {
+                        if args.len() != 1 { return err!(InvalidCall, "expected 1 argument, got {}", span; args.len()) };
+                        if !params.is_empty() { return err!(InvalidCall, "expected 0 parameters, got {}", span; params.len()) };
+                        let operand = Expr::convert(args[0].clone(), session)?;
+                        if let Expr::Integer(n) = operand.clone().simplify() {
+                            if n.is_negative() { return err!(Domain, "factorial is undefined for negative integer {}", span; n) };
+                        }
+                        Expr::Factorial(Interned::new(operand))
+                    },
+                    "factorial2" => {
+                        if args.len() != 1 { return err!(InvalidCall, "expected 1 argument, got {}", span; args.len()) };
+                        if !params.is_empty() { return err!(InvalidCall, "expected 0 parameters, got {}", span; params.len()) };
+                        let operand = Expr::convert(args[0].clone(), session)?;
+                        if let Expr::Integer(n) = operand.clone().simplify() {
+                            if n < BigInt::from(-1) { return err!(Domain, "double factorial is undefined for integer {}", span; n) };
+                        }
+                        Expr::DoubleFactorial(Interned::new(operand))
+                    },
+                    "gamma" => {
+                        if args.len() != 1 { return err!(InvalidCall, "expected 1 argument, got {}", span; args.len()) };
+                        if !params.is_empty() { return err!(InvalidCall, "expected 0 parameters, got {}", span; params.len()) };
+                        let operand = Expr::convert(args[0].clone(), session)?;
+                        if let Expr::Integer(n) = operand.clone().simplify() {
+                            if !n.is_positive() { return err!(Domain, "gamma has a pole at non-positive integer {}", span; n) };
+                        }
+                        Expr::Gamma(Interned::new(operand))
+                    },
+                    "binomial" => {
+                        if args.len() != 2 { return err!(InvalidCall, "expected 2 arguments, got {}", span; args.len()) };
+                        if !params.is_empty() { return err!(InvalidCall, "expected 0 parameters, got {}", span; params.len()) };
+                        Expr::Binomial { n: Interned::new(Expr::convert(args[0].clone(), session)?), k: Interned::new(Expr::convert(args[1].clone(), session)?) }
+                    },
+                    "diff" => {
+                        if args.len() != 1 { return err!(InvalidCall, "expected 1 argument, got {}", span; args.len()) };
+                        if params.len() != 1 { return err!(InvalidCall, "expected 1 parameters, got {}", span; params.len()) };
+                        let var = param_var(&params[0], span)?;
+                        let expr = scoped_arg(args[0].clone(), &[&var], session)?;
+                        let Some(derivative) = expr.differentiate(&var) else {
+                            return err!(InvalidCall, "cannot differentiate {} with respect to {}", span; expr, var);
+                        };
+                        derivative
+                    },
+                    "subs" => {
+                        if args.len() != 1 { return err!(InvalidCall, "expected 1 argument, got {}", span; args.len()) };
+                        if params.is_empty() || params.len() % 2 != 0 { return err!(InvalidCall, "expected pairs of parameters, got {}", span; params.len()) };
+                        let rules = params.chunks(2)
+                            .map(|pair| {
+                                let pattern = match &pair[0] {
+                                    Node::Variable { name } => Expr::Variable(format!("{}", name.ty)),
+                                    other => Expr::convert(other.clone(), session)?.simplify(),
+                                };
+                                Ok((pattern, Expr::convert(pair[1].clone(), session)?))
+                            })
+                            .collect::<Result<Vec<_>>>()?;
+                        Expr::convert(args[0].clone(), session)?.simplify().replace(&rules)
+                    },
+                    "series" => {
+                        if args.len() != 1 { return err!(InvalidCall, "expected 1 argument, got {}", span; args.len()) };
+                        if params.len() != 3 { return err!(InvalidCall, "expected 3 parameters, got {}", span; params.len()) };
+                        let var = param_var(&params[0], span)?;
+                        let point = Expr::convert(params[1].clone(), session)?.simplify();
+                        if limit::contains_infinity(&point) { return err!(Domain, "expected a finite point to expand around, got {}", span; point) };
+                        let Some(order) = Expr::convert(params[2].clone(), session)?.simplify().as_integer().and_then(|x| x.to_i64()) else {
+                            return err!(InvalidCall, "expected an integer order", span);
+                        };
+                        if order > MAX_SERIES_ORDER { return err!(InvalidCall, "expected an order of at most {}, got {}", span; MAX_SERIES_ORDER, order) };
+                        let expr = scoped_arg(args[0].clone(), &[&var], session)?;
+                        let Some(series) = expr.series(&var, &point, order) else {
+                            return err!(InvalidCall, "cannot expand {} as a series in {} around {}", span; expr, var, point);
+                        };
+                        series
+                    },
+                    "rationalize" => {
+                        if args.len() != 1 { return err!(InvalidCall, "expected 1 argument, got {}", span; args.len()) };
+                        if !params.is_empty() { return err!(InvalidCall, "expected 0 parameters, got {}", span; params.len()) };
+                        Expr::convert(args[0].clone(), session)?.rationalize()
+                    },
+                    "equiv" => {
+                        if args.len() != 2 { return err!(InvalidCall, "expected 2 arguments, got {}", span; args.len()) };
+                        if !params.is_empty() { return err!(InvalidCall, "expected 0 parameters, got {}", span; params.len()) };
+                        let a = Expr::convert(args[0].clone(), session)?;
+                        let b = Expr::convert(args[1].clone(), session)?;
+                        Expr::Equivalence(a.equivalent(&b, &session.assumptions))
+                    },
+                    "interval" => {
+                        if args.len() != 1 { return err!(InvalidCall, "expected 1 argument, got {}", span; args.len()) };
+                        if params.is_empty() || params.len() % 3 != 0 { return err!(InvalidCall, "expected triples of parameters like x:1:2, got {}", span; params.len()) };
+                        let vars = params.chunks(3)
+                            .map(|triple| {
+                                let (lo, lo_bounds) = bound_arg(triple[1].clone(), session, span)?;
+                                let (hi, hi_bounds) = bound_arg(triple[2].clone(), session, span)?;
+                                match interval::Interval::new(lo_bounds.lo(), hi_bounds.hi()) {
+                                    Some(range) => Ok((param_var(&triple[0], span)?, range)),
+                                    None => err!(Domain, "the lower bound {} is above the upper bound {}", span; lo, hi),
+                                }
+                            })
+                            .collect::<Result<Vec<_>>>()?;
+                        let names: Vec<&str> = vars.iter().map(|(var, _)| var.as_str()).collect();
+                        let expr = scoped_arg(args[0].clone(), &names, session)?.simplify();
+                        let Some(bounds) = expr.enclose(&vars) else {
+                            return err!(InvalidCall, "cannot bound {} over the given intervals", span; expr);
+                        };
+                        Expr::Interval(bounds)
+                    },
+                    "nsolve" => {
+                        if args.len() != 1 { return err!(InvalidCall, "expected 1 argument, got {}", span; args.len()) };
+                        if params.len() != 2 { return err!(InvalidCall, "expected 2 parameters, got {}", span; params.len()) };
+                        let var = param_var(&params[0], span)?;
+                        let (start, _) = bound_arg(params[1].clone(), session, span)?;
+                        let start = start.to_f64().unwrap();
+                        let expr = match scoped_arg(args[0].clone(), &[&var], session)? {
+                            Expr::Equals { left, right } => Expr::Difference { left, right },
+                            expr => expr,
+                        };
+                        let Some(mut solution) = expr.nsolve(&var, start) else {
+                            return err!(InvalidCall, "cannot find a root of {} in {} near {}", span; expr, var, start);
+                        };
+                        if session.assumptions.constrains(&var) {
+                            solution.roots.retain(|r| r.im == 0.0 && session.assumptions.allows(&[(var.clone(), Expr::decimal(r.re))]));
+                            if solution.roots.is_empty() {
+                                return err!(Domain, "no root of {} found near {} satisfies the assumptions about {}", span; expr, start, var);
+                            }
+                        }
+                        Expr::Solution(solution)
+                    },
+                    "nint" => {
+                        if args.len() != 1 { return err!(InvalidCall, "expected 1 argument, got {}", span; args.len()) };
+                        if params.len() != 3 { return err!(InvalidCall, "expected 3 parameters, got {}", span; params.len()) };
+                        let var = param_var(&params[0], span)?;
+                        let (from, to) = (float_arg(params[1].clone(), session, span)?, float_arg(params[2].clone(), session, span)?);
+                        let expr = scoped_arg(args[0].clone(), &[&var], session)?;
+                        let Some(integral) = expr.nint(&var, from, to) else {
+                            return err!(InvalidCall, "cannot integrate {} over {} from {} to {}, as it is not finite or not numeric", span; expr, var, from, to);
+                        };
+                        Expr::Integral(integral)
+                    },
+                    "mat" => {
+                        if args.is_empty() { return err!(InvalidCall, "expected at least 1 row", span) };
+                        if !params.is_empty() { return err!(InvalidCall, "expected 0 parameters, got {}", span; params.len()) };
+                        let rows = args.into_iter()
+                            .map(|row| match row {
+                                Node::List { items, .. } => items.into_iter().map(|x| Expr::convert(x, session)).collect::<Result<Vec<_>>>(),
+                                _ => err!(InvalidCall, "expected rows like [1, 2]", span),
+                            })
+                            .collect::<Result<Vec<_>>>()?;
+                        if rows[0].is_empty() || rows.iter().any(|row| row.len() != rows[0].len()) {
+                            return err!(InvalidCall, "expected non-empty rows of equal length", span);
+                        }
+                        Expr::Matrix { rows }
+                    },
+                    "vec" => {
+                        if args.is_empty() { return err!(InvalidCall, "expected at least 1 argument", span) };
+                        if !params.is_empty() { return err!(InvalidCall, "expected 0 parameters, got {}", span; params.len()) };
+                        Expr::Matrix { rows: args.into_iter().map(|x| Ok(vec![Expr::convert(x, session)?])).collect::<Result<_>>()? }
+                    },
+                    "transpose" => {
+                        if args.len() != 1 { return err!(InvalidCall, "expected 1 argument, got {}", span; args.len()) };
+                        if !params.is_empty() { return err!(InvalidCall, "expected 0 parameters, got {}", span; params.len()) };
+                        let Expr::Matrix { rows } = Expr::convert(args[0].clone(), session)?.simplify() else {
+                            return err!(InvalidCall, "expected a matrix", span);
+                        };
+                        Expr::Matrix { rows: matrix::transpose(&rows) }
+                    },
+                    "det" | "inv" | "rank" | "rref" | "nullspace" | "charpoly" | "eigenvals" => {
+                        if args.len() != 1 { return err!(InvalidCall, "expected 1 argument, got {}", span; args.len()) };
+                        let max_params = if name == "charpoly" { 1 } else { 0 };
+                        if params.len() > max_params { return err!(InvalidCall, "expected at most {} parameters, got {}", span; max_params, params.len()) };
+                        let Expr::Matrix { rows } = Expr::convert(args[0].clone(), session)?.simplify() else {
+                            return err!(InvalidCall, "expected a matrix", span);
+                        };
+                        let (n, m) = matrix::dims(&rows);
+                        if n != m && !matches!(name.as_str(), "rank" | "rref" | "nullspace") {
+                            return err!(InvalidCall, "expected a square matrix, got {}x{}", span; n, m);
+                        }
+
+                        match name.as_str() {
+                            "det" | "inv" => {
+                                let Some(det) = matrix::det(&rows) else {
+                                    return err!(InvalidCall, "symbolic matrices above {}x{} are not supported", span; matrix::MAX_SYMBOLIC_SIZE, matrix::MAX_SYMBOLIC_SIZE);
+                                };
+                                if name == "det" {
+                                    det
+                                } else if det.is_numeric() && Number::from_expr(&det).unwrap().is_zero() {
+                                    return err!(Domain, "matrix is singular", span);
+                                } else {
+                                    Expr::Matrix { rows: matrix::inverse(&rows, &det) }
+                                }
+                            },
+                            "rank" => Expr::Integer(BigInt::from(matrix::rank(&rows))),
+                            "rref" => Expr::Matrix { rows: matrix::rref(&rows).0 },
+                            "nullspace" => Expr::Matrix { rows: matrix::nullspace(&rows) },
+                            "charpoly" => {
+                                let var = match params.first() {
+                                    Some(param) => param_var(param, span)?,
+                                    None => "x".to_string(),
+                                };
+                                polynomial::from_coefficients(&matrix::charpoly(&rows), &Expr::Variable(var))
+                            },
+                            _ => {
+                                let Some(roots) = polynomial::roots(&matrix::charpoly(&rows)) else {
+                                    return err!(InvalidCall, "cannot find the eigenvalues in closed form", span);
+                                };
+                                Expr::Matrix { rows: roots.into_iter().map(|x| vec![x]).collect() }
+                            },
+                        }
+                    },
+                    "gcd" | "lcm" => {
+                        if args.len() < 2 { return err!(InvalidCall, "expected at least 2 arguments, got {}", span; args.len()) };
+                        if !params.is_empty() { return err!(InvalidCall, "expected 0 parameters, got {}", span; params.len()) };
+                        let ns = args.into_iter().map(|x| integer_arg(x, session, span)).collect::<Result<Vec<_>>>()?;
+                        Expr::Integer(ns[1..].iter().fold(ns[0].abs(), |acc, n| match name.as_str() {
+                            "gcd" => number_theory::signed_gcd(&acc, n),
+                            _ => number_theory::lcm(&acc, n),
+                        }))
+                    },
+                    "isprime" | "factorint" | "totient" | "divisors" | "nextprime" | "modpow" | "modinv" => {
+                        let arity = match name.as_str() { "modpow" => 3, "modinv" => 2, _ => 1 };
+                        if args.len() != arity { return err!(InvalidCall, "expected {} arguments, got {}", span; arity, args.len()) };
+                        if !params.is_empty() { return err!(InvalidCall, "expected 0 parameters, got {}", span; params.len()) };
+                        let ns = args.into_iter().map(|x| integer_arg(x, session, span)).collect::<Result<Vec<_>>>()?;
+                        let n = &ns[0];
+
+                        let factors = || -> Result<Vec<(BigInt, u32)>> {
+                            if n.is_zero() { return err!(Domain, "0 has no prime factorization", span) };
+                            match number_theory::factorize(n) {
+                                Some(factors) => Ok(factors),
+                                None => err!(InvalidCall, "could not factor {}", span; n),
+                            }
+                        };
+                        let modulus = |m: &BigInt| -> Result<()> {
+                            if !m.is_positive() { return err!(Domain, "expected a positive modulus, got {}", span; m) };
+                            Ok(())
+                        };
+
+                        match name.as_str() {
+                            "isprime" => Expr::Boolean(number_theory::is_prime(n)),
+                            "factorint" => Expr::Factorization { negative: n.is_negative(), factors: factors()? },
+                            "totient" => {
+                                if !n.is_positive() { return err!(Domain, "totient is only defined for positive integers", span) };
+                                Expr::Integer(number_theory::totient(&factors()?))
+                            },
+                            "divisors" => {
+                                let factors = factors()?;
+                                if number_theory::divisor_count(&factors).is_none_or(|count| count > MAX_DIVISORS) {
+                                    return err!(InvalidCall, "{} has more than {} divisors", span; n, MAX_DIVISORS);
+                                }
+                                Expr::Matrix { rows: vec![number_theory::divisors(&factors).into_iter().map(Expr::Integer).collect()] }
+                            },
+                            "nextprime" => Expr::Integer(number_theory::next_prime(n)),
+                            "modpow" => {
+                                modulus(&ns[2])?;
+                                match number_theory::mod_pow(n, &ns[1], &ns[2]) {
+                                    Some(x) => Expr::Integer(x),
+                                    None => return err!(Domain, "{} has no inverse modulo {}", span; n, ns[2]),
+                                }
+                            },
+                            _ => {
+                                modulus(&ns[1])?;
+                                match number_theory::mod_inverse(n, &ns[1]) {
+                                    Some(x) => Expr::Integer(x),
+                                    None => return err!(Domain, "{} has no inverse modulo {}", span; n, ns[1]),
+                                }
+                            },
+                        }
+                    },
+                    "mod" | "factormod" => {
+                        if args.len() != 1 { return err!(InvalidCall, "expected 1 argument, got {}", span; args.len()) };
+                        if params.len() != 1 { return err!(InvalidCall, "expected 1 parameters, got {}", span; params.len()) };
+                        let n = integer_arg(params[0].clone(), session, span)?;
+                        if !n.is_positive() { return err!(Domain, "expected a positive modulus, got {}", span; n) };
+                        if name == "factormod" && !number_theory::is_prime(&n) { return err!(Domain, "expected a prime modulus, got {}", span; n) };
+                        let expr = Expr::convert(args[0].clone(), session)?;
+                        let mut modulus = modular::Modulus::new(n.clone());
+
+                        if name == "mod" {
+                            match modulus.reduce_expr(&expr) {
+                                Ok(x) => x,
+                                Err(failure) => return modular_failure(failure, &n, span),
+                            }
+                        } else {
+                            let poly = match modulus.reduce(&expr) {
+                                Ok(poly) => poly,
+                                Err(failure) => return modular_failure(failure, &n, span),
+                            };
+                            if poly.is_empty() { return err!(Domain, "0 has no factorization", span) };
+                            let Some((lead, factors)) = modulus.factor(&poly) else {
+                                return err!(InvalidCall, "could not factor {} modulo {}", span; modulus.to_expr(&poly), n);
+                            };
+                            let lead = (!lead.is_one() || factors.is_empty()).then_some(Expr::Integer(lead));
+                            lead.into_iter()
+                                .chain(factors.iter().map(|(f, k)| match k {
+                                    1 => modulus.to_expr(f),
+                                    k => Expr::Power { base: modulus.to_expr(f).boxed(), exp: Expr::Integer(BigInt::from(*k)).boxed() },
+                                }))
+                                .reduce(|acc, x| Expr::Product { left: acc.boxed(), right: x.boxed() })
+                                .unwrap()
+                        }
+                    },
+                    "sum" | "prod" => {
+                        if args.len() != 1 { return err!(InvalidCall, "expected 1 argument, got {}", span; args.len()) };
+                        if params.len() != 3 { return err!(InvalidCall, "expected 3 parameters, got {}", span; params.len()) };
+                        let var = param_var(&params[0], span)?;
+                        let from = Expr::convert(params[1].clone(), session)?;
+                        let to = Expr::convert(params[2].clone(), session)?;
+                        let body = scoped_arg(args[0].clone(), &[&var], session)?.boxed();
+                        if name == "sum" {
+                            Expr::IndexedSum { var, from: from.boxed(), to: to.boxed(), body }
+                        } else {
+                            Expr::IndexedProduct { var, from: from.boxed(), to: to.boxed(), body }
+                        }
+                    },
+                    "lim" => {
+                        if args.len() != 1 { return err!(InvalidCall, "expected 1 argument, got {}", span; args.len()) };
+                        if !(2..=3).contains(&params.len()) { return err!(InvalidCall, "expected 2 or 3 parameters, got {}", span; params.len()) };
+                        let var = param_var(&params[0], span)?;
+                        let point = Expr::convert(params[1].clone(), session)?.simplify();
+                        let direction = match params.get(2) {
+                            None => Direction::Both,
+                            Some(Node::Sign { token }) if matches!(token.ty, TokenType::Add) => Direction::Above,
+                            Some(Node::Sign { token }) if matches!(token.ty, TokenType::Sub) => Direction::Below,
+                            Some(_) => return err!(InvalidCall, "expected '+' or '-' as the direction", span),
+                        };
+                        let expr = scoped_arg(args[0].clone(), &[&var], session)?;
+                        let Some(limit) = expr.limit(&var, &point, direction) else {
+                            if let (Some(below), Some(above)) = (expr.limit(&var, &point, Direction::Below), expr.limit(&var, &point, Direction::Above)) {
+                                return err!(Domain, "the limit from below is {} but the limit from above is {}", span; below, above);
+                            }
+                            return err!(InvalidCall, "cannot find the limit of {} as {} approaches {}", span; expr, var, point);
+                        };
+                        limit
+                    },
+                    user_fn => {
+                        if !params.is_empty() { return err!(InvalidCall, "expected 0 parameters, got {}", span; params.len()) };
+                        let expr = Expr::Function { name: user_fn.to_string(), args: args.into_iter().map(|x| Expr::convert(x, session)).collect::<Result<_>>()? };
+                        matrix::check(&expr, span)?;
+                        expr
                     },
-                    _user_fn => todo!(),
                 }
             } else { unreachable!() },
         })
@@ -120,12 +730,86 @@ impl Expr {
 }
 
 
+/// Converts an argument that must simplify to an integer, like the `n` in `isprime[n]`.
+fn integer_arg(node: Node, session: &Session, span: Span) -> Result<BigInt> {
+    match Expr::convert(node, session)?.simplify() {
+        Expr::Integer(n) => Ok(n),
+        other => err!(InvalidCall, "expected an integer, got {}", span; other),
+    }
+}
+
+/// Converts an argument that must be a number, like the `1` in `interval:x:1:2[...]`, along with
+/// bounds on its value.
+fn bound_arg(node: Node, session: &Session, span: Span) -> Result<(Expr, interval::Interval)> {
+    let expr = Expr::convert(node, session)?.simplify();
+    match expr.enclose(&[]) {
+        Some(bounds) => Ok((expr, bounds)),
+        None => err!(InvalidCall, "expected a numeric bound, got {}", span; expr),
+    }
+}
+
+/// Converts an argument that must evaluate to a float, possibly infinite, like the bounds of
+/// `nint:x:0:inf[...]`.
+fn float_arg(node: Node, session: &Session, span: Span) -> Result<f64> {
+    let expr = Expr::convert(node, session)?.simplify();
+    match expr.to_f64().filter(|v| !v.is_nan()) {
+        Some(v) => Ok(v),
+        None => err!(InvalidCall, "expected a numeric bound, got {}", span; expr),
+    }
+}
+
+/// Reports why an expression could not be reduced modulo `n`.
+fn modular_failure<T>(failure: modular::Failure, n: &BigInt, span: Span) -> Result<T> {
+    match failure {
+        modular::Failure::NotInvertible(x) => err!(Domain, "{} has no inverse modulo {}", span; x, n),
+        modular::Failure::Multivariate(a, b) => err!(InvalidCall, "expected a polynomial in one variable, got both {} and {}", span; a, b),
+        modular::Failure::Unsupported(x) => err!(InvalidCall, "cannot reduce {} modulo {}", span; x, n),
+    }
+}
+
+/// Converts the argument of a call that binds `vars` itself, like the body of `sum:i:1:n[...]`, so
+/// that values the session has for them are not substituted.
+pub fn scoped_arg(node: Node, vars: &[&str], session: &Session) -> Result<Expr> {
+    let mut scope = session.clone();
+    for var in vars {
+        scope.unbind(var);
+    }
+    Expr::convert(node, &scope)
+}
+
+/// Reads the name of a variable passed as a call parameter, like the `x` in `diff:x[...]`.
+pub fn param_var(node: &Node, span: Span) -> Result<String> {
+    match node {
+        Node::Variable { name } => Ok(format!("{}", name.ty)),
+        _ => err!(InvalidCall, "expected a variable parameter", span),
+    }
+}
+
+
 impl std::fmt::Display for Expr {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Expr::Integer(c) => write!(f, "{}", c),
             Expr::Decimal(v) => write!(f, "{}", v),
             Expr::Variable(s) => write!(f, "{}", s),
+            Expr::Infinity => write!(f, "∞"),
+            Expr::Boolean(b) => write!(f, "{}", b),
+            Expr::Equivalence(e) => write!(f, "{}", e),
+            Expr::Interval(i) => write!(f, "{}", i),
+            Expr::Solution(s) => write!(f, "{}", s),
+            Expr::Integral(i) => write!(f, "{}", i),
+            Expr::Factorization { negative, factors } => {
+                if *negative {
+                    write!(f, "-")?;
+                }
+                if factors.is_empty() {
+                    return write!(f, "1");
+                }
+                write!(f, "{}", factors.iter()
+                    .map(|(p, k)| if *k == 1 { p.to_string() } else { format!("{}{}", p, utils::superscript(&k.to_string())) })
+                    .collect::<Vec<_>>()
+                    .join("·"))
+            },
             Expr::Negation(node) => write!(f, "-{}", node),
             Expr::Sum { left, right } => write!(f, "({} + {})", left, right),
             Expr::Difference { left, right } => write!(f, "({} - {})", left, right),
@@ -133,6 +817,28 @@ impl std::fmt::Display for Expr {
             Expr::Ratio { numerator, denominator } => write!(f, "({} / {})", numerator, denominator),
             Expr::Power { base, exp } => write!(f, "({} ^ {})", base, exp),
             Expr::Root { index, radicand } => write!(f, "{}√{}", utils::superscript(&format!("{}", index)), radicand),
+            Expr::Factorial(node) => write!(f, "{}!", PostfixOperand(node)),
+            Expr::DoubleFactorial(node) => write!(f, "{}!!", PostfixOperand(node)),
+            Expr::Gamma(node) => write!(f, "Γ({})", node),
+            Expr::Binomial { n, k } => write!(f, "C({}, {})", n, k),
+            Expr::IndexedSum { var, from, to, body } => write!(f, "Σ({} = {}..{}) {}", var, from, to, body),
+            Expr::IndexedProduct { var, from, to, body } => write!(f, "Π({} = {}..{}) {}", var, from, to, body),
+            Expr::Matrix { rows } if f.alternate() => matrix::fmt_box(rows, f),
+            Expr::Matrix { rows } => write!(f, "[{}]", rows.iter()
+                .map(|row| format!("[{}]", row.iter().map(|x| x.to_string()).collect::<Vec<_>>().join(", ")))
+                .collect::<Vec<_>>()
+                .join(", ")),
+            Expr::Function { name, args } => write!(f, "{}[{}]", name, args.iter().map(|x| x.to_string()).collect::<Vec<_>>().join(", ")),
+            Expr::Order { base, exp } => match &**exp {
+                Expr::Integer(e) if e.is_one() => write!(f, "O({})", base),
+                Expr::Integer(_) => write!(f, "O({}{})", base, utils::superscript(&format!("{}", exp))),
+                _ => write!(f, "O({} ^ {})", base, exp),
+            },
+            Expr::Derivative { name, order, args } => if args.is_empty() {
+                write!(f, "{}{}", name, "'".repeat(*order))
+            } else {
+                write!(f, "{}{}[{}]", name, "'".repeat(*order), args.iter().map(|x| x.to_string()).collect::<Vec<_>>().join(", "))
+            },
             Expr::Equals { left, right } => write!(f, "{} = {}", left, right),
             Expr::NotEquals { left, right } => write!(f, "{} != {}", left, right),
             Expr::GreaterThan { left, right } => write!(f, "{} > {}", left, right),
@@ -142,3 +848,44 @@ impl std::fmt::Display for Expr {
         }
     }
 }
+
+
+/// Displays the operand of a postfix operator, adding parentheses when it would otherwise be ambiguous.
+struct PostfixOperand<'a>(&'a Expr);
+
+impl std::fmt::Display for PostfixOperand<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            Expr::Integer(c) if !c.is_negative() => write!(f, "{}", c),
+            Expr::Decimal(v) if *v >= 0.0 => write!(f, "{}", v),
+            Expr::Variable(_)
+            | Expr::Sum { .. }
+            | Expr::Difference { .. }
+            | Expr::Ratio { .. }
+            | Expr::Power { .. } => write!(f, "{}", self.0),
+            other => write!(f, "({})", other),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::eval;
+
+    #[test]
+    fn integer_literals_are_exact() {
+        assert_eq!(eval("9007199254740993"), "9007199254740993");
+        assert_eq!(eval("9007199254740993 - 9007199254740992"), "1");
+        assert_eq!(eval("isprime[9007199254740993]"), "false");
+        assert_eq!(eval("isprime[9007199254740997]"), "true");
+        assert_eq!(eval("factorint[1000000016000000063]"), "1000000007·1000000009");
+    }
+
+    #[test]
+    fn substitution() {
+        assert_eq!(eval("subs:x:3[x^2 + y]"), "(9 + y)");
+        assert_eq!(eval("subs:x:y:y:x[x - y]"), "(y + -x)");
+        assert_eq!(eval("subs:x^2:u[x^4 + x^2]"), "((x ^ 4) + u)");
+        assert_eq!(eval("subs:(sin[x]):s[sin[x]^2 + cos[x]]"), "((s ^ 2) + cos[x])");
+    }
+}
@@ -0,0 +1,409 @@
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_traits::{One, Signed, ToPrimitive, Zero};
+
+use super::{matrix, number::Number, number_theory, polynomial, Expr};
+
+
+/// Factorials of integers above this are not reduced term by term.
+const MAX_FACTORIAL: u64 = 1_000_000;
+
+/// Polynomials are not raised to powers of a higher degree than this.
+const MAX_DEGREE: u64 = 10_000;
+
+/// Random polynomials tried for each factor before equal-degree factorization gives up.
+const MAX_SPLIT_ATTEMPTS: usize = 1000;
+
+/// Why an expression could not be reduced.
+pub enum Failure {
+    /// Division by a value with no inverse.
+    NotInvertible(Expr),
+    /// Both of these variables appear, where polynomials are limited to one.
+    Multivariate(String, String),
+    /// A part with no meaning modulo `n`, like a decimal or a variable exponent.
+    Unsupported(Expr),
+}
+
+/// A polynomial with coefficients reduced modulo `n`, from the constant term up, with no trailing
+/// zeros so that zero itself is empty.
+pub type Poly = Vec<BigInt>;
+
+/// Arithmetic on the integers modulo `n`, and on polynomials over them in a single variable.
+pub struct Modulus {
+    n: BigInt,
+    var: Option<String>,
+}
+
+fn degree(a: &Poly) -> usize {
+    a.len().saturating_sub(1)
+}
+
+impl Modulus {
+    pub fn new(n: BigInt) -> Self {
+        Self { n, var: None }
+    }
+
+    fn poly(&self, coeffs: impl IntoIterator<Item = BigInt>) -> Poly {
+        let mut coeffs: Poly = coeffs.into_iter().map(|c| c.mod_floor(&self.n)).collect();
+        while coeffs.last().is_some_and(Zero::is_zero) {
+            coeffs.pop();
+        }
+        coeffs
+    }
+
+    fn constant(&self, c: BigInt) -> Poly {
+        self.poly([c])
+    }
+
+    fn inverse(&self, c: &BigInt) -> Option<BigInt> {
+        number_theory::mod_inverse(c, &self.n)
+    }
+
+    fn add(&self, a: &Poly, b: &Poly) -> Poly {
+        self.poly((0..a.len().max(b.len())).map(|i| a.get(i).cloned().unwrap_or_default() + b.get(i).cloned().unwrap_or_default()))
+    }
+
+    fn neg(&self, a: &Poly) -> Poly {
+        self.poly(a.iter().map(|c| -c))
+    }
+
+    fn sub(&self, a: &Poly, b: &Poly) -> Poly {
+        self.add(a, &self.neg(b))
+    }
+
+    fn mul(&self, a: &Poly, b: &Poly) -> Poly {
+        if a.is_empty() || b.is_empty() {
+            return Poly::new();
+        }
+        let mut result = vec![BigInt::zero(); a.len() + b.len() - 1];
+        for (i, x) in a.iter().enumerate() {
+            for (j, y) in b.iter().enumerate() {
+                result[i + j] += x * y;
+            }
+        }
+        self.poly(result)
+    }
+
+    /// The quotient and remainder of `a / b`, or `None` if the leading coefficient of `b` has no inverse.
+    fn div_rem(&self, a: &Poly, b: &Poly) -> Option<(Poly, Poly)> {
+        let lead = self.inverse(b.last()?)?;
+        let mut rem = a.clone();
+        let mut quotient = vec![BigInt::zero(); a.len().saturating_sub(b.len()) + 1];
+        while rem.len() >= b.len() {
+            let shift = rem.len() - b.len();
+            let c = (rem.last().unwrap() * &lead).mod_floor(&self.n);
+            for (i, x) in b.iter().enumerate() {
+                rem[shift + i] -= &c * x;
+            }
+            quotient[shift] = c;
+            rem = self.poly(rem);
+        }
+        Some((self.poly(quotient), rem))
+    }
+
+    fn rem(&self, a: &Poly, b: &Poly) -> Poly {
+        self.div_rem(a, b).unwrap().1
+    }
+
+    /// `a^k mod m` by repeated squaring, or just `a^k` when `m` is `None`.
+    fn pow(&self, a: &Poly, k: &BigInt, m: Option<&Poly>) -> Poly {
+        let reduce = |x: Poly| match m {
+            Some(m) => self.rem(&x, m),
+            None => x,
+        };
+        let mut result = self.constant(BigInt::one());
+        let base = reduce(a.clone());
+        for i in (0..k.bits()).rev() {
+            result = reduce(self.mul(&result, &result));
+            if k.bit(i) {
+                result = reduce(self.mul(&result, &base));
+            }
+        }
+        result
+    }
+
+    /// Reads `expr` as an element of `Z/nZ[x]`, taking each operation modulo `n`.
+    pub fn reduce(&mut self, expr: &Expr) -> Result<Poly, Failure> {
+        Ok(match expr {
+            Expr::Integer(k) => self.constant(k.clone()),
+            Expr::Variable(name) => {
+                match &self.var {
+                    Some(var) if var != name => return Err(Failure::Multivariate(var.clone(), name.clone())),
+                    _ => self.var = Some(name.clone()),
+                }
+                self.poly([BigInt::zero(), BigInt::one()])
+            },
+            Expr::Negation(node) => {
+                let a = self.reduce(node)?;
+                self.neg(&a)
+            },
+            Expr::Sum { left, right } => {
+                let (a, b) = (self.reduce(left)?, self.reduce(right)?);
+                self.add(&a, &b)
+            },
+            Expr::Difference { left, right } => {
+                let (a, b) = (self.reduce(left)?, self.reduce(right)?);
+                self.sub(&a, &b)
+            },
+            Expr::Product { left, right } => {
+                let (a, b) = (self.reduce(left)?, self.reduce(right)?);
+                self.mul(&a, &b)
+            },
+            Expr::Ratio { numerator, denominator } => {
+                let (a, b) = (self.reduce(numerator)?, self.reduce(denominator)?);
+                match self.div_rem(&a, &b) {
+                    Some((quotient, rem)) if rem.is_empty() => quotient,
+                    _ => return Err(Failure::NotInvertible(denominator.clone().into_inner())),
+                }
+            },
+            Expr::Power { base, exp } => {
+                let Some(k) = exp.clone().simplify().as_integer().cloned() else {
+                    return Err(Failure::Unsupported(exp.clone().into_inner()));
+                };
+                let a = self.reduce(base)?;
+                if degree(&a) > 0 && k.to_u64().is_none_or(|k| k.saturating_mul(degree(&a) as u64) > MAX_DEGREE) {
+                    return Err(Failure::Unsupported(expr.clone()));
+                }
+                if !k.is_negative() {
+                    return Ok(self.pow(&a, &k, None));
+                }
+                match a.as_slice() {
+                    [c] => match self.inverse(c) {
+                        Some(inv) => self.pow(&self.constant(inv), &-k, None),
+                        None => return Err(Failure::NotInvertible(base.clone().into_inner())),
+                    },
+                    _ => return Err(Failure::NotInvertible(base.clone().into_inner())),
+                }
+            },
+            Expr::Factorial(node) => {
+                let Some(k) = node.clone().simplify().as_integer().and_then(|k| k.to_u64()) else {
+                    return Err(Failure::Unsupported(expr.clone()));
+                };
+                // `n` divides `k!` as soon as `k >= n`.
+                if BigInt::from(k) >= self.n {
+                    return Ok(Poly::new());
+                }
+                if k > MAX_FACTORIAL {
+                    return Err(Failure::Unsupported(expr.clone()));
+                }
+                self.constant((1..=k).fold(BigInt::one(), |acc, i| (acc * i).mod_floor(&self.n)))
+            },
+            other => match Number::from_expr(&other.clone().simplify()) {
+                Some(Number::Rational(r)) => match self.inverse(r.denom()) {
+                    Some(inv) => self.constant(r.numer() * inv),
+                    None => return Err(Failure::NotInvertible(Expr::Integer(r.denom().clone()))),
+                },
+                _ => return Err(Failure::Unsupported(other.clone())),
+            },
+        })
+    }
+
+    /// Reduces `expr` modulo `n`, entry by entry for matrices.
+    pub fn reduce_expr(&mut self, expr: &Expr) -> Result<Expr, Failure> {
+        if matrix::is_scalar(expr) {
+            let a = self.reduce(expr)?;
+            return Ok(self.to_expr(&a));
+        }
+
+        match expr.clone().simplify() {
+            Expr::Matrix { rows } => Ok(Expr::Matrix {
+                rows: rows.iter()
+                    .map(|row| row.iter().map(|x| self.reduce_expr(x)).collect())
+                    .collect::<Result<_, _>>()?,
+            }),
+            other => Err(Failure::Unsupported(other)),
+        }
+    }
+
+    pub fn to_expr(&self, a: &Poly) -> Expr {
+        let coeffs: Vec<Expr> = a.iter().cloned().map(Expr::Integer).collect();
+        match &self.var {
+            Some(var) => polynomial::from_coefficients(&coeffs, &Expr::Variable(var.clone())),
+            None => coeffs.into_iter().next().unwrap_or(Expr::Integer(BigInt::zero())),
+        }
+    }
+
+    fn gcd(&self, a: &Poly, b: &Poly) -> Poly {
+        let (mut a, mut b) = (a.clone(), b.clone());
+        while !b.is_empty() {
+            let r = self.rem(&a, &b);
+            a = std::mem::replace(&mut b, r);
+        }
+        self.monic(&a)
+    }
+
+    fn monic(&self, a: &Poly) -> Poly {
+        match a.last() {
+            Some(lead) => {
+                let inv = self.inverse(lead).unwrap();
+                self.poly(a.iter().map(|c| c * &inv))
+            },
+            None => Poly::new(),
+        }
+    }
+
+    fn derivative(&self, a: &Poly) -> Poly {
+        self.poly(a.iter().enumerate().skip(1).map(|(i, c)| c * i))
+    }
+
+    fn is_one(a: &Poly) -> bool {
+        a.len() == 1 && a[0].is_one()
+    }
+
+    /// The factorization of a non-zero polynomial over `GF(p)`, taking `n` as the prime `p`, as its
+    /// leading coefficient and its monic irreducible factors with their multiplicities.
+    ///
+    /// Returns `None` if a factor of equal-degree parts could not be split.
+    pub fn factor(&self, a: &Poly) -> Option<(BigInt, Vec<(Poly, u32)>)> {
+        let lead = a.last()?.clone();
+        let mut factors = Vec::new();
+        for (f, k) in self.square_free(&self.monic(a)) {
+            for (g, d) in self.distinct_degree(&f) {
+                for h in self.equal_degree(&g, d)? {
+                    factors.push((h, k));
+                }
+            }
+        }
+        factors.sort_by(|(a, _), (b, _)| a.len().cmp(&b.len()).then_with(|| a.iter().rev().cmp(b.iter().rev())));
+        Some((lead, factors))
+    }
+
+    /// Splits a monic polynomial into square-free parts with their multiplicities.
+    fn square_free(&self, f: &Poly) -> Vec<(Poly, u32)> {
+        let mut result = Vec::new();
+        let mut c = self.gcd(f, &self.derivative(f));
+        let mut w = self.div_rem(f, &c).unwrap().0;
+        let mut i = 1;
+        while !Self::is_one(&w) {
+            let y = self.gcd(&w, &c);
+            let factor = self.div_rem(&w, &y).unwrap().0;
+            if !Self::is_one(&factor) {
+                result.push((factor, i));
+            }
+            c = self.div_rem(&c, &y).unwrap().0;
+            w = y;
+            i += 1;
+        }
+
+        if !Self::is_one(&c) {
+            // What is left is a polynomial in `x^p`, the `p`-th power of the one with every `p`-th
+            // coefficient, since `a^p = a` in `GF(p)`.
+            let p = self.n.to_usize().unwrap();
+            let root = self.poly(c.iter().step_by(p).cloned());
+            let p = p as u32;
+            result.extend(self.square_free(&root).into_iter().map(|(g, k)| (g, k * p)));
+        }
+        result
+    }
+
+    /// Splits a square-free monic polynomial into the products of its irreducible factors of each degree.
+    fn distinct_degree(&self, f: &Poly) -> Vec<(Poly, usize)> {
+        let mut result = Vec::new();
+        let mut f = f.clone();
+        let x = self.poly([BigInt::zero(), BigInt::one()]);
+        let mut h = x.clone();
+        let mut d = 1;
+        while degree(&f) >= 2 * d {
+            // `x^(p^d) - x` is the product of every monic irreducible of degree dividing `d`.
+            h = self.pow(&h, &self.n, Some(&f));
+            let g = self.gcd(&f, &self.sub(&h, &x));
+            if !Self::is_one(&g) {
+                f = self.div_rem(&f, &g).unwrap().0;
+                h = self.rem(&h, &f);
+                result.push((g, d));
+            }
+            d += 1;
+        }
+        if degree(&f) > 0 {
+            let d = degree(&f);
+            result.push((f, d));
+        }
+        result
+    }
+
+    /// Splits a product of distinct monic irreducibles of degree `d` by Cantor-Zassenhaus.
+    fn equal_degree(&self, f: &Poly, d: usize) -> Option<Vec<Poly>> {
+        let count = degree(f) / d;
+        let mut factors = vec![f.clone()];
+        let mut random = Lcg(degree(f) as u64);
+        let mut attempts = 0;
+
+        while factors.len() < count {
+            attempts += 1;
+            if attempts > MAX_SPLIT_ATTEMPTS * count {
+                return None;
+            }
+
+            let a = self.poly((0..degree(f)).map(|_| BigInt::from(random.next()) % &self.n));
+            if degree(&a) == 0 {
+                continue;
+            }
+            let splitter = if self.n == BigInt::from(2) {
+                // The trace `a + a^2 + ... + a^(2^(d - 1))` is 0 or 1 on each factor.
+                let mut term = a.clone();
+                let mut trace = a.clone();
+                for _ in 1..d {
+                    term = self.rem(&self.mul(&term, &term), f);
+                    trace = self.add(&trace, &term);
+                }
+                trace
+            } else {
+                // `a^((p^d - 1) / 2)` is 1 or -1 on each factor that `a` does not vanish on.
+                let k = (self.n.pow(d as u32) - 1u32) / 2u32;
+                self.sub(&self.pow(&a, &k, Some(f)), &self.constant(BigInt::one()))
+            };
+
+            factors = factors.into_iter()
+                .flat_map(|u| {
+                    let g = self.gcd(&splitter, &u);
+                    if degree(&u) > d && degree(&g) > 0 && degree(&g) < degree(&u) {
+                        let rest = self.div_rem(&u, &g).unwrap().0;
+                        vec![g, rest]
+                    } else {
+                        vec![u]
+                    }
+                })
+                .collect();
+        }
+        Some(factors)
+    }
+}
+
+/// A small linear congruential generator, so factorizations come out the same every run.
+struct Lcg(u64);
+
+impl Lcg {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        self.0 >> 16
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::{error, eval};
+
+    #[test]
+    fn reduction() {
+        assert_eq!(eval("mod:7[3/4]"), "6");
+        assert_eq!(eval("mod:13[10!]"), "6");
+        assert_eq!(eval("mod:7[(x+1)^7]"), "(1 + (x ^ 7))");
+        assert_eq!(eval("mod:7[x^2+8x+14]"), "(x + (x ^ 2))");
+    }
+
+    #[test]
+    fn reduction_errors() {
+        assert_eq!(error("mod:6[1/2]"), "2 has no inverse modulo 6");
+        assert_eq!(error("mod:5[x y]"), "expected a polynomial in one variable, got both x and y");
+        assert_eq!(error("mod:5[sin[x]]"), "cannot reduce sin[x] modulo 5");
+        assert_eq!(error("factormod:4[x^2]"), "expected a prime modulus, got 4");
+    }
+
+    #[test]
+    fn factoring_over_gf_p() {
+        assert_eq!(eval("factormod:5[x^4 - 1]"), "(1 + x)(2 + x)(3 + x)(4 + x)");
+        assert_eq!(eval("factormod:2[x^2 + x + 1]"), "((1 + x) + (x ^ 2))");
+        assert_eq!(eval("factormod:3[x^3 - x]"), "x(1 + x)(2 + x)");
+        assert_eq!(eval("factormod:7[(x+1)^3 (x^2+1)]"), "((1 + x) ^ 3)(1 + (x ^ 2))");
+    }
+}
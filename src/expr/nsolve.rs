@@ -0,0 +1,378 @@
+use super::{compile::CompiledFn, polynomial, Expr};
+use crate::prelude::*;
+
+
+/// Newton steps taken before falling back to bracketing.
+const MAX_NEWTON_STEPS: usize = 100;
+
+/// Iterations of Brent's method before giving up, far more than a bracket of floats ever needs.
+const MAX_BRENT_STEPS: usize = 200;
+
+/// Sweeps of Durand–Kerner updates over all the roots before giving up.
+const MAX_SWEEPS: usize = 1000;
+
+/// Relative size of a step below which an iteration has converged.
+const TOLERANCE: f64 = 1e-14;
+
+/// Half-width, relative to the starting point, of the first interval searched for a sign change.
+const BRACKET_START: f64 = 0.01;
+
+/// Factor the searched interval grows by each time no sign change is found.
+const BRACKET_GROWTH: f64 = 1.6;
+
+/// Times the searched interval grows before giving up, reaching about `10^10` times its start.
+const MAX_BRACKET_STEPS: usize = 60;
+
+/// Rounding errors of a polynomial evaluated at a root are allowed to be this many times the bound
+/// for a single evaluation, before a root is taken as accurate as floats allow.
+const ROUNDING_SLACK: f64 = 16.0;
+
+/// Imaginary parts of polynomial roots below this, relative to their size, are taken as rounding
+/// errors. Roots of multiplicity two are only found to about the square root of the precision, so
+/// this sits well above it.
+const MAX_ROUNDING_IMAGINARY: f64 = 1e-7;
+
+/// A complex number, as the roots of polynomials can be.
+#[derive(Clone, Copy, PartialEq)]
+pub struct Complex {
+    pub re: f64,
+    pub im: f64,
+}
+
+impl Complex {
+    fn real(re: f64) -> Self {
+        Complex { re, im: 0.0 }
+    }
+
+    fn polar(r: f64, theta: f64) -> Self {
+        Complex { re: r * theta.cos(), im: r * theta.sin() }
+    }
+
+    fn abs(self) -> f64 {
+        self.re.hypot(self.im)
+    }
+}
+
+impl std::ops::Add for Complex {
+    type Output = Complex;
+    fn add(self, rhs: Complex) -> Complex {
+        Complex { re: self.re + rhs.re, im: self.im + rhs.im }
+    }
+}
+
+impl std::ops::Sub for Complex {
+    type Output = Complex;
+    fn sub(self, rhs: Complex) -> Complex {
+        Complex { re: self.re - rhs.re, im: self.im - rhs.im }
+    }
+}
+
+impl std::ops::Mul for Complex {
+    type Output = Complex;
+    fn mul(self, rhs: Complex) -> Complex {
+        Complex { re: self.re * rhs.re - self.im * rhs.im, im: self.re * rhs.im + self.im * rhs.re }
+    }
+}
+
+impl std::ops::Div for Complex {
+    type Output = Complex;
+    fn div(self, rhs: Complex) -> Complex {
+        let scale = rhs.re.abs().max(rhs.im.abs());
+        let (re, im) = (rhs.re / scale, rhs.im / scale);
+        let norm = re * re + im * im;
+        Complex { re: (self.re * re + self.im * im) / scale / norm, im: (self.im * re - self.re * im) / scale / norm }
+    }
+}
+
+/// How the roots were found.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Method {
+    Newton,
+    Brent,
+    DurandKerner,
+}
+
+/// The roots found by `nsolve`, with how the iteration that found them went.
+#[derive(Clone, PartialEq)]
+pub struct Solution {
+    pub var: String,
+    pub roots: Vec<Complex>,
+    pub method: Method,
+    pub iterations: usize,
+    pub converged: bool,
+    /// The largest absolute value of the function at the roots.
+    pub residual: f64,
+    /// The size of the last step, or the width of the last bracket, estimating how far off the roots are.
+    pub error: f64,
+}
+
+/// The value of a polynomial given by its coefficients from the constant term up, along with a
+/// bound on the rounding errors of computing it.
+fn horner(coeffs: &[f64], z: Complex) -> (Complex, f64) {
+    let value = coeffs.iter().rev().fold(Complex::real(0.0), |acc, &c| acc * z + Complex::real(c));
+    let magnitude = coeffs.iter().rev().fold(0.0, |acc, c| acc * z.abs() + c.abs());
+    (value, ROUNDING_SLACK * coeffs.len() as f64 * f64::EPSILON * magnitude)
+}
+
+/// All the roots of a polynomial with real coefficients, by Durand–Kerner iteration.
+///
+/// Each sweep moves every root estimate by the value of the polynomial over its distances to the
+/// others, until the values at all of them are down to rounding errors or the steps stop shrinking.
+fn durand_kerner(var: &str, original: &[f64]) -> Solution {
+    let zeros = original.iter().take_while(|&&c| c == 0.0).count();
+    let lead = *original.last().unwrap();
+    let coeffs: Vec<f64> = original[zeros..].iter().map(|c| c / lead).collect();
+    let degree = coeffs.len() - 1;
+
+    // Every root lies within the Cauchy bound, so the estimates start spread around a circle that
+    // wide, turned off the real axis so that none starts on a line of symmetry.
+    let radius = 1.0 + coeffs[..degree].iter().fold(0.0, |acc: f64, c| acc.max(c.abs()));
+    let mut roots: Vec<Complex> = (0..degree)
+        .map(|k| Complex::polar(radius, 0.4 + std::f64::consts::TAU * k as f64 / degree as f64))
+        .collect();
+
+    let (mut iterations, mut converged, mut error) = (0, degree == 0, 0.0);
+    while !converged && iterations < MAX_SWEEPS {
+        iterations += 1;
+        error = 0.0f64;
+        let mut settled = true;
+        for k in 0..degree {
+            let (value, rounding) = horner(&coeffs, roots[k]);
+            settled &= value.abs() <= rounding;
+            let denominator = (0..degree).filter(|&j| j != k).fold(Complex::real(1.0), |acc, j| acc * (roots[k] - roots[j]));
+            let step = value / denominator;
+            if step.re.is_finite() && step.im.is_finite() {
+                roots[k] = roots[k] - step;
+                error = error.max(step.abs());
+            }
+        }
+        converged = settled || error <= TOLERANCE * radius;
+    }
+
+    let mut roots: Vec<Complex> = roots.into_iter()
+        .map(|z| match z.im.abs() <= MAX_ROUNDING_IMAGINARY * z.abs().max(1.0) {
+            true => Complex::real(z.re),
+            false => z,
+        })
+        .chain(std::iter::repeat_n(Complex::real(0.0), zeros))
+        .collect();
+    roots.sort_by(|a, b| a.re.total_cmp(&b.re).then(a.im.total_cmp(&b.im)));
+
+    let residual = roots.iter().fold(0.0, |acc: f64, &z| acc.max(horner(original, z).0.abs()));
+    Solution { var: var.to_string(), roots, method: Method::DurandKerner, iterations, converged, residual, error }
+}
+
+/// Newton's method from `x`, stopping at the first step that leaves the domain or hits a flat spot.
+fn newton(f: &CompiledFn, df: &CompiledFn, mut x: f64) -> (f64, usize, bool, f64) {
+    let mut error = f64::INFINITY;
+    for i in 1..=MAX_NEWTON_STEPS {
+        let (y, slope) = (f.call(&[x]), df.call(&[x]));
+        if y == 0.0 {
+            return (x, i, true, 0.0);
+        }
+        let step = y / slope;
+        if !step.is_finite() || !(x - step).is_finite() {
+            return (x, i, false, error);
+        }
+        x -= step;
+        error = step.abs();
+        if error <= TOLERANCE * x.abs().max(1.0) {
+            return (x, i, true, error);
+        }
+    }
+    (x, MAX_NEWTON_STEPS, false, error)
+}
+
+/// Searches outward from `x` for an interval where `f` changes sign, skipping points outside its
+/// domain.
+fn bracket(f: &CompiledFn, x: f64) -> Option<((f64, f64), (f64, f64))> {
+    let start = (x, f.call(&[x]));
+    let (mut left, mut right) = (start, start);
+    let mut h = BRACKET_START * x.abs().max(1.0);
+    for _ in 0..MAX_BRACKET_STEPS {
+        for (side, sign) in [(&mut left, -1.0), (&mut right, 1.0)] {
+            let next = (x + sign * h, f.call(&[x + sign * h]));
+            if side.1.is_finite() && next.1.is_finite() && side.1.signum() != next.1.signum() {
+                return Some((*side, next));
+            }
+            *side = next;
+        }
+        h *= BRACKET_GROWTH;
+    }
+    None
+}
+
+/// Brent's method on a bracket `a`, `b` where `f` changes sign, combining inverse quadratic
+/// interpolation and secant steps with bisection whenever they do not shrink the bracket fast enough.
+fn brent(f: &CompiledFn, (mut a, mut fa): (f64, f64), (mut b, mut fb): (f64, f64)) -> (f64, usize, bool, f64) {
+    let (mut c, mut fc) = (a, fa);
+    let (mut d, mut e) = (b - a, b - a);
+    for i in 1..=MAX_BRENT_STEPS {
+        if fb.signum() == fc.signum() {
+            (c, fc) = (a, fa);
+            (d, e) = (b - a, b - a);
+        }
+        if fc.abs() < fb.abs() {
+            (a, fa) = (b, fb);
+            (b, fb) = (c, fc);
+            (c, fc) = (a, fa);
+        }
+
+        let tolerance = 2.0 * f64::EPSILON * b.abs() + 0.5 * TOLERANCE;
+        let half = (c - b) / 2.0;
+        if half.abs() <= tolerance || fb == 0.0 {
+            return (b, i, true, half.abs());
+        }
+
+        if e.abs() >= tolerance && fa.abs() > fb.abs() {
+            let s = fb / fa;
+            let (mut p, mut q) = match a == c {
+                true => (2.0 * half * s, 1.0 - s),
+                false => {
+                    let (q, r) = (fa / fc, fb / fc);
+                    (s * (2.0 * half * q * (q - r) - (b - a) * (r - 1.0)), (q - 1.0) * (r - 1.0) * (s - 1.0))
+                },
+            };
+            if p > 0.0 {
+                q = -q;
+            }
+            p = p.abs();
+            if 2.0 * p < (3.0 * half * q - (tolerance * q).abs()).min((e * q).abs()) {
+                (e, d) = (d, p / q);
+            } else {
+                (d, e) = (half, half);
+            }
+        } else {
+            (d, e) = (half, half);
+        }
+
+        (a, fa) = (b, fb);
+        b += if d.abs() > tolerance { d } else { tolerance.copysign(half) };
+        fb = f.call(&[b]);
+    }
+    (b, MAX_BRENT_STEPS, false, (c - b).abs())
+}
+
+impl Expr {
+    /// Finds roots of the expression in `var` numerically.
+    ///
+    /// Polynomials with numeric coefficients get all their complex roots at once. Anything else gets
+    /// Newton's method from `start`, with the derivative found symbolically, falling back to Brent's
+    /// method on the nearest sign change found around `start`. Returns `None` when the expression
+    /// cannot be evaluated, Newton's method does not converge and there is no sign change, or the
+    /// sign change is across a pole.
+    pub fn nsolve(&self, var: &str, start: f64) -> Option<Solution> {
+        let expr = self.clone().simplify();
+        let coeffs = polynomial::coefficients(&expr, var)
+            .and_then(|coeffs| coeffs.iter().map(|c| c.to_f64().filter(|c| c.is_finite())).collect::<Option<Vec<f64>>>());
+        match coeffs {
+            Some(coeffs) if coeffs.len() > 1 => return Some(durand_kerner(var, &coeffs)),
+            Some(_) => return None,
+            None => (),
+        }
+
+        let f = expr.compile(&[var])?;
+        let solution = |x: f64, method, iterations, converged, error| Solution {
+            var: var.to_string(),
+            roots: vec![Complex::real(x)],
+            method,
+            iterations,
+            converged,
+            residual: f.call(&[x]).abs(),
+            error,
+        };
+
+        let df = expr.differentiate(var).and_then(|df| df.simplify().compile(&[var]));
+        let newton = df.map(|df| newton(&f, &df, start));
+        if let Some((x, iterations, true, error)) = newton {
+            return Some(solution(x, Method::Newton, iterations, true, error));
+        }
+        // Newton's method that did not converge found nothing, as it may be heading off after a root
+        // at infinity like that of `exp[x]`, and a sign change is needed to go on instead.
+        let (a, b) = bracket(&f, start)?;
+        let (x, iterations, converged, error) = brent(&f, a, b);
+        // A sign change across a pole shrinks onto the pole, where the values only grow.
+        if f.call(&[x]).abs() > a.1.abs().min(b.1.abs()) {
+            return None;
+        }
+        Some(solution(x, Method::Brent, iterations, converged, error))
+    }
+}
+
+
+impl std::fmt::Display for Complex {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.re, self.im) {
+            (re, 0.0) => write!(f, "{}", re),
+            (0.0, im) => write!(f, "{}i", im),
+            (re, im) => write!(f, "{} {} {}i", re, if im < 0.0 { "-" } else { "+" }, im.abs()),
+        }
+    }
+}
+
+impl std::fmt::Display for Method {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Method::Newton => write!(f, "Newton's method"),
+            Method::Brent => write!(f, "Brent's method"),
+            Method::DurandKerner => write!(f, "Durand–Kerner"),
+        }
+    }
+}
+
+impl std::fmt::Display for Solution {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.roots.as_slice() {
+            [] => write!(f, "no roots")?,
+            [root] => write!(f, "{} {} {}", self.var, if self.converged { "=" } else { "≈" }, root)?,
+            roots => write!(f, "{} ∈ {{{}}}", self.var, roots.iter().map(|z| z.to_string()).collect::<Vec<_>>().join(", "))?,
+        }
+        match self.converged {
+            true => write!(f, " ({} converged in {} iterations", self.method, self.iterations)?,
+            false => write!(f, " ({} did not converge after {} iterations", self.method, self.iterations)?,
+        }
+        write!(f, ", residual {}, error {})", utils::magnitude(self.residual), utils::magnitude(self.error))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{session::Session, testing::{error, eval_in, parse}};
+
+    #[test]
+    fn nsolve_accuracy() {
+        for (input, start, exact) in [("cos[x] - x", 1.0, 0.7390851332151607), ("x^3 - 2", 1.0, 2f64.cbrt()), ("ln[x] - 1", 2.0, std::f64::consts::E)] {
+            let Some(solution) = parse(input).nsolve("x", start) else { panic!("no root of {}", input) };
+            assert!(solution.converged, "{}", input);
+            let Some(root) = solution.roots.iter().find(|z| z.im == 0.0) else { panic!("no real root of {}", input) };
+            assert!((root.re - exact).abs() <= 1e-14 * exact.abs(), "{}: {} != {}", input, root.re, exact);
+        }
+    }
+
+    #[test]
+    fn newton_root() {
+        let Some(solution) = parse("exp[x] - 2").nsolve("x", 1.0) else { panic!("no root of exp[x] - 2") };
+        assert!(solution.converged);
+        assert!((solution.roots[0].re - 2f64.ln()).abs() < 1e-14);
+    }
+
+    #[test]
+    fn no_root_when_newton_heads_off() {
+        assert!(parse("exp[x] + 1").nsolve("x", 1.0).is_none());
+        assert!(parse("exp[x]").nsolve("x", 3.0).is_none());
+    }
+
+    #[test]
+    fn no_root_at_a_pole() {
+        assert!(parse("1/x").nsolve("x", 0.5).is_none());
+        assert_eq!(error("nsolve:x:0.5[1/x]"), "cannot find a root of (1 / x) in x near 0.5");
+    }
+
+    #[test]
+    fn bound_variable_is_not_substituted() {
+        let mut session = Session::new();
+        eval_in(&mut session, "x := 5");
+        let solution = eval_in(&mut session, "nsolve:x:1[x^2 - 4]");
+        assert!(solution.starts_with("x ∈ {"), "{}", solution);
+    }
+}
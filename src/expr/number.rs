@@ -0,0 +1,172 @@
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::{One, Signed, ToPrimitive, Zero};
+
+use super::{functions, special, Expr};
+
+
+/// A numeric constant, kept exact unless a decimal was involved.
+#[derive(Clone, PartialEq)]
+pub enum Number {
+    Rational(BigRational),
+    Decimal(f64),
+}
+
+impl Number {
+    pub fn zero() -> Self {
+        Number::Rational(BigRational::zero())
+    }
+
+    pub fn one() -> Self {
+        Number::Rational(BigRational::one())
+    }
+
+    pub fn is_zero(&self) -> bool {
+        match self {
+            Number::Rational(r) => r.is_zero(),
+            Number::Decimal(v) => *v == 0.0,
+        }
+    }
+
+    pub fn is_one(&self) -> bool {
+        match self {
+            Number::Rational(r) => r.is_one(),
+            Number::Decimal(v) => *v == 1.0,
+        }
+    }
+
+    pub fn is_negative(&self) -> bool {
+        match self {
+            Number::Rational(r) => r.is_negative(),
+            Number::Decimal(v) => *v < 0.0,
+        }
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        match self {
+            Number::Rational(r) => r.to_f64().unwrap_or(f64::NAN),
+            Number::Decimal(v) => *v,
+        }
+    }
+
+    /// The multiplicative inverse, or `None` for zero.
+    pub fn recip(&self) -> Option<Self> {
+        if self.is_zero() {
+            return None;
+        }
+
+        Some(match self {
+            Number::Rational(r) => Number::Rational(r.recip()),
+            Number::Decimal(v) => Number::Decimal(v.recip()),
+        })
+    }
+
+    /// Reads a numeric constant out of an integer, decimal or ratio of integers.
+    pub fn from_expr(expr: &Expr) -> Option<Self> {
+        match expr {
+            Expr::Integer(n) => Some(Number::Rational(BigRational::from_integer(n.clone()))),
+            Expr::Decimal(v) => Some(Number::Decimal(*v)),
+            Expr::Factorization { negative, factors } => {
+                let n = factors.iter().fold(BigInt::one(), |acc, (p, k)| acc * p.pow(*k));
+                Some(Number::from(if *negative { -n } else { n }))
+            },
+            Expr::Negation(node) => Number::from_expr(node).map(|x| -x),
+            Expr::Ratio { numerator, denominator } => match (Number::from_expr(numerator)?, Number::from_expr(denominator)?) {
+                (_, d) if d.is_zero() => None,
+                (n, d) => Some(n * d.recip()?),
+            },
+            _ => None,
+        }
+    }
+
+    /// Converts back into an integer, decimal or reduced ratio of integers.
+    pub fn into_expr(self) -> Expr {
+        match self {
+            Number::Rational(r) if r.is_integer() => Expr::integer(r.to_integer()),
+            Number::Rational(r) => {
+                let (n, d) = r.into();
+                Expr::ratio(Expr::integer(n).boxed(), Expr::integer(d).boxed())
+            },
+            Number::Decimal(v) => Expr::decimal(v),
+        }
+    }
+}
+
+impl From<BigInt> for Number {
+    fn from(value: BigInt) -> Self {
+        Number::Rational(BigRational::from_integer(value))
+    }
+}
+
+impl std::ops::Add for Number {
+    type Output = Number;
+    fn add(self, rhs: Number) -> Number {
+        match (self, rhs) {
+            (Number::Rational(a), Number::Rational(b)) => Number::Rational(a + b),
+            (a, b) => Number::Decimal(a.to_f64() + b.to_f64()),
+        }
+    }
+}
+
+impl std::ops::Mul for Number {
+    type Output = Number;
+    fn mul(self, rhs: Number) -> Number {
+        match (self, rhs) {
+            (Number::Rational(a), Number::Rational(b)) => Number::Rational(a * b),
+            (a, b) => Number::Decimal(a.to_f64() * b.to_f64()),
+        }
+    }
+}
+
+impl std::ops::Neg for Number {
+    type Output = Number;
+    fn neg(self) -> Number {
+        match self {
+            Number::Rational(r) => Number::Rational(-r),
+            Number::Decimal(v) => Number::Decimal(-v),
+        }
+    }
+}
+
+/// The `n`th root of `r`, which is real and negative for negative `r` when `n` is an odd integer.
+pub fn real_root(n: f64, r: f64) -> f64 {
+    match r < 0.0 && n.fract() == 0.0 && n % 2.0 != 0.0 {
+        true => -(-r).powf(n.recip()),
+        false => r.powf(n.recip()),
+    }
+}
+
+impl Expr {
+    /// Evaluates an expression without variables as a float, or `None` if it has free variables or
+    /// functions without a known definition.
+    pub fn to_f64(&self) -> Option<f64> {
+        self.evaluate(&[])
+    }
+
+    /// Evaluates the expression as a float with the values of its variables given by `vars`, by
+    /// walking the tree.
+    pub fn evaluate(&self, vars: &[(&str, f64)]) -> Option<f64> {
+        Some(match self {
+            Expr::Integer(n) => n.to_f64()?,
+            Expr::Decimal(v) => *v,
+            Expr::Infinity => f64::INFINITY,
+            Expr::Variable(name) => vars.iter().find(|(n, _)| n == name)?.1,
+            Expr::Factorization { .. } => Number::from_expr(self)?.to_f64(),
+            Expr::Negation(node) => -node.evaluate(vars)?,
+            Expr::Sum { left, right } => left.evaluate(vars)? + right.evaluate(vars)?,
+            Expr::Difference { left, right } => left.evaluate(vars)? - right.evaluate(vars)?,
+            Expr::Product { left, right } => left.evaluate(vars)? * right.evaluate(vars)?,
+            Expr::Ratio { numerator, denominator } => numerator.evaluate(vars)? / denominator.evaluate(vars)?,
+            Expr::Power { base, exp } => base.evaluate(vars)?.powf(exp.evaluate(vars)?),
+            Expr::Root { index, radicand } => real_root(index.evaluate(vars)?, radicand.evaluate(vars)?),
+            Expr::Factorial(node) => special::gamma(node.evaluate(vars)? + 1.0),
+            Expr::Gamma(node) => special::gamma(node.evaluate(vars)?),
+            Expr::Binomial { n, k } => {
+                let (n, k) = (n.evaluate(vars)?, k.evaluate(vars)?);
+                special::gamma(n + 1.0) / (special::gamma(k + 1.0) * special::gamma(n - k + 1.0))
+            },
+            Expr::Function { name, args } if args.len() == 1 => functions::eval(name, args[0].evaluate(vars)?)?,
+            _ => return None,
+        })
+    }
+}
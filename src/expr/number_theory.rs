@@ -0,0 +1,268 @@
+use num_bigint::{BigInt, BigUint};
+use num_integer::Integer;
+use num_traits::{One, Signed, Zero};
+
+
+/// Bases for Miller-Rabin, which together make it deterministic below `3.3 * 10^24`.
+const WITNESSES: [u32; 13] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41];
+
+/// Factors below this are found by trial division before falling back to Pollard's rho.
+const TRIAL_DIVISION_LIMIT: u32 = 10_000;
+
+/// Iterations of Pollard's rho tried for each polynomial before moving on to the next.
+const MAX_RHO_ITERATIONS: u64 = 1 << 22;
+
+/// Differences multiplied together by Pollard's rho before taking their gcd with the number.
+const RHO_BATCH: u64 = 128;
+
+/// Polynomials `x^2 + c` tried by Pollard's rho before giving up on a factor.
+const MAX_RHO_ATTEMPTS: u32 = 32;
+
+pub fn signed_gcd(a: &BigInt, b: &BigInt) -> BigInt {
+    gcd(a.magnitude().clone(), b.magnitude().clone()).into()
+}
+
+
+// https://www.wikiwand.com/en/Binary_GCD_algorithm
+pub fn gcd(mut a: BigUint, mut b: BigUint) -> BigUint {
+    if a.is_zero() {
+        return b;
+    } else if b.is_zero() {
+        return a;
+    }
+
+    let i = a.trailing_zeros().unwrap(); a >>= i;
+    let j = b.trailing_zeros().unwrap(); b >>= j;
+    let k = i.min(j);
+
+    loop {
+        if a > b {
+            std::mem::swap(&mut a, &mut b);
+        }
+
+        b -= &a;
+
+        if b.is_zero() {
+            return a << k;
+        }
+
+        b >>= b.trailing_zeros().unwrap();
+    }
+}
+
+pub fn lcm(a: &BigInt, b: &BigInt) -> BigInt {
+    if a.is_zero() || b.is_zero() {
+        return BigInt::zero();
+    }
+    (a / signed_gcd(a, b) * b).abs()
+}
+
+/// Miller-Rabin with fixed bases, so it is exact below `3.3 * 10^24`.
+///
+/// Above that it is only a strong probable prime test, so `isprime` may report a composite as prime.
+pub fn is_prime(n: &BigInt) -> bool {
+    if *n < BigInt::from(2) {
+        return false;
+    }
+
+    for p in WITNESSES {
+        if (n % p).is_zero() {
+            return *n == BigInt::from(p);
+        }
+    }
+
+    let n_minus_one = n - 1u32;
+    let s = n_minus_one.trailing_zeros().unwrap();
+    let d = &n_minus_one >> s;
+
+    WITNESSES.iter().all(|&a| {
+        let mut x = BigInt::from(a).modpow(&d, n);
+        if x.is_one() || x == n_minus_one {
+            return true;
+        }
+        for _ in 1..s {
+            x = &x * &x % n;
+            if x == n_minus_one {
+                return true;
+            }
+        }
+        false
+    })
+}
+
+pub fn next_prime(n: &BigInt) -> BigInt {
+    let mut candidate = if *n < BigInt::from(2) { BigInt::from(2) } else { n + 1u32 };
+    while !is_prime(&candidate) {
+        candidate += 1u32;
+    }
+    candidate
+}
+
+/// Finds a non-trivial factor of an odd composite with Brent's variant of Pollard's rho, which
+/// compares each point of the sequence against one saved at the last power of two and takes the gcd
+/// of a whole batch of differences at once.
+fn pollard_rho(n: &BigInt) -> Option<BigInt> {
+    for c in 1..=MAX_RHO_ATTEMPTS {
+        let f = |x: &BigInt| (x * x + c) % n;
+        let mut y = BigInt::from(2);
+        let (mut x, mut batch_start) = (y.clone(), y.clone());
+        let (mut q, mut d) = (BigInt::one(), BigInt::one());
+        let mut r = 1u64;
+
+        while d.is_one() && r <= MAX_RHO_ITERATIONS {
+            x = y.clone();
+            for _ in 0..r {
+                y = f(&y);
+            }
+
+            let mut k = 0;
+            while k < r && d.is_one() {
+                batch_start = y.clone();
+                for _ in 0..RHO_BATCH.min(r - k) {
+                    y = f(&y);
+                    q = q * (&x - &y).abs() % n;
+                }
+                d = signed_gcd(&q, n);
+                k += RHO_BATCH;
+            }
+            r *= 2;
+        }
+
+        // The batch can multiply in every factor of `n` at once, so it is stepped through again one
+        // difference at a time.
+        if d == *n {
+            loop {
+                batch_start = f(&batch_start);
+                d = signed_gcd(&(&x - &batch_start), n);
+                if !d.is_one() {
+                    break;
+                }
+            }
+        }
+
+        if !d.is_one() && d != *n {
+            return Some(d);
+        }
+    }
+
+    None
+}
+
+/// The prime factorization of `|n|` as ascending primes with their multiplicities, or `None` if
+/// some factor could not be split.
+pub fn factorize(n: &BigInt) -> Option<Vec<(BigInt, u32)>> {
+    let mut n = n.abs();
+    let mut primes = Vec::new();
+
+    for p in (2..TRIAL_DIVISION_LIMIT).map(BigInt::from) {
+        if &p * &p > n {
+            break;
+        }
+        while (&n % &p).is_zero() {
+            n /= &p;
+            primes.push(p.clone());
+        }
+    }
+
+    let mut stack = vec![n];
+    while let Some(m) = stack.pop() {
+        if m.is_one() || m.is_zero() {
+            continue;
+        }
+        if is_prime(&m) {
+            primes.push(m);
+            continue;
+        }
+        let d = pollard_rho(&m)?;
+        stack.push(&m / &d);
+        stack.push(d);
+    }
+
+    primes.sort();
+    let mut factors: Vec<(BigInt, u32)> = Vec::new();
+    for p in primes {
+        match factors.last_mut() {
+            Some((q, k)) if *q == p => *k += 1,
+            _ => factors.push((p, 1)),
+        }
+    }
+    Some(factors)
+}
+
+pub fn totient(factors: &[(BigInt, u32)]) -> BigInt {
+    factors.iter().fold(BigInt::one(), |acc, (p, k)| acc * p.pow(k - 1) * (p - 1u32))
+}
+
+/// Every positive divisor, in ascending order.
+pub fn divisors(factors: &[(BigInt, u32)]) -> Vec<BigInt> {
+    let mut divisors = vec![BigInt::one()];
+    for (p, k) in factors {
+        let mut next = Vec::with_capacity(divisors.len() * (*k as usize + 1));
+        for d in &divisors {
+            let mut power = d.clone();
+            for _ in 0..=*k {
+                next.push(power.clone());
+                power *= p;
+            }
+        }
+        divisors = next;
+    }
+    divisors.sort();
+    divisors
+}
+
+/// The inverse of `a` modulo `m`, if they are coprime.
+pub fn mod_inverse(a: &BigInt, m: &BigInt) -> Option<BigInt> {
+    let e = a.mod_floor(m).extended_gcd(m);
+    e.gcd.is_one().then(|| e.x.mod_floor(m))
+}
+
+/// `b^e mod m`, taking a negative exponent as a power of the inverse.
+pub fn mod_pow(b: &BigInt, e: &BigInt, m: &BigInt) -> Option<BigInt> {
+    if m.is_one() {
+        return Some(BigInt::zero());
+    }
+    let base = if e.is_negative() { mod_inverse(b, m)? } else { b.mod_floor(m) };
+    Some(base.modpow(&e.abs(), m))
+}
+
+/// The number of divisors a factorization has, if it fits in a `u64`.
+pub fn divisor_count(factors: &[(BigInt, u32)]) -> Option<u64> {
+    factors.iter().try_fold(1u64, |acc, (_, k)| acc.checked_mul(*k as u64 + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use num_bigint::BigInt;
+
+    use super::{factorize, is_prime, pollard_rho};
+
+    fn big(n: &str) -> BigInt {
+        n.parse().unwrap()
+    }
+
+    #[test]
+    fn primes_above_f64_precision() {
+        // 2^53 + 1 = 3 · 107 · 28059810762433, while 2^53 + 5 is prime.
+        assert!(!is_prime(&big("9007199254740993")));
+        assert!(is_prime(&big("9007199254740997")));
+        assert!(is_prime(&big("170141183460469231731687303715884105727")));
+        assert!(!is_prime(&big("170141183460469231731687303715884105729")));
+    }
+
+    #[test]
+    fn factors_above_f64_precision() {
+        assert_eq!(factorize(&big("1000000016000000063")), Some(vec![(big("1000000007"), 1), (big("1000000009"), 1)]));
+        assert_eq!(factorize(&big("9007199254740993")), Some(vec![(big("3"), 1), (big("107"), 1), (big("28059810762433"), 1)]));
+        assert_eq!(factorize(&big("-72")), Some(vec![(big("2"), 3), (big("3"), 2)]));
+    }
+
+    #[test]
+    fn rho_splits_composites() {
+        for (p, q) in [("10007", "10009"), ("2147483647", "2305843009213693951"), ("1000003", "1000003")] {
+            let n = big(p) * big(q);
+            let d = pollard_rho(&n).unwrap();
+            assert!(d == big(p) || d == big(q), "{} split into {}", n, d);
+        }
+    }
+}
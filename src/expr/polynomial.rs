@@ -0,0 +1,220 @@
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_rational::BigRational;
+use num_traits::{One, Signed, ToPrimitive, Zero};
+
+use super::{number::Number, Expr};
+
+
+/// Powers above this are not expanded when reading off coefficients.
+const MAX_EXPANDED_DEGREE: u32 = 64;
+
+fn is_zero(expr: &Expr) -> bool {
+    matches!(expr, Expr::Integer(n) if n.is_zero())
+}
+
+fn add(a: &[Expr], b: &[Expr]) -> Vec<Expr> {
+    (0..a.len().max(b.len()))
+        .map(|i| match (a.get(i), b.get(i)) {
+            (Some(x), Some(y)) => Expr::sum(x.clone().boxed(), y.clone().boxed()),
+            (Some(x), None) | (None, Some(x)) => x.clone(),
+            (None, None) => unreachable!(),
+        })
+        .collect()
+}
+
+fn mul(a: &[Expr], b: &[Expr]) -> Vec<Expr> {
+    let mut result = vec![Expr::integer(BigInt::zero()); a.len() + b.len() - 1];
+    for (i, x) in a.iter().enumerate() {
+        for (j, y) in b.iter().enumerate() {
+            let term = Expr::product(x.clone().boxed(), y.clone().boxed());
+            result[i + j] = Expr::sum(result[i + j].clone().boxed(), term.boxed()).simplify();
+        }
+    }
+    result
+}
+
+fn map(a: Vec<Expr>, f: impl Fn(Expr) -> Expr) -> Vec<Expr> {
+    a.into_iter().map(f).collect()
+}
+
+/// Reads `expr` as a polynomial in `var`, returning its coefficients from the constant term up.
+///
+/// Returns `None` when `var` appears anywhere other than in sums, products and non-negative integer
+/// powers, or is divided by.
+pub fn coefficients(expr: &Expr, var: &str) -> Option<Vec<Expr>> {
+    let mut coeffs = map(raw_coefficients(expr, var)?, Expr::simplify);
+    while coeffs.len() > 1 && coeffs.last().is_some_and(is_zero) {
+        coeffs.pop();
+    }
+    Some(coeffs)
+}
+
+fn raw_coefficients(expr: &Expr, var: &str) -> Option<Vec<Expr>> {
+    if !expr.contains_var(var) {
+        return Some(vec![expr.clone()]);
+    }
+
+    Some(match expr {
+        Expr::Variable(_) => vec![Expr::integer(BigInt::zero()), Expr::integer(BigInt::from(1))],
+        Expr::Negation(node) => map(raw_coefficients(node, var)?, |x| Expr::negation(x.boxed())),
+        Expr::Sum { left, right } => add(&raw_coefficients(left, var)?, &raw_coefficients(right, var)?),
+        Expr::Difference { left, right } => add(
+            &raw_coefficients(left, var)?,
+            &map(raw_coefficients(right, var)?, |x| Expr::negation(x.boxed())),
+        ),
+        Expr::Product { left, right } => mul(&raw_coefficients(left, var)?, &raw_coefficients(right, var)?),
+        Expr::Ratio { numerator, denominator } if !denominator.contains_var(var) => {
+            map(raw_coefficients(numerator, var)?, |x| Expr::ratio(x.boxed(), denominator.clone()))
+        },
+        Expr::Power { base, exp } => {
+            let k = exp.clone().simplify().as_integer()?.to_u32().filter(|k| *k <= MAX_EXPANDED_DEGREE)?;
+            let base = raw_coefficients(base, var)?;
+            (0..k).fold(vec![Expr::integer(BigInt::from(1))], |acc, _| mul(&acc, &base))
+        },
+        _ => return None,
+    })
+}
+
+/// Builds `Σ coeffs[k] x^k`.
+pub fn from_coefficients(coeffs: &[Expr], x: &Expr) -> Expr {
+    coeffs.iter()
+        .enumerate()
+        .map(|(k, c)| Expr::product(c.clone().boxed(), Expr::power(x.clone().boxed(), Expr::integer(BigInt::from(k)).boxed()).boxed()))
+        .reduce(|acc, term| Expr::sum(acc.boxed(), term.boxed()))
+        .unwrap_or(Expr::integer(BigInt::zero()))
+        .simplify()
+}
+
+/// Multiplies out the parts of `expr` that are polynomial in its variables.
+pub fn expand(expr: &Expr) -> Expr {
+    expand_in(expr.clone().simplify(), &expr.variables())
+}
+
+fn expand_in(expr: Expr, vars: &[String]) -> Expr {
+    let Some((var, rest)) = vars.split_first() else { return expr };
+    match coefficients(&expr, var) {
+        Some(coeffs) if coeffs.len() > 1 => {
+            let coeffs: Vec<Expr> = coeffs.into_iter().map(|c| expand_in(c, rest)).collect();
+            from_coefficients(&coeffs, &Expr::variable(var.clone()))
+        },
+        _ => expand_in(expr, rest),
+    }
+}
+
+/// Constant terms above this are not searched for rational roots.
+const MAX_ROOT_SEARCH: u64 = 1 << 40;
+
+fn divisors(n: &BigInt) -> Option<Vec<BigInt>> {
+    let n = n.abs().to_u64().filter(|n| *n <= MAX_ROOT_SEARCH)?;
+    let mut small = Vec::new();
+    let mut large = Vec::new();
+    let mut d = 1;
+    while d * d <= n {
+        if n % d == 0 {
+            small.push(BigInt::from(d));
+            if d * d != n {
+                large.push(BigInt::from(n / d));
+            }
+        }
+        d += 1;
+    }
+    small.extend(large.into_iter().rev());
+    Some(small)
+}
+
+/// Divides out the root `r`, if it is one, returning the quotient.
+fn deflate(coeffs: &[BigRational], r: &BigRational) -> Option<Vec<BigRational>> {
+    let mut quotient = vec![BigRational::zero(); coeffs.len() - 1];
+    let mut carry = BigRational::zero();
+    for k in (1..coeffs.len()).rev() {
+        carry = &carry * r + &coeffs[k];
+        quotient[k - 1] = carry.clone();
+    }
+    (&carry * r + &coeffs[0]).is_zero().then_some(quotient)
+}
+
+/// The roots of a polynomial of degree at most two, by the quadratic formula.
+fn solve_quadratic(coeffs: &[Expr]) -> Vec<Expr> {
+    let int = |n: i64| Expr::integer(BigInt::from(n));
+    match coeffs {
+        [_] => vec![],
+        [c, b] => vec![Expr::negation(Expr::ratio(c.clone().boxed(), b.clone().boxed()).boxed()).simplify()],
+        [c, b, a] => {
+            let disc = expand(&Expr::difference(
+                Expr::power(b.clone().boxed(), int(2).boxed()).boxed(),
+                Expr::product(int(4).boxed(), Expr::product(a.clone().boxed(), c.clone().boxed()).boxed()).boxed(),
+            ));
+            // A negative discriminant gives a pair of complex roots, written with `i` for the imaginary unit.
+            let sqrt = match Number::from_expr(&disc) {
+                Some(d) if d.is_negative() => Expr::product(
+                    Expr::root(int(2).boxed(), (-d).into_expr().boxed()).simplify().boxed(),
+                    Expr::Variable("i".to_string()).boxed(),
+                ).simplify(),
+                _ => Expr::root(int(2).boxed(), disc.boxed()).simplify(),
+            };
+            let root = |sqrt: Expr| Expr::ratio(
+                Expr::sum(Expr::negation(b.clone().boxed()).boxed(), sqrt.boxed()).boxed(),
+                Expr::product(int(2).boxed(), a.clone().boxed()).boxed(),
+            ).simplify();
+            vec![root(Expr::negation(sqrt.clone().boxed())), root(sqrt)]
+        },
+        _ => unreachable!(),
+    }
+}
+
+/// The roots of a polynomial given by its coefficients, repeated by multiplicity.
+///
+/// Rational roots of polynomials with rational coefficients are found by the rational root theorem,
+/// and whatever is left of degree at most two is solved by the quadratic formula. Returns `None` when
+/// some roots have no such closed form.
+pub fn roots(coeffs: &[Expr]) -> Option<Vec<Expr>> {
+    let mut coeffs = coeffs.to_vec();
+    while coeffs.len() > 1 && coeffs.last().is_some_and(is_zero) {
+        coeffs.pop();
+    }
+
+    let rational: Option<Vec<BigRational>> = coeffs.iter()
+        .map(|c| match Number::from_expr(c)? {
+            Number::Rational(r) => Some(r),
+            Number::Decimal(_) => None,
+        })
+        .collect();
+    let Some(mut rational) = rational else {
+        return (coeffs.len() <= 3).then(|| solve_quadratic(&coeffs));
+    };
+
+    let mut roots = Vec::new();
+    while rational.len() > 1 && rational[0].is_zero() {
+        rational.remove(0);
+        roots.push(Expr::integer(BigInt::zero()));
+    }
+
+    if rational.len() > 3 {
+        // Scales to integer coefficients, whose rational roots are `±p / q` for `p` dividing the
+        // constant term and `q` dividing the leading coefficient.
+        let lcm = rational.iter().fold(BigInt::one(), |acc, c| acc.lcm(c.denom()));
+        let integers: Vec<BigInt> = rational.iter().map(|c| (c * &lcm).to_integer()).collect();
+        let (ps, qs) = (divisors(&integers[0])?, divisors(integers.last().unwrap())?);
+
+        for p in &ps {
+            for q in &qs {
+                for r in [BigRational::new(p.clone(), q.clone()), BigRational::new(-p, q.clone())] {
+                    while rational.len() > 1 {
+                        let Some(quotient) = deflate(&rational, &r) else { break };
+                        rational = quotient;
+                        roots.push(Number::Rational(r.clone()).into_expr());
+                    }
+                }
+            }
+        }
+    }
+
+    if rational.len() > 3 {
+        return None;
+    }
+
+    let rest: Vec<Expr> = rational.into_iter().map(|c| Number::Rational(c).into_expr()).collect();
+    roots.extend(solve_quadratic(&rest));
+    Some(roots)
+}
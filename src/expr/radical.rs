@@ -0,0 +1,329 @@
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::{One, Signed, ToPrimitive, Zero};
+
+use super::{number::Number, number_theory, polynomial, trace, Expr};
+
+
+/// Radicals are eliminated from a single denominator at most this many times.
+const MAX_CONJUGATIONS: usize = 16;
+
+/// Radicands needing more bits than this are not searched for perfect powers.
+const MAX_FACTORED_BITS: u64 = 128;
+
+/// Radicals of a higher index than this are not conjugated away, as the conjugate has this many terms.
+const MAX_CONJUGATE_INDEX: u32 = 12;
+
+fn int(n: i64) -> Expr {
+    Expr::integer(BigInt::from(n))
+}
+
+fn is_zero(expr: &Expr) -> bool {
+    Number::from_expr(expr).is_some_and(|n| n.is_zero())
+}
+
+/// The name standing in for the `i`th radical while it is treated as a variable. Identifiers never
+/// contain `#`, so it cannot clash with a variable of the user's.
+fn placeholder(i: usize) -> String {
+    format!("#{}", i)
+}
+
+/// The index of a radical `ᵏ√x`.
+fn radical_index(expr: &Expr) -> Option<u32> {
+    match expr {
+        Expr::Root { index, .. } => index.as_integer()?.to_u32().filter(|k| *k >= 2),
+        _ => None,
+    }
+}
+
+/// Every distinct radical outside of other radicals, in order of first appearance.
+fn radicals(expr: &Expr, found: &mut Vec<Expr>) {
+    if radical_index(expr).is_some() {
+        if !found.contains(expr) {
+            found.push(expr.clone());
+        }
+        return;
+    }
+    expr.children().into_iter().for_each(|x| radicals(x, found));
+}
+
+impl Expr {
+    /// Rewrites radicals into a normal form and clears them from denominators where possible.
+    ///
+    /// Perfect powers are pulled out of numeric radicands, square roots like `√(3 + 2√2)` are
+    /// denested, and denominators are multiplied through by conjugates until they are radical-free.
+    pub fn rationalize(self) -> Expr {
+        trace::scope(self, "rationalize", |expr| merge(clear_denominators(normalize(expr.simplify()))).simplify())
+    }
+}
+
+fn normalize(expr: Expr) -> Expr {
+    match expr.map_children(normalize) {
+        Expr::Root { index, radicand } => match (index.as_integer().and_then(|k| k.to_u32()), Number::from_expr(&radicand)) {
+            (Some(k), Some(Number::Rational(r))) if k >= 2 => extract_powers(k, &r),
+            (Some(2), None) => denest(&radicand).unwrap_or(Expr::root(index, radicand)),
+            _ => Expr::root(index, radicand),
+        },
+        // `b^(p/q) = b^m (q√b)^r` for `p = m q + r`
+        Expr::Power { base, exp } => match (Number::from_expr(&base), Number::from_expr(&exp)) {
+            (Some(Number::Rational(_)), Some(Number::Rational(e))) if !e.is_integer() && e.denom().to_u32().is_some() => {
+                let (m, r) = (e.floor().to_integer(), e.numer() - e.floor().to_integer() * e.denom());
+                let root = normalize(Expr::root(Expr::integer(e.denom().clone()).boxed(), base.clone()));
+                Expr::product(
+                    Expr::power(base, Expr::integer(m).boxed()).boxed(),
+                    Expr::power(root.boxed(), Expr::integer(r).boxed()).boxed(),
+                ).simplify()
+            },
+            _ => Expr::power(base, exp),
+        },
+        other => other,
+    }
+}
+
+/// `ᵏ√r` with its denominator moved outside and every `k`th power pulled out of what is left.
+fn extract_powers(k: u32, r: &BigRational) -> Expr {
+    let root = || Expr::root(int(k as i64).boxed(), Number::Rational(r.clone()).into_expr().boxed());
+    if r.is_negative() {
+        return match k % 2 {
+            1 => Expr::negation(extract_powers(k, &-r).boxed()).simplify(),
+            _ => root(),
+        };
+    }
+
+    // `ᵏ√(p/q) = ᵏ√(p q^(k - 1)) / q`
+    let m = r.numer() * r.denom().pow(k - 1);
+    if m.is_zero() || m.bits() > MAX_FACTORED_BITS {
+        return root();
+    }
+    let Some(factors) = number_theory::factorize(&m) else { return root() };
+
+    let (mut outside, mut inside) = (BigInt::one(), BigInt::one());
+    for (p, e) in factors {
+        outside *= p.pow(e / k);
+        inside *= p.pow(e % k);
+    }
+    let coeff = Number::Rational(BigRational::new(outside, r.denom().clone())).into_expr();
+    Expr::product(coeff.boxed(), Expr::root(int(k as i64).boxed(), Expr::integer(inside).boxed()).boxed()).simplify()
+}
+
+/// Denests `√(a + b√c) = √((a + s) / 2) ± √((a - s) / 2)` for rational `a`, `b` and `c` where
+/// `s = √(a² - b² c)` is rational.
+fn denest(radicand: &Expr) -> Option<Expr> {
+    let mut found = Vec::new();
+    radicals(radicand, &mut found);
+    let [inner @ Expr::Root { radicand: c, .. }] = found.as_slice() else { return None };
+    if radical_index(inner) != Some(2) {
+        return None;
+    }
+
+    let var = placeholder(0);
+    let coeffs = polynomial::coefficients(&radicand.clone().replace(&[(inner.clone(), Expr::variable(var.clone()))]), &var)?;
+    let rational = |x: &Expr| match Number::from_expr(x)? {
+        Number::Rational(r) => Some(r),
+        Number::Decimal(_) => None,
+    };
+    let [a, b] = coeffs.as_slice() else { return None };
+    let (a, b, c) = (rational(a)?, rational(b)?, rational(c)?);
+
+    let disc = &a * &a - &b * &b * &c;
+    let s = match Number::from_expr(&Expr::root(int(2).boxed(), Number::Rational(disc).into_expr().boxed()).simplify())? {
+        Number::Rational(s) => s,
+        Number::Decimal(_) => return None,
+    };
+    let two = BigRational::from_integer(BigInt::from(2));
+    let (p, q) = ((&a + &s) / &two, (&a - &s) / &two);
+    if p.is_negative() || q.is_negative() {
+        return None;
+    }
+
+    let (p, q) = (extract_powers(2, &p), extract_powers(2, &q));
+    Some(if b.is_negative() {
+        Expr::difference(p.boxed(), q.boxed())
+    } else {
+        Expr::sum(p.boxed(), q.boxed())
+    }.simplify())
+}
+
+/// Splits `expr` into coefficients of powers of the radical `t = ᵏ√x`, with `t^k` reduced to `x`, so
+/// there are exactly `k` of them.
+fn radical_coefficients(expr: &Expr, var: &str, k: u32, x: &Expr) -> Option<Vec<Expr>> {
+    let mut reduced = vec![int(0); k as usize];
+    for (j, c) in polynomial::coefficients(expr, var)?.into_iter().enumerate() {
+        let term = Expr::product(c.boxed(), Expr::power(x.clone().boxed(), int((j / k as usize) as i64).boxed()).boxed());
+        let slot = &mut reduced[j % k as usize];
+        *slot = Expr::sum(slot.clone().boxed(), term.boxed()).simplify();
+    }
+    Some(reduced)
+}
+
+/// Multiplies out products and powers of sums, treating radicals as variables and reducing their
+/// powers by their index.
+fn expand_radicals(expr: Expr) -> Expr {
+    let mut found = Vec::new();
+    radicals(&expr, &mut found);
+    let rules: Vec<(Expr, Expr)> = found.iter().enumerate().map(|(i, r)| (r.clone(), Expr::variable(placeholder(i)))).collect();
+
+    let mut expr = polynomial::expand(&expr.replace(&rules));
+    for (i, radical) in found.iter().enumerate() {
+        let (Some(k), Expr::Root { radicand, .. }) = (radical_index(radical), radical) else { continue };
+        if let Some(coeffs) = radical_coefficients(&expr, &placeholder(i), k, radicand) {
+            expr = polynomial::expand(&polynomial::from_coefficients(&coeffs, &Expr::variable(placeholder(i))));
+        }
+    }
+
+    let back: Vec<(Expr, Expr)> = rules.into_iter().map(|(r, var)| (var, r)).collect();
+    expr.replace(&back).simplify()
+}
+
+/// A factor that clears the radical `t = ᵏ√x` from `d`, when `d` is `A + B t` for `A` and `B` free
+/// of `t`.
+///
+/// The factor is `Σ A^(k - 1 - i) (-B t)^i`, turning `d` into `A^k - (-B)^k x`.
+fn conjugate(d: &Expr, radical: &Expr) -> Option<Expr> {
+    let k = radical_index(radical).filter(|k| *k <= MAX_CONJUGATE_INDEX)?;
+    let Expr::Root { radicand, .. } = radical else { return None };
+    let var = placeholder(0);
+    let t = Expr::variable(var.clone());
+
+    let coeffs = radical_coefficients(&d.clone().replace(&[(radical.clone(), t.clone())]), &var, k, radicand)?;
+    if coeffs[2..].iter().any(|c| !is_zero(c)) {
+        return None;
+    }
+    let (a, b) = (&coeffs[0], &coeffs[1]);
+    if is_zero(b) {
+        return None;
+    }
+
+    let neg_bt = Expr::negation(Expr::product(b.clone().boxed(), t.boxed()).boxed());
+    let factor = (0..k)
+        .map(|i| Expr::product(
+            Expr::power(a.clone().boxed(), int((k - 1 - i) as i64).boxed()).boxed(),
+            Expr::power(neg_bt.clone().boxed(), int(i as i64).boxed()).boxed(),
+        ))
+        .reduce(|acc, x| Expr::sum(acc.boxed(), x.boxed()))?;
+    Some(factor.substitute(&var, radical).simplify())
+}
+
+/// Multiplies the numerator and denominator of every ratio by conjugates until the denominator has no
+/// radicals left that can be cleared.
+fn clear_denominators(expr: Expr) -> Expr {
+    let expr = expr.map_children(clear_denominators);
+    let Expr::Ratio { numerator, denominator } = expr else { return expr };
+
+    let (mut n, mut d) = (numerator.clone().into_inner(), denominator.clone().into_inner());
+    for _ in 0..MAX_CONJUGATIONS {
+        let mut found = Vec::new();
+        radicals(&d, &mut found);
+        if found.is_empty() {
+            break;
+        }
+        let Some(factor) = found.iter().find_map(|r| conjugate(&d, r)) else {
+            // Clearing only some of the radicals just makes things longer.
+            return Expr::ratio(numerator, denominator);
+        };
+        n = expand_radicals(Expr::product(n.boxed(), factor.clone().boxed()));
+        d = expand_radicals(Expr::product(d.boxed(), factor.boxed()));
+    }
+
+    if let Some(m) = d.as_integer().cloned() {
+        // Divides out the integer content shared with the denominator, taking its sign along.
+        let mut g = content(&n, m.clone());
+        if m.is_negative() {
+            g = -g;
+        }
+        n = expand_radicals(Expr::ratio(n.boxed(), Expr::integer(g.clone()).boxed()));
+        d = Expr::integer(m / g);
+    }
+    Expr::ratio(n.boxed(), d.boxed()).simplify()
+}
+
+/// The gcd of `g` and the integer coefficients of the terms of `expr`.
+fn content(expr: &Expr, g: BigInt) -> BigInt {
+    match expr {
+        Expr::Sum { left, right } | Expr::Difference { left, right } => content(right, content(left, g)),
+        Expr::Negation(x) => content(x, g),
+        Expr::Integer(c) => number_theory::signed_gcd(&g, c),
+        Expr::Product { left, .. } => content(left, g),
+        _ => BigInt::one(),
+    }
+}
+
+/// The index and radicand of a radical `ᵏ√r` of a rational, when it can be multiplied with others of
+/// the same index by multiplying radicands.
+fn numeric_radical(expr: &Expr) -> Option<(u32, BigRational)> {
+    let (k, Expr::Root { radicand, .. }) = (radical_index(expr)?, expr) else { return None };
+    match Number::from_expr(radicand)? {
+        Number::Rational(r) if !r.is_negative() || k % 2 == 1 => Some((k, r)),
+        _ => None,
+    }
+}
+
+fn factors(expr: Expr, found: &mut Vec<Expr>) {
+    match expr {
+        Expr::Product { left, right } => {
+            factors(left.into_inner(), found);
+            factors(right.into_inner(), found);
+        },
+        other => found.push(other),
+    }
+}
+
+/// Combines radicals of rationals with the same index, like `√2 √3 = √6`, and takes their powers
+/// inside them, like `(∛2)² = ∛4`.
+fn merge(expr: Expr) -> Expr {
+    match expr.map_children(merge) {
+        Expr::Power { base, exp } => match (numeric_radical(&base), exp.as_integer().and_then(|e| e.to_i32())) {
+            (Some((k, r)), Some(m)) if m > 0 && (r.numer().bits() + r.denom().bits()) * m as u64 <= MAX_FACTORED_BITS => extract_powers(k, &r.pow(m)),
+            _ => Expr::power(base, exp),
+        },
+        product @ Expr::Product { .. } => {
+            let mut found = Vec::new();
+            factors(product, &mut found);
+            let mut merged: Vec<(u32, BigRational)> = Vec::new();
+            let mut rest = Vec::new();
+            for factor in found {
+                match numeric_radical(&factor) {
+                    Some((k, r)) => match merged.iter_mut().find(|(j, _)| *j == k) {
+                        Some((_, acc)) => *acc *= r,
+                        None => merged.push((k, r)),
+                    },
+                    None => rest.push(factor),
+                }
+            }
+            rest.extend(merged.into_iter().map(|(k, r)| extract_powers(k, &r)));
+            rest.into_iter().reduce(|acc, x| Expr::product(acc.boxed(), x.boxed())).unwrap().simplify()
+        },
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::{error, eval};
+
+    #[test]
+    fn perfect_powers_are_pulled_out() {
+        assert_eq!(eval("rationalize[sqrt[8]]"), "2²√2");
+        assert_eq!(eval("rationalize[sqrt[12]/sqrt[3]]"), "2");
+    }
+
+    #[test]
+    fn square_roots_are_denested() {
+        assert_eq!(eval("rationalize[sqrt[3+2sqrt[2]]]"), "(²√2 + 1)");
+    }
+
+    #[test]
+    fn denominators_are_cleared() {
+        assert_eq!(eval("rationalize[1/sqrt[2]]"), "(²√2 / 2)");
+        assert_eq!(eval("rationalize[1/(1+sqrt[2])]"), "(-1 + ²√2)");
+        assert_eq!(eval("rationalize[1/(sqrt[2]+sqrt[3])]"), "(²√3 + -²√2)");
+        assert_eq!(eval("rationalize[1/cbrt[2]]"), "(³√4 / 2)");
+        assert_eq!(eval("rationalize[1/(1+cbrt[2])]"), "(((1 + -³√2) + ³√4) / 3)");
+        assert_eq!(eval("rationalize[x/sqrt[x]]"), "²√x");
+    }
+
+    #[test]
+    fn arity() {
+        assert_eq!(error("rationalize[1,2]"), "expected 1 argument, got 2");
+    }
+}
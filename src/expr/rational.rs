@@ -0,0 +1,207 @@
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::{One, ToPrimitive, Zero};
+
+use super::{number::Number, polynomial, Expr};
+
+
+/// Powers above this are not expanded when reading a rational function.
+const MAX_DEGREE: u32 = 64;
+
+/// A polynomial with rational coefficients, from the constant term up, with no trailing zeros so
+/// that zero itself is empty.
+pub type Poly = Vec<BigRational>;
+
+fn trim(mut a: Poly) -> Poly {
+    while a.last().is_some_and(Zero::is_zero) {
+        a.pop();
+    }
+    a
+}
+
+pub fn constant(c: BigRational) -> Poly {
+    trim(vec![c])
+}
+
+/// The polynomial `x`.
+pub fn x() -> Poly {
+    vec![BigRational::zero(), BigRational::one()]
+}
+
+/// The degree, or `None` for zero.
+pub fn degree(a: &Poly) -> Option<usize> {
+    a.len().checked_sub(1)
+}
+
+/// The coefficient of `x^k`.
+pub fn coeff(a: &Poly, k: usize) -> BigRational {
+    a.get(k).cloned().unwrap_or_default()
+}
+
+pub fn add(a: &Poly, b: &Poly) -> Poly {
+    trim((0..a.len().max(b.len())).map(|i| coeff(a, i) + coeff(b, i)).collect())
+}
+
+pub fn sub(a: &Poly, b: &Poly) -> Poly {
+    trim((0..a.len().max(b.len())).map(|i| coeff(a, i) - coeff(b, i)).collect())
+}
+
+pub fn scale(a: &Poly, c: &BigRational) -> Poly {
+    trim(a.iter().map(|x| x * c).collect())
+}
+
+pub fn mul(a: &Poly, b: &Poly) -> Poly {
+    if a.is_empty() || b.is_empty() {
+        return Poly::new();
+    }
+    let mut result = vec![BigRational::zero(); a.len() + b.len() - 1];
+    for (i, x) in a.iter().enumerate() {
+        for (j, y) in b.iter().enumerate() {
+            result[i + j] += x * y;
+        }
+    }
+    trim(result)
+}
+
+fn pow(a: &Poly, k: u32) -> Poly {
+    (0..k).fold(constant(BigRational::one()), |acc, _| mul(&acc, a))
+}
+
+/// The quotient and remainder of `a / b`, for a non-zero `b`.
+pub fn div_rem(a: &Poly, b: &Poly) -> (Poly, Poly) {
+    let lead = b.last().unwrap();
+    let mut rem = a.clone();
+    let mut quotient = vec![BigRational::zero(); a.len().saturating_sub(b.len()) + 1];
+    while rem.len() >= b.len() {
+        let shift = rem.len() - b.len();
+        let c = rem.last().unwrap() / lead;
+        for (i, x) in b.iter().enumerate() {
+            rem[shift + i] -= &c * x;
+        }
+        quotient[shift] = c;
+        rem = trim(rem);
+    }
+    (trim(quotient), rem)
+}
+
+/// The monic greatest common divisor, or zero if both are zero.
+pub fn gcd(a: &Poly, b: &Poly) -> Poly {
+    let (mut a, mut b) = (a.clone(), b.clone());
+    while !b.is_empty() {
+        let r = div_rem(&a, &b).1;
+        a = std::mem::replace(&mut b, r);
+    }
+    match a.last().cloned() {
+        Some(lead) => scale(&a, &lead.recip()),
+        None => a,
+    }
+}
+
+/// `a(x + h)`.
+pub fn shift(a: &Poly, h: &BigRational) -> Poly {
+    let linear = vec![h.clone(), BigRational::one()];
+    a.iter().rev().fold(Poly::new(), |acc, c| add(&mul(&acc, &linear), &constant(c.clone())))
+}
+
+pub fn eval(a: &Poly, x: &BigRational) -> BigRational {
+    a.iter().rev().fold(BigRational::zero(), |acc, c| acc * x + c)
+}
+
+pub fn derivative(a: &Poly) -> Poly {
+    trim(a.iter().enumerate().skip(1).map(|(i, c)| c * BigInt::from(i)).collect())
+}
+
+pub fn to_expr(a: &Poly, var: &str) -> Expr {
+    let coeffs: Vec<Expr> = a.iter().map(|c| Number::Rational(c.clone()).into_expr()).collect();
+    polynomial::from_coefficients(&coeffs, &Expr::variable(var.to_string()))
+}
+
+fn fraction(expr: &Expr, var: &str) -> Option<(Poly, Poly)> {
+    let one = constant(BigRational::one());
+    if !expr.contains_var(var) {
+        return match Number::from_expr(&expr.clone().simplify())? {
+            Number::Rational(c) => Some((constant(c), one)),
+            Number::Decimal(_) => None,
+        };
+    }
+
+    Some(match expr {
+        Expr::Variable(_) => (x(), one),
+        Expr::Negation(node) => {
+            let (n, d) = fraction(node, var)?;
+            (scale(&n, &-BigRational::one()), d)
+        },
+        Expr::Sum { left, right } | Expr::Difference { left, right } => {
+            let ((n1, d1), (n2, d2)) = (fraction(left, var)?, fraction(right, var)?);
+            let (a, b) = (mul(&n1, &d2), mul(&n2, &d1));
+            let n = if matches!(expr, Expr::Sum { .. }) { add(&a, &b) } else { sub(&a, &b) };
+            (n, mul(&d1, &d2))
+        },
+        Expr::Product { left, right } => {
+            let ((n1, d1), (n2, d2)) = (fraction(left, var)?, fraction(right, var)?);
+            (mul(&n1, &n2), mul(&d1, &d2))
+        },
+        Expr::Ratio { numerator, denominator } => {
+            let ((n1, d1), (n2, d2)) = (fraction(numerator, var)?, fraction(denominator, var)?);
+            if n2.is_empty() {
+                return None;
+            }
+            (mul(&n1, &d2), mul(&d1, &n2))
+        },
+        Expr::Power { base, exp } => {
+            let k = exp.clone().simplify().as_integer()?.to_i64()?;
+            let (n, d) = fraction(base, var)?;
+            let e = k.unsigned_abs().to_u32().filter(|e| *e <= MAX_DEGREE)?;
+            match k.is_negative() {
+                true if n.is_empty() => return None,
+                true => (pow(&d, e), pow(&n, e)),
+                false => (pow(&n, e), pow(&d, e)),
+            }
+        },
+        _ => return None,
+    })
+}
+
+/// Reads `expr` as a ratio of polynomials in `var` with rational coefficients, in lowest terms with
+/// a monic denominator.
+pub fn from_expr(expr: &Expr, var: &str) -> Option<(Poly, Poly)> {
+    let (n, d) = fraction(expr, var)?;
+    let g = gcd(&n, &d);
+    let (n, d) = (div_rem(&n, &g).0, div_rem(&d, &g).0);
+    let lead = d.last()?.recip();
+    Some((scale(&n, &lead), scale(&d, &lead)))
+}
+
+/// Splits a rational function of `var` into its polynomial part and terms `c / (var - r)`, when its
+/// denominator has distinct rational roots and is not constant.
+pub fn partial_fractions(expr: &Expr, var: &str) -> Option<Vec<Expr>> {
+    let (n, d) = from_expr(expr, var)?;
+    if d.len() <= 1 {
+        return None;
+    }
+
+    let coeffs: Vec<Expr> = d.iter().map(|c| Number::Rational(c.clone()).into_expr()).collect();
+    let roots = polynomial::roots(&coeffs)?.iter()
+        .map(|r| match Number::from_expr(r)? {
+            Number::Rational(r) => Some(r),
+            Number::Decimal(_) => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+    if (1..roots.len()).any(|i| roots[..i].contains(&roots[i])) {
+        return None;
+    }
+
+    let (quotient, rem) = div_rem(&n, &d);
+    let slope = derivative(&d);
+    let mut terms = Vec::new();
+    if !quotient.is_empty() {
+        terms.push(to_expr(&quotient, var));
+    }
+    for r in roots {
+        // The residue at a simple root `r` is `n(r) / d'(r)`.
+        let c = eval(&rem, &r) / eval(&slope, &r);
+        let linear = to_expr(&vec![-r, BigRational::one()], var);
+        terms.push(Expr::ratio(Number::Rational(c).into_expr().boxed(), linear.boxed()).simplify());
+    }
+    Some(terms)
+}
@@ -0,0 +1,270 @@
+use std::cell::Cell;
+
+use num_bigint::BigInt;
+use num_traits::{One, Zero};
+
+use super::{number::Number, trace, Expr};
+
+
+/// Steps a single attempt to match a rule may take, as matching sums and products tries every way of
+/// pairing up their terms.
+const MAX_MATCH_STEPS: usize = 10_000;
+
+/// A rewrite rule `lhs -> rhs`, where every variable of `lhs` is a wildcard standing for any
+/// expression.
+#[derive(Clone, PartialEq)]
+pub struct Rule {
+    lhs: Expr,
+    rhs: Expr,
+    wildcards: Vec<String>,
+}
+
+/// The expressions bound to wildcards so far.
+type Bindings = Vec<(String, Expr)>;
+
+#[derive(Clone, Copy)]
+enum Op {
+    Sum,
+    Product,
+}
+
+impl Op {
+    fn split(self, expr: &Expr) -> Vec<Expr> {
+        match self {
+            Op::Sum => terms(expr),
+            Op::Product => factors(expr),
+        }
+    }
+
+    fn join(self, items: Vec<Expr>) -> Expr {
+        match self {
+            Op::Sum => items.into_iter()
+                .reduce(|acc, x| Expr::sum(acc.boxed(), x.boxed()))
+                .unwrap_or(Expr::integer(BigInt::zero())),
+            Op::Product => items.into_iter()
+                .reduce(|acc, x| Expr::product(acc.boxed(), x.boxed()))
+                .unwrap_or(Expr::integer(BigInt::one())),
+        }
+    }
+}
+
+fn terms(expr: &Expr) -> Vec<Expr> {
+    match expr {
+        Expr::Sum { left, right } => [terms(left), terms(right)].concat(),
+        Expr::Negation(x) if matches!(**x, Expr::Sum { .. }) => terms(x)
+            .into_iter()
+            .map(|t| Expr::negation(t.boxed()).simplify())
+            .collect(),
+        other => vec![other.clone()],
+    }
+}
+
+/// The factors of a product, with those of a denominator raised to the power `-1`.
+fn factors(expr: &Expr) -> Vec<Expr> {
+    match expr {
+        Expr::Product { left, right } => [factors(left), factors(right)].concat(),
+        Expr::Ratio { numerator, denominator } => factors(numerator)
+            .into_iter()
+            .chain(factors(denominator).into_iter().map(|f| Expr::power(f.boxed(), Expr::integer(-BigInt::one()).boxed())))
+            .collect(),
+        Expr::Integer(n) if n.is_one() => vec![],
+        other => vec![other.clone()],
+    }
+}
+
+/// The expression with its children replaced, so two expressions of the same kind with the same
+/// names and number of children compare equal.
+fn shape(expr: &Expr) -> Expr {
+    expr.clone().map_children(|_| Expr::integer(BigInt::zero()))
+}
+
+struct Matcher<'a> {
+    wildcards: &'a [String],
+    steps: Cell<usize>,
+}
+
+impl Matcher<'_> {
+    /// Whether `pattern` is a wildcard that is not bound yet.
+    fn is_free(&self, pattern: &Expr, bindings: &Bindings) -> bool {
+        matches!(pattern, Expr::Variable(name) if self.wildcards.contains(name) && !bindings.iter().any(|(n, _)| n == name))
+    }
+
+    /// Matches `pattern` against `expr`, calling `k` with each way of binding the wildcards until it
+    /// accepts one.
+    fn matches(&self, pattern: &Expr, expr: &Expr, bindings: Bindings, k: &mut dyn FnMut(Bindings) -> bool) -> bool {
+        self.steps.set(self.steps.get() + 1);
+        if self.steps.get() > MAX_MATCH_STEPS {
+            return false;
+        }
+
+        match pattern {
+            Expr::Variable(name) if self.wildcards.contains(name) => match bindings.iter().find(|(n, _)| n == name) {
+                Some((_, value)) => value == expr && k(bindings),
+                None => {
+                    let mut bindings = bindings;
+                    bindings.push((name.clone(), expr.clone()));
+                    k(bindings)
+                },
+            },
+            _ if !pattern.variables().iter().any(|v| self.wildcards.contains(v)) => pattern == expr && k(bindings),
+            Expr::Sum { .. } => self.match_unordered(&terms(pattern), &terms(expr), bindings, Op::Sum, false, &mut |b, _| k(b)),
+            Expr::Product { .. } | Expr::Ratio { .. } => {
+                self.match_unordered(&factors(pattern), &factors(expr), bindings, Op::Product, false, &mut |b, _| k(b))
+            },
+            Expr::Negation(x) => match Number::from_expr(expr) {
+                Some(n) if n.is_negative() => self.matches(x, &(-n).into_expr(), bindings, k),
+                _ => matches!(expr, Expr::Negation(_)) && self.matches_each(&pattern.children(), &expr.children(), bindings, k),
+            },
+            _ => shape(pattern) == shape(expr) && self.matches_each(&pattern.children(), &expr.children(), bindings, k),
+        }
+    }
+
+    /// Matches each pattern against the expression in the same position.
+    fn matches_each(&self, patterns: &[&Expr], exprs: &[&Expr], bindings: Bindings, k: &mut dyn FnMut(Bindings) -> bool) -> bool {
+        match patterns.split_first() {
+            None => k(bindings),
+            Some((pattern, rest)) => self.matches(pattern, exprs[0], bindings, &mut |b| self.matches_each(rest, &exprs[1..], b, k)),
+        }
+    }
+
+    /// Matches the terms or factors of a pattern against those of an expression in any order,
+    /// calling `k` with the bindings and the items left over.
+    ///
+    /// A lone wildcard left for last takes the rest of the items, joined back together with `op`,
+    /// unless `partial` allows items to be left over for the caller.
+    fn match_unordered(
+        &self,
+        patterns: &[Expr],
+        items: &[Expr],
+        bindings: Bindings,
+        op: Op,
+        partial: bool,
+        k: &mut dyn FnMut(Bindings, Vec<Expr>) -> bool,
+    ) -> bool {
+        if patterns.is_empty() {
+            return (partial || items.is_empty()) && k(bindings, items.to_vec());
+        }
+
+        // Patterns that constrain their item are tried before free wildcards, which match anything.
+        let next = patterns.iter().position(|p| !self.is_free(p, &bindings));
+        if next.is_none() && patterns.len() == 1 && !partial {
+            return !items.is_empty() && self.matches(&patterns[0], &op.join(items.to_vec()), bindings, &mut |b| k(b, Vec::new()));
+        }
+
+        let i = next.unwrap_or(0);
+        let rest: Vec<Expr> = patterns.iter().enumerate().filter(|(j, _)| *j != i).map(|(_, p)| p.clone()).collect();
+        (0..items.len()).any(|j| {
+            let remaining: Vec<Expr> = items.iter().enumerate().filter(|(l, _)| *l != j).map(|(_, x)| x.clone()).collect();
+            self.matches(&patterns[i], &items[j], bindings.clone(), &mut |b| self.match_unordered(&rest, &remaining, b, op, partial, k))
+        })
+    }
+}
+
+impl Rule {
+    /// Creates the rule, or returns `None` if `lhs` is a lone variable, which would match everything.
+    pub fn new(lhs: Expr, rhs: Expr) -> Option<Self> {
+        let lhs = lhs.simplify();
+        if matches!(lhs, Expr::Variable(_)) {
+            return None;
+        }
+
+        Some(Self { wildcards: lhs.variables(), lhs, rhs: rhs.simplify() })
+    }
+
+    /// The pattern and its replacement.
+    pub fn sides(&self) -> (&Expr, &Expr) {
+        (&self.lhs, &self.rhs)
+    }
+
+    /// Rewrites `expr` if the pattern matches it, or for a sum or product, if it matches some of its
+    /// terms or factors.
+    fn apply(&self, expr: &Expr) -> Option<Expr> {
+        let matcher = Matcher { wildcards: &self.wildcards, steps: Cell::new(0) };
+        let mut result = None;
+        let rewrite = |bindings: Bindings| self.rhs.clone().replace(&bindings
+            .into_iter()
+            .map(|(name, value)| (Expr::variable(name), value))
+            .collect::<Vec<_>>());
+
+        let op = match &self.lhs {
+            Expr::Sum { .. } => Some(Op::Sum),
+            Expr::Product { .. } | Expr::Ratio { .. } => Some(Op::Product),
+            _ => None,
+        };
+        match op {
+            Some(op) => matcher.match_unordered(&op.split(&self.lhs), &op.split(expr), Vec::new(), op, true, &mut |bindings, rest| {
+                result = Some(op.join([vec![rewrite(bindings)], rest].concat()));
+                true
+            }),
+            None => matcher.matches(&self.lhs, expr, Vec::new(), &mut |bindings| {
+                result = Some(rewrite(bindings));
+                true
+            }),
+        };
+
+        result
+    }
+}
+
+impl Expr {
+    /// Rewrites every subexpression matching one of `rules`, working outwards from the innermost.
+    ///
+    /// Only the first rule that matches is applied to each subexpression.
+    pub fn rewrite(self, rules: &[Rule]) -> Expr {
+        trace::scope(self, "apply rule", |expr| {
+            let expr = expr.map_children(|x| x.rewrite(rules));
+            match rules.iter().find_map(|rule| rule.apply(&expr).map(|x| (rule, x))) {
+                Some((rule, rewritten)) => trace::step(format!("apply rule {}", rule), rewritten),
+                None => expr,
+            }
+        })
+    }
+}
+
+impl std::fmt::Display for Rule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:#} -> {:#}", self.lhs, self.rhs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{session::Session, testing::{error, eval_in}};
+
+    fn with_rules(rules: &[&str]) -> Session {
+        let mut session = Session::new();
+        for rule in rules {
+            eval_in(&mut session, rule);
+        }
+        session
+    }
+
+    #[test]
+    fn wildcards_match_any_expression() {
+        let mut session = with_rules(&["rule[f[x] -> 2x]"]);
+        assert_eq!(eval_in(&mut session, "f[3] + f[y]"), "(6 + 2y)");
+        let mut session = with_rules(&["rule[g[x, x] -> 0]"]);
+        assert_eq!(eval_in(&mut session, "g[1, 1] + g[1, 2]"), "g[1, 2]");
+    }
+
+    #[test]
+    fn rules_match_terms_of_sums() {
+        let mut session = with_rules(&["rule[sin[a]^2 + cos[a]^2 -> 1]"]);
+        assert_eq!(eval_in(&mut session, "sin[t]^2 + cos[t]^2 + t"), "(1 + t)");
+    }
+
+    #[test]
+    fn bound_variables_stay_wildcards() {
+        let mut session = Session::new();
+        eval_in(&mut session, "a := 3");
+        assert_eq!(eval_in(&mut session, "rule[sin[a]^2 + cos[a]^2 -> 1]"), "((sin[a] ^ 2) + (cos[a] ^ 2)) -> 1");
+        assert_eq!(eval_in(&mut session, "sin[u]^2 + cos[u]^2"), "1");
+    }
+
+    #[test]
+    fn malformed_rules() {
+        assert_eq!(error("rule[x -> 1]"), "the left side of a rule cannot be a lone variable, as it would match everything");
+        assert_eq!(error("rule[1]"), "expected a single rule like sin[a]^2 + cos[a]^2 -> 1");
+        assert_eq!(error("rule[a -> b, c -> d]"), "expected a single rule like sin[a]^2 + cos[a]^2 -> 1");
+    }
+}
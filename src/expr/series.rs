@@ -0,0 +1,362 @@
+use num_bigint::BigInt;
+use num_traits::{ToPrimitive, Zero};
+
+use super::{functions, number::Number, Expr};
+
+
+/// Extra working terms tried, in turn, when cancellation or negative powers eat into the precision of a
+/// series before it reaches the requested order.
+const EXTRA_TERMS: [i64; 5] = [0, 2, 4, 8, 16];
+
+/// A truncated Laurent series `Σ coeffs[i] t^(valuation + i) + O(t^order)` in a shifted variable `t`.
+#[derive(Clone)]
+pub struct Series {
+    pub valuation: i64,
+    pub coeffs: Vec<Expr>,
+}
+
+fn int(n: i64) -> Expr {
+    Expr::integer(n.into())
+}
+
+fn is_zero(expr: &Expr) -> bool {
+    matches!(expr, Expr::Integer(n) if n.is_zero())
+}
+
+fn add(a: Expr, b: Expr) -> Expr {
+    Expr::sum(a.boxed(), b.boxed()).simplify()
+}
+
+fn mul(a: Expr, b: Expr) -> Expr {
+    Expr::product(a.boxed(), b.boxed()).simplify()
+}
+
+fn div(a: Expr, b: Expr) -> Expr {
+    Expr::ratio(a.boxed(), b.boxed()).simplify()
+}
+
+fn call(name: &str, arg: Expr) -> Expr {
+    Expr::function(name.to_string(), vec![arg]).simplify()
+}
+
+impl Series {
+    /// The exponent of the first unknown term.
+    pub fn order(&self) -> i64 {
+        self.valuation + self.coeffs.len() as i64
+    }
+
+    /// The coefficient of `t^exp`, which must be below the order.
+    pub fn coeff(&self, exp: i64) -> Expr {
+        if exp < self.valuation {
+            int(0)
+        } else {
+            self.coeffs[(exp - self.valuation) as usize].clone()
+        }
+    }
+
+    /// The lowest power with a non-zero coefficient, along with that coefficient.
+    pub fn leading(&self) -> Option<(i64, Expr)> {
+        self.coeffs.iter()
+            .enumerate()
+            .find(|(_, c)| !is_zero(c))
+            .map(|(i, c)| (self.valuation + i as i64, c.clone()))
+    }
+
+    fn constant(c: Expr, n: i64) -> Self {
+        let mut coeffs = vec![c];
+        coeffs.resize(n.max(1) as usize, int(0));
+        Self { valuation: 0, coeffs }
+    }
+
+    fn from_fn(valuation: i64, order: i64, f: impl FnMut(i64) -> Expr) -> Self {
+        Self { valuation, coeffs: (valuation..order).map(f).collect() }
+    }
+
+    /// Drops leading zero coefficients, so the valuation is that of the first non-zero term.
+    fn normalized(mut self) -> Self {
+        let zeros = self.coeffs.iter().take_while(|c| is_zero(c)).count();
+        self.coeffs.drain(..zeros);
+        self.valuation += zeros as i64;
+        self
+    }
+
+    fn scale(self, c: &Expr) -> Self {
+        Self { valuation: self.valuation, coeffs: self.coeffs.into_iter().map(|x| mul(c.clone(), x)).collect() }
+    }
+
+    fn add(&self, rhs: &Series) -> Self {
+        let order = self.order().min(rhs.order());
+        Self::from_fn(self.valuation.min(rhs.valuation), order, |e| add(self.coeff(e), rhs.coeff(e)))
+    }
+
+    fn mul(&self, rhs: &Series) -> Self {
+        let (a, b) = (self.clone().normalized(), rhs.clone().normalized());
+        let order = (a.order() + b.valuation).min(b.order() + a.valuation);
+        Self::from_fn(a.valuation + b.valuation, order, |e| {
+            (a.valuation..=e - b.valuation)
+                .map(|i| (a.coeff(i), b.coeff(e - i)))
+                .filter(|(x, y)| !is_zero(x) && !is_zero(y))
+                .fold(int(0), |acc, (x, y)| add(acc, mul(x, y)))
+        })
+    }
+
+    fn inv(&self) -> Option<Self> {
+        self.pow(&int(-1))
+    }
+
+    /// Raises the series to a power that does not depend on `t`.
+    ///
+    /// Uses the recurrence `g_k = 1/(k a_0) Σ ((p + 1) i - k) a_i g_(k-i)` for the coefficients of
+    /// `(Σ a_i t^i)^p`, so the exponent may be symbolic. Returns `None` when the result would need
+    /// fractional powers of `t`.
+    fn pow(&self, p: &Expr) -> Option<Self> {
+        let a = self.clone().normalized();
+        let a0 = a.coeffs.first()?.clone();
+
+        let valuation = if a.valuation == 0 {
+            0
+        } else {
+            // Products like `-2 (1 / 2)` simplify to `-(1)` rather than an integer.
+            match Number::from_expr(&mul(int(a.valuation), p.clone()))? {
+                Number::Rational(v) if v.is_integer() => v.to_integer().to_i64()?,
+                _ => return None,
+            }
+        };
+
+        let len = a.coeffs.len();
+        let mut g = vec![Expr::power(a0.clone().boxed(), p.clone().boxed()).simplify()];
+        for k in 1..len {
+            let sum = (1..=k).fold(int(0), |acc, i| {
+                let weight = add(mul(add(p.clone(), int(1)), int(i as i64)), int(-(k as i64)));
+                add(acc, mul(weight, mul(a.coeffs[i].clone(), g[k - i].clone())))
+            });
+            g.push(div(sum, mul(int(k as i64), a0.clone())));
+        }
+
+        Some(Self { valuation, coeffs: g })
+    }
+
+    fn derivative(&self) -> Self {
+        Self::from_fn(self.valuation - 1, self.order() - 1, |e| mul(int(e + 1), self.coeff(e + 1)))
+    }
+
+    /// Integrates term by term with a zero constant, failing if there is a `t^-1` term.
+    fn integral(&self) -> Option<Self> {
+        if self.valuation <= -1 && !is_zero(&self.coeff(-1)) {
+            return None;
+        }
+
+        let valuation = self.valuation.max(0) + 1;
+        Some(Self::from_fn(valuation, self.order() + 1, |e| div(self.coeff(e - 1), int(e))))
+    }
+
+    /// Splits off the constant term, leaving a series with positive valuation.
+    fn split_constant(&self) -> Option<(Expr, Series)> {
+        let a = self.clone().normalized();
+        if a.valuation < 0 || a.order() <= 0 {
+            return None;
+        }
+
+        let c0 = a.coeff(0);
+        let rest = Self::from_fn(1, a.order(), |e| a.coeff(e));
+        Some((c0, rest))
+    }
+
+    /// Evaluates `Σ c_k u^k` for a series `u` with positive valuation.
+    fn compose(u: &Series, n: i64, mut c: impl FnMut(usize) -> Expr) -> Self {
+        let order = u.order().min(n);
+        let mut result = Self::constant(c(0), order);
+        let mut power = Self::constant(int(1), order);
+        for k in 1..order.max(0) as usize {
+            power = power.mul(u);
+            if power.valuation >= order {
+                break;
+            }
+            let coeff = c(k);
+            if !is_zero(&coeff) {
+                result = result.add(&power.clone().scale(&coeff));
+            }
+        }
+        result
+    }
+
+    fn exp(&self, n: i64) -> Option<Self> {
+        let (c0, u) = self.split_constant()?;
+        let mut factorial = BigInt::from(1);
+        let series = Self::compose(&u, n, |k| {
+            if k > 0 {
+                factorial *= k;
+            }
+            div(int(1), Expr::integer(factorial.clone()))
+        });
+        Some(series.scale(&call("exp", c0)))
+    }
+
+    /// The series of `sin` and `cos` of `self`, as `(sin, cos)`.
+    fn sin_cos(&self, n: i64) -> Option<(Self, Self)> {
+        let (c0, u) = self.split_constant()?;
+        let taylor = |odd: bool| move |k: usize| {
+            if (k % 2 == 1) != odd {
+                return int(0);
+            }
+            let sign = if (k / 2).is_multiple_of(2) { 1 } else { -1 };
+            let factorial: BigInt = (1..=k as u64).product();
+            div(int(sign), Expr::integer(factorial))
+        };
+
+        let (s, c) = (Self::compose(&u, n, taylor(true)), Self::compose(&u, n, taylor(false)));
+        let (sin0, cos0) = (call("sin", c0.clone()), call("cos", c0));
+        let sin = c.clone().scale(&sin0).add(&s.clone().scale(&cos0));
+        let cos = c.scale(&cos0).add(&s.scale(&Expr::negation(sin0.boxed()).simplify()));
+        Some((sin, cos))
+    }
+
+    /// Evaluates `f(c0) + ∫ f'(self) self' dt`, for functions with a simple derivative.
+    fn integrate_derivative(&self, f0: Expr, df: Self) -> Option<Self> {
+        let integral = df.mul(&self.derivative()).integral()?;
+        Some(integral.add(&Self::constant(f0, self.order())))
+    }
+}
+
+
+/// Expands `expr` in `t`, where `var = point + t`, computing terms below `t^n`.
+fn expand(expr: &Expr, var: &str, point: &Expr, n: i64) -> Option<Series> {
+    if !expr.contains_var(var) {
+        return Some(Series::constant(expr.clone().simplify(), n));
+    }
+
+    Some(match expr {
+        Expr::Variable(_) => Series::from_fn(0, n.max(2), |e| match e {
+            0 => point.clone(),
+            1 => int(1),
+            _ => int(0),
+        }),
+        Expr::Negation(x) => expand(x, var, point, n)?.scale(&int(-1)),
+        Expr::Sum { left, right } => expand(left, var, point, n)?.add(&expand(right, var, point, n)?),
+        Expr::Difference { left, right } => expand(left, var, point, n)?.add(&expand(right, var, point, n)?.scale(&int(-1))),
+        Expr::Product { left, right } => expand(left, var, point, n)?.mul(&expand(right, var, point, n)?),
+        Expr::Ratio { numerator, denominator } => expand(numerator, var, point, n)?.mul(&expand(denominator, var, point, n)?.inv()?),
+        Expr::Power { base, exp } if !exp.contains_var(var) => expand(base, var, point, n)?.pow(&exp.clone().simplify())?,
+        Expr::Power { base, exp } => {
+            let ln = Expr::function("ln".to_string(), vec![base.clone().into_inner()]);
+            expand(&Expr::product(exp.clone(), ln.boxed()), var, point, n)?.exp(n)?
+        },
+        Expr::Root { index, radicand } if !index.contains_var(var) => {
+            let p = Expr::ratio(int(1).boxed(), index.clone()).simplify();
+            expand(radicand, var, point, n)?.pow(&p)?
+        },
+        Expr::Function { name, args } if args.len() == 1 => {
+            let arg = expand(&args[0], var, point, n)?;
+            match name.as_str() {
+                "exp" => arg.exp(n)?,
+                "sin" => arg.sin_cos(n)?.0,
+                "cos" => arg.sin_cos(n)?.1,
+                "tan" => {
+                    let (sin, cos) = arg.sin_cos(n)?;
+                    sin.mul(&cos.inv()?)
+                },
+                "sinh" | "cosh" => {
+                    let pos = arg.exp(n)?;
+                    let neg = arg.scale(&int(-1)).exp(n)?;
+                    let sign = if name == "sinh" { -1 } else { 1 };
+                    pos.add(&neg.scale(&int(sign))).scale(&div(int(1), int(2)))
+                },
+                "ln" => {
+                    let arg = arg.normalized();
+                    if arg.valuation != 0 {
+                        return None;
+                    }
+                    arg.integrate_derivative(call("ln", arg.coeff(0)), arg.inv()?)?
+                },
+                "atan" => {
+                    let (c0, _) = arg.split_constant()?;
+                    let df = Series::constant(int(1), n).add(&arg.mul(&arg)).inv()?;
+                    arg.integrate_derivative(call("atan", c0), df)?
+                },
+                "asin" | "acos" => {
+                    let (c0, _) = arg.split_constant()?;
+                    let df = Series::constant(int(1), n).add(&arg.mul(&arg).scale(&int(-1))).pow(&div(int(-1), int(2)))?;
+                    let sign = if name == "asin" { 1 } else { -1 };
+                    arg.integrate_derivative(call(name, c0), df.scale(&int(sign)))?
+                },
+                _ if functions::is_elementary(name) => return None,
+                _ => {
+                    let (c0, u) = arg.split_constant()?;
+                    let mut factorial = BigInt::from(1);
+                    Series::compose(&u, n, |k| {
+                        if k > 0 {
+                            factorial *= k;
+                        }
+                        let f = if k == 0 {
+                            Expr::function(name.clone(), vec![c0.clone()])
+                        } else {
+                            Expr::derivative(name.clone(), k, vec![c0.clone()])
+                        };
+                        div(f, Expr::integer(factorial.clone()))
+                    })
+                },
+            }
+        },
+        Expr::Derivative { name, order, args } if args.len() == 1 => {
+            let (c0, u) = expand(&args[0], var, point, n)?.split_constant()?;
+            let mut factorial = BigInt::from(1);
+            Series::compose(&u, n, |k| {
+                if k > 0 {
+                    factorial *= k;
+                }
+                div(Expr::derivative(name.clone(), order + k, vec![c0.clone()]), Expr::integer(factorial.clone()))
+            })
+        },
+        _ => return None,
+    })
+}
+
+impl Expr {
+    /// The Laurent series of the expression in `var` around `point`, with every term below
+    /// `(var - point)^order`.
+    ///
+    /// Returns `None` for expressions without such a series, like `ln[x]` or `sqrt[x]` around `0`.
+    pub fn laurent(&self, var: &str, point: &Expr, order: i64) -> Option<Series> {
+        EXTRA_TERMS.iter()
+            .filter_map(|extra| expand(self, var, point, order + extra))
+            .find(|series| series.order() >= order)
+            .map(|series| Series::from_fn(series.valuation.min(order), order, |e| series.coeff(e)))
+    }
+
+    /// The truncated series of the expression in `var` around `point`, ending with an order term.
+    pub fn series(&self, var: &str, point: &Expr, order: i64) -> Option<Expr> {
+        let series = self.laurent(var, point, order)?;
+        let t = Expr::difference(Expr::variable(var.to_string()).boxed(), point.clone().boxed()).simplify();
+
+        (series.valuation..order)
+            .map(|e| Expr::product(series.coeff(e).boxed(), Expr::power(t.clone().boxed(), int(e).boxed()).boxed()))
+            .chain(std::iter::once(Expr::order(t.clone().boxed(), int(order).boxed())))
+            .reduce(|acc, term| Expr::sum(acc.boxed(), term.boxed()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{session::Session, testing::{eval, eval_in}};
+
+    #[test]
+    fn taylor_coefficients() {
+        assert_eq!(eval("series:x:0:5[exp[x]]"), "(((((1 + x) + ((x ^ 2) / 2)) + ((x ^ 3) / 6)) + ((x ^ 4) / 24)) + O(x⁵))");
+        assert_eq!(eval("series:x:0:6[sin[x]]"), "(((x + -((x ^ 3) / 6)) + ((x ^ 5) / 120)) + O(x⁶))");
+        assert_eq!(eval("series:x:0:4[1/(1-x)]"), "((((1 + x) + (x ^ 2)) + (x ^ 3)) + O(x⁴))");
+        assert_eq!(eval("series:x:0:4[ln[1+x]]"), "(((x + -((x ^ 2) / 2)) + ((x ^ 3) / 3)) + O(x⁴))");
+    }
+
+    #[test]
+    fn laurent_terms_stay_apart() {
+        assert_eq!(eval("series:x:0:4[1/sin[x]]"), "((((1 / x) + (x / 6)) + (7(x ^ 3) / 360)) + O(x⁴))");
+        assert_eq!(eval("series:x:0:3[1/x + exp[x]]"), "(((((1 / x) + 1) + x) + ((x ^ 2) / 2)) + O(x³))");
+    }
+
+    #[test]
+    fn bound_variable_is_not_substituted() {
+        let mut session = Session::new();
+        eval_in(&mut session, "x := 3");
+        assert_eq!(eval_in(&mut session, "series:x:0:3[exp[x]]"), "(((1 + x) + ((x ^ 2) / 2)) + O(x³))");
+    }
+}
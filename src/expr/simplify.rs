@@ -1,107 +1,672 @@
-use super::Expr;
+use num_bigint::BigInt;
+use num_integer::{Integer, Roots};
+use num_rational::BigRational;
+use num_traits::{One, Signed, ToPrimitive, Zero};
+
+use super::{functions, interned::Interned, limit::contains_infinity, matrix, number::Number, number_theory::signed_gcd, special, summation, trace, Expr};
 use crate::prelude::*;
 
+
+/// Integer powers whose result would need more bits than this are left unevaluated.
+const MAX_EXACT_POWER_BITS: u64 = 1 << 20;
+
+/// Sums containing an order term are only expanded into powers up to this exponent.
+const MAX_SERIES_POWER: u32 = 64;
+
 impl Expr {
     pub fn simplify(self) -> Self {
+        trace::scope(self, "simplify", Expr::simplify_node)
+    }
+
+    /// Simplifies the expression, naming the rewrite made at its root for a trace.
+    fn simplify_node(self) -> Self {
         match self {
             Expr::Integer(c) => Expr::Integer(c),
             Expr::Decimal(c) => Expr::Decimal(c),
             Expr::Variable(s) => Expr::Variable(s),
+            Expr::Infinity => Expr::Infinity,
+            Expr::Boolean(b) => Expr::Boolean(b),
+            Expr::Equivalence(e) => Expr::Equivalence(e),
+            Expr::Interval(i) => Expr::Interval(i),
+            Expr::Solution(s) => Expr::Solution(s),
+            Expr::Integral(i) => Expr::Integral(i),
+            Expr::Factorization { negative, factors } => Expr::Factorization { negative, factors },
             Expr::Negation(v) => match v.clone().simplify() {
-                Expr::Integer(x) => Expr::integer(-x),
-                Expr::Decimal(x) => Expr::decimal(-x),
-                other => Expr::negation(other.boxed()),
+                Expr::Matrix { rows } => trace::step("negate entries", Expr::matrix(matrix::map(rows, |x| Expr::negation(x.boxed()).simplify()))),
+                Expr::Integer(x) => trace::step("negate", Expr::integer(-x)),
+                Expr::Decimal(x) => trace::step("negate", Expr::decimal(-x)),
+                Expr::Negation(x) => trace::step("cancel double negation", x.into_inner()),
+                order @ Expr::Order { .. } => trace::step("absorb sign into order term", order),
+                other => match Number::from_expr(&other) {
+                    Some(n) => trace::step("negate", (-n).into_expr()),
+                    None => Expr::negation(other.boxed()),
+                },
+            },
+            // The terms of a series are simplified one by one and kept apart, rather than the sum of
+            // those before the order term being merged into a single fraction.
+            Expr::Sum { left, right } if has_order_term(&left) || has_order_term(&right) => {
+                let mut terms = Vec::new();
+                flatten_sum(Expr::sum(left, right), false, &mut terms);
+                let terms = terms.into_iter()
+                    .map(|(term, negate)| match negate {
+                        true => Expr::negation(term.boxed()).simplify(),
+                        false => term.simplify(),
+                    })
+                    .collect();
+                trace::step("collect like terms", collect_sum(terms))
             },
             Expr::Sum { left, right } => match (left.clone().simplify(), right.clone().simplify()) {
-                (Expr::Integer(a), Expr::Integer(b)) => Expr::integer(a + b),
-                (Expr::Decimal(a), Expr::Decimal(b)) => Expr::decimal(a + b),
-                (Expr::Integer(a), Expr::Decimal(b)) | (Expr::Decimal(b), Expr::Integer(a)) => Expr::decimal(a as f64 + b),
-
-                (Expr::Integer(x), Expr::Ratio { numerator, denominator }) | (Expr::Ratio { numerator, denominator }, Expr::Integer(x)) =>
-                    Expr::ratio(
-                        Expr::sum(numerator, Expr::product(Expr::integer(x).boxed(), denominator.clone())
-                            .simplify().boxed()).simplify().boxed(),
-                        denominator,
-                    ).simplify(),
+                (Expr::Matrix { rows: a }, Expr::Matrix { rows: b }) => match matrix::add(&a, &b) {
+                    Some(rows) => trace::step("add matrices", Expr::matrix(rows)),
+                    None => Expr::sum(Expr::matrix(a).boxed(), Expr::matrix(b).boxed()),
+                },
+                (l, r) if !matrix::is_scalar(&l) || !matrix::is_scalar(&r) => Expr::sum(l.boxed(), r.boxed()),
+                (l, r) if infinity_sign(&l).is_some() || infinity_sign(&r).is_some() => match add_infinite(&l, &r) {
+                    Some(x) => trace::step("add to infinity", x),
+                    None => Expr::sum(l.boxed(), r.boxed()),
+                },
+
+                (Expr::Integer(a), Expr::Integer(b)) => trace::step("add numbers", Expr::integer(a + b)),
+                (Expr::Decimal(a), Expr::Decimal(b)) => trace::step("add numbers", Expr::decimal(a + b)),
+                (Expr::Integer(a), Expr::Decimal(b)) | (Expr::Decimal(b), Expr::Integer(a)) => trace::step("add numbers", Expr::decimal(a.to_f64().unwrap() + b)),
+
+                (Expr::Integer(z), other) | (other, Expr::Integer(z)) if z.is_zero() => trace::step("drop zero term", other),
 
-                (Expr::Ratio { numerator: n1, denominator: d1 }, Expr::Ratio { numerator: n2, denominator: d2 }) => {
-                    let d = Expr::product(d1.clone(), d2.clone()).simplify();
-                    let n = Expr::sum(Expr::product(n1, d2).simplify().boxed(), Expr::product(n2, d1).simplify().boxed()).simplify();
-                    Expr::ratio(n.boxed(), d.boxed()).simplify()
+                (Expr::Integer(x), Expr::Ratio { numerator, denominator }) | (Expr::Ratio { numerator, denominator }, Expr::Integer(x))
+                    if !denominator.is_numeric() && !has_order_term(&numerator) => trace::step("add over common denominator", Expr::ratio(
+                        Expr::sum(numerator, Expr::product(Expr::integer(x).boxed(), denominator.clone()).simplify().boxed()).simplify().boxed(),
+                        denominator,
+                    ).simplify()),
+                (l, r) if fraction_parts(&l).zip(fraction_parts(&r)).is_some_and(|(a, b)| can_add_fractions(&a, &b)) => {
+                    let (Some(a), Some(b)) = (fraction_parts(&l), fraction_parts(&r)) else { unreachable!() };
+                    trace::step("add over common denominator", add_fractions(a, b))
                 },
 
-                _ => Expr::sum(left, right)
+                (l, r) => trace::step("collect like terms", collect_sum(vec![l, r])),
             },
             Expr::Difference { left, right } => Expr::sum(left, Expr::negation(right).simplify().boxed()).simplify(),
             Expr::Product { left, right } => match (left.clone().simplify(), right.clone().simplify()) {
-                (Expr::Integer(a), Expr::Integer(b)) => Expr::integer(a * b),
-                (Expr::Decimal(a), Expr::Decimal(b)) => Expr::decimal(a * b),
-                (Expr::Integer(a), Expr::Decimal(b)) | (Expr::Decimal(b), Expr::Integer(a)) => Expr::decimal(a as f64 * b),
+                (Expr::Matrix { rows: a }, Expr::Matrix { rows: b }) => match matrix::mul(&a, &b) {
+                    Some(rows) => trace::step("multiply matrices", Expr::matrix(rows)),
+                    None => Expr::product(Expr::matrix(a).boxed(), Expr::matrix(b).boxed()),
+                },
+                (Expr::Matrix { rows }, c) | (c, Expr::Matrix { rows }) if matrix::is_scalar(&c) => trace::step("scale matrix", Expr::matrix(matrix::scale(rows, &c))),
+                (l, r) if !matrix::is_scalar(&l) || !matrix::is_scalar(&r) => Expr::product(l.boxed(), r.boxed()),
+                (l, r) if infinity_sign(&l).is_some() || infinity_sign(&r).is_some() => match multiply_infinite(&l, &r) {
+                    Some(x) => trace::step("multiply infinity", x),
+                    None => Expr::product(l.boxed(), r.boxed()),
+                },
+
+                (Expr::Integer(a), Expr::Integer(b)) => trace::step("multiply numbers", Expr::integer(a * b)),
+                (Expr::Decimal(a), Expr::Decimal(b)) => trace::step("multiply numbers", Expr::decimal(a * b)),
+                (Expr::Integer(a), Expr::Decimal(b)) | (Expr::Decimal(b), Expr::Integer(a)) => trace::step("multiply numbers", Expr::decimal(a.to_f64().unwrap() * b)),
 
-                (Expr::Integer(x), Expr::Ratio { numerator, denominator }) | (Expr::Ratio { numerator, denominator }, Expr::Integer(x)) =>
-                    Expr::ratio(Expr::product(Expr::integer(x).boxed(), denominator.clone()).simplify().boxed(), denominator).simplify(),
+                (Expr::Integer(z), _) | (_, Expr::Integer(z)) if z.is_zero() => trace::step("multiply by zero", Expr::integer(z)),
+                (Expr::Integer(o), other) | (other, Expr::Integer(o)) if o.is_one() => trace::step("drop factor of 1", other),
 
-                (Expr::Ratio { numerator: n1, denominator: d1 }, Expr::Ratio { numerator: n2, denominator: d2 }) =>
-                    Expr::ratio(Expr::product(n1, n2).simplify().boxed(), Expr::product(d1, d2).simplify().boxed()).simplify(),
+                (l, r) if has_order_term(&l) || has_order_term(&r) => trace::step("multiply series", multiply_series(l, r)),
 
-                _ => Expr::product(left, right)
+                (l, r) => {
+                    let mut monomial = Monomial::one();
+                    monomial.push(l, &BigInt::one());
+                    monomial.push(r, &BigInt::one());
+                    trace::step("combine factors", monomial.into_expr())
+                },
             },
             Expr::Ratio { numerator, denominator } => match (numerator.clone().simplify(), denominator.clone().simplify()) {
-                (Expr::Integer(n), Expr::Integer(d)) => if d == 0 {
+                (Expr::Matrix { rows }, d) if matrix::is_scalar(&d) => trace::step("divide entries", Expr::matrix(matrix::map(rows, |x| Expr::ratio(x.boxed(), d.clone().boxed()).simplify()))),
+                (n, d) if !matrix::is_scalar(&n) || !matrix::is_scalar(&d) => Expr::ratio(n.boxed(), d.boxed()),
+                (n, d) if infinity_sign(&n).is_some() || infinity_sign(&d).is_some() => match divide_infinite(&n, &d) {
+                    Some(x) => trace::step("divide infinity", x),
+                    None => Expr::ratio(n.boxed(), d.boxed()),
+                },
+
+                (Expr::Integer(n), Expr::Integer(d)) => if d.is_zero() {
                     Expr::ratio(Expr::integer(n).boxed(), Expr::integer(d).boxed())
-                } else if n == 0 {
-                    Expr::integer(0)
-                } else if n % d == 0 {
-                    Expr::integer(n / d)
+                } else if n.is_zero() {
+                    trace::step("divide zero", Expr::integer(BigInt::zero()))
+                } else if (&n % &d).is_zero() {
+                    trace::step("divide numbers", Expr::integer(n / d))
                 } else {
-                    let cd = signed_gcd(n, d);
-                    Expr::ratio(Expr::integer(n / cd).boxed(), Expr::integer(d / cd).boxed())
+                    let cd = signed_gcd(&n, &d);
+                    let sign = if d.is_negative() { -BigInt::one() } else { BigInt::one() };
+                    let name = format!("reduce by gcd {}", cd.abs());
+                    trace::step(name, Expr::ratio(Expr::integer(n / &cd * &sign).boxed(), Expr::integer(d / cd * sign).boxed()))
+                },
+
+                (n, Expr::Integer(d)) if d.is_one() => trace::step("divide by 1", n),
+                (n, d) if Number::from_expr(&d).is_some_and(|d| d.is_zero()) => Expr::ratio(n.boxed(), d.boxed()),
+                (Expr::Integer(n), _) if n.is_zero() => trace::step("divide zero", Expr::integer(n)),
+
+                (n, d) if has_order_term(&n) => trace::step("divide series", multiply_series(n, Expr::power(d.boxed(), Expr::integer(-BigInt::one()).boxed()).simplify())),
+
+                (n, d) => {
+                    let mut monomial = Monomial::one();
+                    monomial.push(n, &BigInt::one());
+                    monomial.push(d, &-BigInt::one());
+                    trace::step("combine fractions", monomial.into_expr())
+                },
+            },
+            Expr::Power { base, exp } => match (base.clone().simplify(), exp.clone().simplify()) {
+                (b, e) if infinity_sign(&b).is_some() || infinity_sign(&e).is_some() => match raise_infinite(&b, &e) {
+                    Some(x) => trace::step("raise infinity", x),
+                    None => Expr::power(b.boxed(), e.boxed()),
+                },
+                (Expr::Matrix { rows }, Expr::Integer(e)) if e.abs().to_u64().is_some_and(|e| e <= matrix::MAX_POWER) => {
+                    let k = e.abs().to_u64().unwrap();
+                    let base = match matrix::dims(&rows) {
+                        (n, m) if n == m && e.is_negative() => matrix::det(&rows)
+                            .filter(|det| !Number::from_expr(det).is_some_and(|d| d.is_zero()))
+                            .map(|det| matrix::inverse(&rows, &det)),
+                        _ => Some(rows.clone()),
+                    };
+                    match base.and_then(|base| matrix::pow(&base, k)) {
+                        Some(rows) => trace::step("raise matrix to power", Expr::matrix(rows)),
+                        None => Expr::power(Expr::matrix(rows).boxed(), Expr::integer(e).boxed()),
+                    }
+                },
+                (b, e) if !matrix::is_scalar(&b) || !matrix::is_scalar(&e) => Expr::power(b.boxed(), e.boxed()),
+
+                (b, Expr::Integer(e)) if e.is_one() => trace::step("drop exponent 1", b),
+                (_, Expr::Integer(e)) if e.is_zero() => trace::step("raise to power 0", Expr::integer(BigInt::one())),
+                (Expr::Integer(b), _) if b.is_one() => trace::step("raise 1 to a power", Expr::integer(b)),
+                (Expr::Integer(b), Expr::Integer(e)) => match e.abs().to_u32().filter(|x| b.bits() * *x as u64 <= MAX_EXACT_POWER_BITS) {
+                    Some(x) if e.is_positive() => trace::step("evaluate power", Expr::integer(b.pow(x))),
+                    Some(x) => trace::step("evaluate power", Expr::ratio(Expr::integer(BigInt::one()).boxed(), Expr::integer(b.pow(x)).boxed()).simplify()),
+                    None => Expr::power(Expr::integer(b).boxed(), Expr::integer(e).boxed()),
+                },
+                (Expr::Decimal(b), Expr::Decimal(e)) => trace::step("evaluate power", Expr::decimal(b.powf(e))),
+                (Expr::Integer(b), Expr::Decimal(e)) => trace::step("evaluate power", Expr::decimal(b.to_f64().unwrap().powf(e))),
+                (Expr::Decimal(b), Expr::Integer(e)) => trace::step("evaluate power", Expr::decimal(b.powf(e.to_f64().unwrap()))),
+
+                (Expr::Root { index, radicand }, e) if *index == e => trace::step("cancel root and power", radicand.into_inner()),
+                (Expr::Root { index, radicand }, Expr::Integer(e)) if matches!(&*index, Expr::Integer(n) if (&e % n).is_zero()) => {
+                    let Expr::Integer(n) = index.into_inner() else { unreachable!() };
+                    trace::step("cancel root and power", Expr::power(radicand, Expr::integer(e / n).boxed()).simplify())
+                },
+                (order @ Expr::Order { .. }, Expr::Integer(e)) if e.is_positive() => trace::step("expand power of series", (1..e.to_u32().unwrap_or(1))
+                    .fold(order.clone(), |acc, _| multiply_series(acc, order.clone()))),
+                (b, Expr::Integer(e)) if has_order_term(&b) && e.is_positive() && e.to_u32().is_some_and(|e| e <= MAX_SERIES_POWER) =>
+                    trace::step("expand power of series", (1..e.to_u32().unwrap()).fold(b.clone(), |acc, _| multiply_series(acc, b.clone()))),
+
+                (b, Expr::Integer(e)) if b.is_numeric() || matches!(b, Expr::Product { .. } | Expr::Ratio { .. } | Expr::Negation(_) | Expr::Power { .. }) || e.is_negative() => {
+                    let mut monomial = Monomial::one();
+                    monomial.push(b, &e);
+                    trace::step("distribute power", monomial.into_expr())
+                },
+                (b, e) if b.is_numeric() && e.is_numeric() => match (Number::from_expr(&b), Number::from_expr(&e)) {
+                    (Some(Number::Rational(b)), Some(Number::Rational(e))) => match e.denom().to_u32().and_then(|q| exact_root(&b, q)) {
+                        Some(root) => trace::step("take exact root", Expr::power(Number::Rational(root).into_expr().boxed(), Expr::integer(e.numer().clone()).boxed()).simplify()),
+                        None => Expr::power(Number::Rational(b).into_expr().boxed(), Number::Rational(e).into_expr().boxed()),
+                    },
+                    (Some(b), Some(e)) => trace::step("evaluate power", Expr::decimal(b.to_f64().powf(e.to_f64()))),
+                    _ => Expr::power(b.boxed(), e.boxed()),
+                },
+
+                (b, e) => Expr::power(b.boxed(), e.boxed()),
+            },
+            Expr::Root { index, radicand } => match (index.clone().simplify(), radicand.clone().simplify()) {
+                (Expr::Integer(n), r) if n.is_one() => trace::step("drop root of index 1", r),
+                (Expr::Integer(n), Expr::Decimal(r)) => match n.to_f64().unwrap() {
+                    n if r < 0.0 && n % 2.0 == 1.0 => trace::step("evaluate root", Expr::decimal(-(-r).powf(n.recip()))),
+                    n => trace::step("evaluate root", Expr::decimal(r.powf(n.recip()))),
                 },
+                (Expr::Integer(n), r) => match (n.to_u32(), Number::from_expr(&r)) {
+                    (Some(q), Some(Number::Rational(x))) if x.is_negative() && q % 2 == 1 => match exact_root(&-x, q) {
+                        Some(root) => trace::step("take exact root", Number::Rational(-root).into_expr()),
+                        None => Expr::root(Expr::integer(n).boxed(), r.boxed()),
+                    },
+                    (Some(q), Some(Number::Rational(x))) if !x.is_negative() => match exact_root(&x, q) {
+                        Some(root) => trace::step("take exact root", Number::Rational(root).into_expr()),
+                        None => Expr::root(Expr::integer(n).boxed(), r.boxed()),
+                    },
+                    _ => Expr::root(Expr::integer(n).boxed(), r.boxed()),
+                },
+                (n, r) => Expr::root(n.boxed(), r.boxed()),
+            },
+            Expr::Factorial(v) => match v.clone().simplify() {
+                Expr::Integer(n) => match n.to_u64().filter(|n| *n <= special::MAX_EXACT_FACTORIAL) {
+                    Some(n) => trace::step("evaluate factorial", Expr::integer(special::factorial(n))),
+                    None => Expr::factorial(Expr::integer(n).boxed()),
+                },
+                Expr::Decimal(x) => trace::step("evaluate factorial", Expr::decimal(special::gamma(x + 1.0))),
+                other => Expr::factorial(other.boxed()),
+            },
+            Expr::DoubleFactorial(v) => match v.clone().simplify() {
+                Expr::Integer(n) if n == BigInt::from(-1) => trace::step("evaluate double factorial", Expr::integer(BigInt::one())),
+                Expr::Integer(n) => match n.to_u64().filter(|n| *n <= special::MAX_EXACT_FACTORIAL) {
+                    Some(n) => trace::step("evaluate double factorial", Expr::integer(special::double_factorial(n))),
+                    None => Expr::doublefactorial(Expr::integer(n).boxed()),
+                },
+                other => Expr::doublefactorial(other.boxed()),
+            },
+            Expr::Gamma(v) => match v.clone().simplify() {
+                Expr::Integer(n) => match (&n - 1u32).to_u64().filter(|n| *n <= special::MAX_EXACT_FACTORIAL) {
+                    Some(m) => trace::step("evaluate gamma", Expr::integer(special::factorial(m))),
+                    None => Expr::gamma(Expr::integer(n).boxed()),
+                },
+                Expr::Decimal(x) => trace::step("evaluate gamma", Expr::decimal(special::gamma(x))),
+                other => Expr::gamma(other.boxed()),
+            },
+            Expr::Binomial { n, k } => match (n.clone().simplify(), k.clone().simplify()) {
+                (Expr::Integer(n), Expr::Integer(k)) => match special::binomial(&n, &k) {
+                    Some(c) => trace::step("evaluate binomial", Expr::integer(c)),
+                    None => Expr::binomial(Expr::integer(n).boxed(), Expr::integer(k).boxed()),
+                },
+                (_, Expr::Integer(k)) if k.is_zero() => trace::step("evaluate binomial", Expr::integer(BigInt::one())),
+                (n, Expr::Integer(k)) if k.is_one() => trace::step("evaluate binomial", n),
+
+                (n, k) => Expr::binomial(n.boxed(), k.boxed()),
+            },
+            Expr::Function { name, args } => {
+                let args: Vec<Expr> = args.into_iter().map(Expr::simplify).collect();
+                if args.len() != 1 || !functions::is_elementary(&name) {
+                    return Expr::function(name, args);
+                }
+
+                if let Some(exact) = functions::exact(&name, &args[0]) {
+                    return trace::step(format!("evaluate {}", name), exact.simplify());
+                }
+
+                match &args[0] {
+                    Expr::Decimal(x) => trace::step(format!("evaluate {}", name), Expr::decimal(functions::eval(&name, *x).unwrap())),
+                    _ => Expr::function(name, args),
+                }
+            },
+            Expr::Derivative { name, order, args } => Expr::derivative(name, order, args.into_iter().map(Expr::simplify).collect()),
+            Expr::Order { base, exp } => Expr::order(base.simplify().boxed(), exp.simplify().boxed()),
+            Expr::Matrix { rows } => Expr::matrix(matrix::map(rows, Expr::simplify)),
+            Expr::IndexedSum { var, from, to, body } => trace::step("evaluate sum", summation::sum(&var, from.simplify(), to.simplify(), body.simplify())),
+            Expr::IndexedProduct { var, from, to, body } => trace::step("evaluate product", summation::product(&var, from.simplify(), to.simplify(), body.simplify())),
+            Expr::Equals { left, right } => Expr::equals(left.simplify().boxed(), right.simplify().boxed()),
+            Expr::NotEquals { left, right } => Expr::notequals(left.simplify().boxed(), right.simplify().boxed()),
+            Expr::GreaterThan { left, right } => Expr::greaterthan(left.simplify().boxed(), right.simplify().boxed()),
+            Expr::LessThan { left, right } => Expr::lessthan(left.simplify().boxed(), right.simplify().boxed()),
+            Expr::GreaterThanEq { left, right } => Expr::greaterthaneq(left.simplify().boxed(), right.simplify().boxed()),
+            Expr::LessThanEq { left, right } => Expr::lessthaneq(left.simplify().boxed(), right.simplify().boxed()),
+
+        }
+    }
+
+    /// Whether the expression is a numeric constant like `3`, `-1/2` or `0.5`.
+    pub fn is_numeric(&self) -> bool {
+        Number::from_expr(self).is_some()
+    }
+}
+
+
+/// The exact `n`th root of a non-negative rational, if it is rational itself.
+fn exact_root(x: &BigRational, n: u32) -> Option<BigRational> {
+    if n == 0 || x.is_negative() {
+        return None;
+    }
+
+    let (num, den) = (x.numer().nth_root(n), x.denom().nth_root(n));
+    (num.pow(n) == *x.numer() && den.pow(n) == *x.denom()).then(|| BigRational::new(num, den))
+}
+
+
+/// A product split into a numeric coefficient and powers `base ^ exp`, with equal bases grouped.
+struct Monomial {
+    coef: Number,
+    powers: Vec<(Expr, Expr)>,
+}
+
+impl Monomial {
+    fn one() -> Self {
+        Self { coef: Number::one(), powers: Vec::new() }
+    }
+
+    /// Splits an already simplified term.
+    fn of(term: Expr) -> Self {
+        let mut monomial = Monomial::one();
+        monomial.push(term, &BigInt::one());
+        monomial
+    }
+
+    /// Multiplies the monomial by `expr ^ k`, where `expr` is already simplified.
+    fn push(&mut self, expr: Expr, k: &BigInt) {
+        if let Some(n) = Number::from_expr(&expr) {
+            let power = match (&n, k.to_i32()) {
+                (Number::Rational(r), Some(k)) if r.is_zero() && k < 0 => None,
+                (Number::Rational(r), Some(k)) if (r.numer().bits() + r.denom().bits()) * k.unsigned_abs() as u64 > MAX_EXACT_POWER_BITS => None,
+                (Number::Rational(r), Some(k)) => Some(Number::Rational(r.pow(k))),
+                (Number::Decimal(v), Some(k)) => Some(Number::Decimal(v.powi(k))),
+                _ => None,
+            };
+            if let Some(power) = power {
+                self.coef = self.coef.clone() * power;
+                return;
+            }
+        }
+
+        match expr {
+            Expr::Negation(x) => {
+                if k.is_odd() {
+                    self.coef = -self.coef.clone();
+                }
+                self.push(x.into_inner(), k);
+            },
+            Expr::Product { left, right } => {
+                self.push(left.into_inner(), k);
+                self.push(right.into_inner(), k);
+            },
+            Expr::Ratio { numerator, denominator } => {
+                self.push(numerator.into_inner(), k);
+                self.push(denominator.into_inner(), &-k);
+            },
+            Expr::Power { base, exp } if matches!(*exp, Expr::Integer(_)) => {
+                let Expr::Integer(e) = exp.into_inner() else { unreachable!() };
+                self.push(base.into_inner(), &(e * k));
+            },
+            Expr::Power { base, exp } => {
+                let exp = if k.is_one() { exp.into_inner() } else { Expr::product(Expr::integer(k.clone()).boxed(), exp).simplify() };
+                self.push_power(base.into_inner(), exp);
+            },
+            other => self.push_power(other, Expr::integer(k.clone())),
+        }
+    }
 
-                _ => Expr::ratio(numerator, denominator),
+    fn push_power(&mut self, base: Expr, exp: Expr) {
+        match self.powers.iter_mut().find(|(b, _)| *b == base) {
+            Some((_, e)) => *e = Expr::sum(e.clone().boxed(), exp.boxed()).simplify(),
+            None => self.powers.push((base, exp)),
+        }
+    }
+
+    fn same_powers(&self, other: &Monomial) -> bool {
+        self.powers.len() == other.powers.len() && self.powers.iter().all(|p| other.powers.contains(p))
+    }
+
+    /// The total exponent of `base`, or `None` when the monomial depends on the variables of `base`
+    /// in some other way.
+    fn degree_in(&self, base: &Expr) -> Option<BigInt> {
+        let vars = base.variables();
+        self.powers.iter().try_fold(BigInt::zero(), |acc, (b, e)| match e {
+            Expr::Integer(e) if b == base => Some(acc + e),
+            _ if vars.iter().any(|v| b.contains_var(v) || e.contains_var(v)) => None,
+            _ => Some(acc),
+        })
+    }
+
+    fn into_expr(self) -> Expr {
+        if self.coef.is_zero() {
+            return Expr::integer(BigInt::zero());
+        }
+
+        let mut numerator = Vec::new();
+        let mut denominator = Vec::new();
+        for (base, exp) in self.powers {
+            match exp {
+                Expr::Integer(e) if e.is_zero() => (),
+                Expr::Integer(e) if e.is_negative() => denominator.push(power_of(base, Expr::integer(-e))),
+                Expr::Negation(e) => denominator.push(power_of(base, e.into_inner())),
+                e if Number::from_expr(&e).is_some_and(|e| e.is_negative()) => denominator.push(power_of(base, Expr::negation(e.boxed()).simplify())),
+                e => numerator.push(power_of(base, e)),
+            }
+        }
+
+        let negative = self.coef.is_negative();
+        let coef = if negative { -self.coef } else { self.coef };
+        match coef {
+            Number::Rational(r) => {
+                let (n, d) = r.into();
+                if !n.is_one() || numerator.is_empty() {
+                    numerator.insert(0, Expr::integer(n));
+                }
+                if !d.is_one() {
+                    denominator.insert(0, Expr::integer(d));
+                }
             },
-            Expr::Power { base, exp } => todo!(),
-            Expr::Root { index, radicand } => todo!(),
-            Expr::Equals { left, right } => todo!(),
-            Expr::NotEquals { left, right } => todo!(),
-            Expr::GreaterThan { left, right } => todo!(),
-            Expr::LessThan { left, right } => todo!(),
-            Expr::GreaterThanEq { left, right } => todo!(),
-            Expr::LessThanEq { left, right } => todo!(),
+            Number::Decimal(v) => if v != 1.0 || numerator.is_empty() {
+                numerator.insert(0, Expr::decimal(v));
+            },
+        }
 
+        let product = |factors: Vec<Expr>| factors.into_iter()
+            .reduce(|acc, x| Expr::product(acc.boxed(), x.boxed()))
+            .unwrap_or(Expr::integer(BigInt::one()));
+
+        let expr = if denominator.is_empty() {
+            product(numerator)
+        } else {
+            Expr::ratio(product(numerator).boxed(), product(denominator).boxed())
+        };
+
+        if negative {
+            Expr::negation(expr.boxed())
+        } else {
+            expr
         }
     }
 }
 
+fn power_of(base: Expr, exp: Expr) -> Expr {
+    match exp {
+        Expr::Integer(e) if e.is_one() => base,
+        exp => match base {
+            Expr::Root { index, radicand } if *index == exp => radicand.into_inner(),
+            base => Expr::power(base.boxed(), exp.boxed()),
+        },
+    }
+}
+
+/// Whether the expression is `∞` or `-∞`, as `Some(false)` and `Some(true)`.
+fn infinity_sign(expr: &Expr) -> Option<bool> {
+    match expr {
+        Expr::Infinity => Some(false),
+        Expr::Negation(node) => infinity_sign(node).map(|negative| !negative),
+        _ => None,
+    }
+}
+
+fn infinity(negative: bool) -> Expr {
+    match negative {
+        true => Expr::negation(Expr::Infinity.boxed()),
+        false => Expr::Infinity,
+    }
+}
+
+/// The sign of an infinite or nonzero constant, as `Some(true)` if it is negative.
+fn sign(expr: &Expr) -> Option<bool> {
+    infinity_sign(expr).or_else(|| Number::from_expr(expr).filter(|n| !n.is_zero()).map(|n| n.is_negative()))
+}
 
-fn signed_gcd(a: i64, b: i64) -> i64 {
-    gcd(a.unsigned_abs(), b.unsigned_abs()) as i64
+/// `l + r` where either is `∞` or `-∞`, or `None` for `∞ - ∞` and terms that are infinite otherwise.
+fn add_infinite(l: &Expr, r: &Expr) -> Option<Expr> {
+    match (infinity_sign(l), infinity_sign(r)) {
+        (Some(a), Some(b)) => (a == b).then(|| infinity(a)),
+        (Some(negative), None) => (!contains_infinity(r)).then(|| infinity(negative)),
+        (None, Some(negative)) => (!contains_infinity(l)).then(|| infinity(negative)),
+        (None, None) => None,
+    }
 }
 
+/// `l r` where either is `∞` or `-∞`, or `None` for `0 ∞` and factors of unknown sign.
+fn multiply_infinite(l: &Expr, r: &Expr) -> Option<Expr> {
+    Some(infinity(sign(l)? != sign(r)?))
+}
 
-// https://www.wikiwand.com/en/Binary_GCD_algorithm
-fn gcd(mut a: u64, mut b: u64) -> u64 {
-    if a == 0 {
-        return b;
-    } else if b == 0 {
-        return a;
+/// `n / d` where either is `∞` or `-∞`, or `None` for `∞ / ∞` and the like.
+fn divide_infinite(n: &Expr, d: &Expr) -> Option<Expr> {
+    match infinity_sign(d) {
+        Some(_) => (!contains_infinity(n)).then(|| Expr::integer(BigInt::zero())),
+        None => multiply_infinite(n, d),
     }
+}
 
-    let i = a.trailing_zeros(); a >>= i;
-    let j = b.trailing_zeros(); b >>= j;
-    let k = i.min(j);
+/// `b ^ e` where either is `∞` or `-∞`, or `None` for `1 ^ ∞`, `∞ ^ 0` and the like.
+fn raise_infinite(b: &Expr, e: &Expr) -> Option<Expr> {
+    if let Some(negative) = infinity_sign(e) {
+        let b = match infinity_sign(b) {
+            Some(negative) => if negative { f64::NEG_INFINITY } else { f64::INFINITY },
+            None => Number::from_expr(b)?.to_f64(),
+        };
+        // `b ^ -∞` is `(1 / b) ^ ∞`.
+        return match if negative { b.recip() } else { b } {
+            b if b > 1.0 => Some(Expr::Infinity),
+            b if b.abs() < 1.0 => Some(Expr::integer(BigInt::zero())),
+            _ => None,
+        };
+    }
 
-    loop {
-        if a > b {
-            std::mem::swap(&mut a, &mut b);
+    let negative = infinity_sign(b)?;
+    match e {
+        Expr::Integer(k) if k.is_positive() => Some(infinity(negative && k.is_odd())),
+        e => match Number::from_expr(e)? {
+            e if e.is_negative() => Some(Expr::integer(BigInt::zero())),
+            e if !e.is_zero() && !negative => Some(Expr::Infinity),
+            _ => None,
+        },
+    }
+}
+
+/// The numerator and denominator of a fraction, or of the negation of one with the sign moved into
+/// the numerator.
+fn fraction_parts(expr: &Expr) -> Option<(Interned, Interned)> {
+    match expr {
+        Expr::Ratio { numerator, denominator } => Some((numerator.clone(), denominator.clone())),
+        Expr::Negation(x) => match &**x {
+            Expr::Ratio { numerator, denominator } => Some((Expr::negation(numerator.clone()).simplify().boxed(), denominator.clone())),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Whether two fractions are added over a common denominator rather than kept as separate terms, as
+/// numeric denominators are left to like-term collection and series keep their order terms apart.
+fn can_add_fractions((n1, d1): &(Interned, Interned), (n2, d2): &(Interned, Interned)) -> bool {
+    (!d1.is_numeric() || !d2.is_numeric()) && !has_order_term(n1) && !has_order_term(n2)
+}
+
+/// `n1 / d1 + n2 / d2` as a single fraction.
+fn add_fractions((n1, d1): (Interned, Interned), (n2, d2): (Interned, Interned)) -> Expr {
+    if d1 == d2 {
+        return Expr::ratio(Expr::sum(n1, n2).simplify().boxed(), d1).simplify();
+    }
+    let d = Expr::product(d1.clone(), d2.clone()).simplify();
+    let n = Expr::sum(Expr::product(n1, d2).simplify().boxed(), Expr::product(n2, d1).simplify().boxed()).simplify();
+    Expr::ratio(n.boxed(), d.boxed()).simplify()
+}
+
+fn has_order_term(expr: &Expr) -> bool {
+    match expr {
+        Expr::Order { .. } => true,
+        Expr::Sum { left, right } => has_order_term(left) || has_order_term(right),
+        Expr::Negation(x) => has_order_term(x),
+        _ => false,
+    }
+}
+
+fn flatten_sum(expr: Expr, negate: bool, terms: &mut Vec<(Expr, bool)>) {
+    match expr {
+        Expr::Sum { left, right } => {
+            flatten_sum(left.into_inner(), negate, terms);
+            flatten_sum(right.into_inner(), negate, terms);
+        },
+        Expr::Negation(x) if matches!(*x, Expr::Sum { .. }) => flatten_sum(x.into_inner(), !negate, terms),
+        other => terms.push((other, negate)),
+    }
+}
+
+/// Combines like terms of a sum of already simplified terms, keeping them in order of first appearance.
+///
+/// Order terms absorb every term of at least their degree, and are kept at the end.
+fn collect_sum(exprs: Vec<Expr>) -> Expr {
+    let mut terms = Vec::new();
+    for expr in exprs {
+        flatten_sum(expr, false, &mut terms);
+    }
+
+    let mut orders: Vec<(Expr, Expr)> = Vec::new();
+    let mut groups: Vec<Monomial> = Vec::new();
+    for (term, negate) in terms {
+        if let Expr::Order { base, exp } = term {
+            match orders.iter_mut().find(|(b, _)| *b == *base) {
+                Some((_, e)) => if let (Expr::Integer(old), Expr::Integer(new)) = (&*e, &*exp) {
+                    if new < old {
+                        *e = exp.into_inner();
+                    }
+                },
+                None => orders.push((base.into_inner(), exp.into_inner())),
+            }
+            continue;
         }
 
-        b -= a;
+        let mut monomial = Monomial::of(term);
+        if negate {
+            monomial.coef = -monomial.coef;
+        }
+
+        match groups.iter_mut().find(|g| g.same_powers(&monomial)) {
+            Some(g) => g.coef = g.coef.clone() + monomial.coef,
+            None => groups.push(monomial),
+        }
+    }
+
+    let absorbed = |m: &Monomial| orders.iter().any(|(base, exp)| match (m.degree_in(base), exp) {
+        (Some(d), Expr::Integer(n)) => d >= *n,
+        _ => false,
+    });
+
+    let kept: Vec<Monomial> = groups.into_iter().filter(|m| !m.coef.is_zero() && !absorbed(m)).collect();
+
+    kept.into_iter()
+        .map(Monomial::into_expr)
+        .chain(orders.into_iter().map(|(base, exp)| Expr::order(base.boxed(), exp.boxed())))
+        .reduce(|acc, x| Expr::sum(acc.boxed(), x.boxed()))
+        .unwrap_or(Expr::integer(BigInt::zero()))
+}
+
+/// Multiplies two simplified expressions where at least one contains an order term, distributing over
+/// sums so the order term can absorb what it covers.
+fn multiply_series(l: Expr, r: Expr) -> Expr {
+    let mut ls = Vec::new();
+    let mut rs = Vec::new();
+    flatten_sum(l, false, &mut ls);
+    flatten_sum(r, false, &mut rs);
 
-        if b == 0 {
-            return a << k;
+    let mut products = Vec::new();
+    for (a, na) in ls.iter() {
+        for (b, nb) in rs.iter() {
+            let product = multiply_term(a.clone(), b.clone());
+            products.push(if na ^ nb { Expr::negation(product.boxed()).simplify() } else { product });
         }
+    }
+
+    collect_sum(products)
+}
+
+fn multiply_term(a: Expr, b: Expr) -> Expr {
+    match (a, b) {
+        (Expr::Order { base: b1, exp: e1 }, Expr::Order { base: b2, exp: e2 }) if b1 == b2 =>
+            Expr::order(b1, Expr::sum(e1, e2).simplify().boxed()),
+        (Expr::Order { base, exp }, other) | (other, Expr::Order { base, exp }) => match Monomial::of(other.clone()).degree_in(&base) {
+            Some(d) => Expr::order(base, Expr::sum(exp, Expr::integer(d).boxed()).simplify().boxed()),
+            None => Expr::product(other.boxed(), Expr::order(base, exp).boxed()),
+        },
+        (a, b) => Expr::product(a.boxed(), b.boxed()).simplify(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::eval;
+
+    #[test]
+    fn like_terms_collect() {
+        assert_eq!(eval("x + 2x - 3x"), "0");
+        assert_eq!(eval("2a b + 3b a - a"), "(5ab + -a)");
+        assert_eq!(eval("x^2 x^3 / x"), "(x ^ 4)");
+        assert_eq!(eval("x/2 + x/3"), "(5x / 6)");
+    }
 
-        b >>= b.trailing_zeros();
+    #[test]
+    fn fractions_combine() {
+        assert_eq!(eval("1/x + 1/y"), "((y + x) / xy)");
+        assert_eq!(eval("1/(x+1) + 1/(x-1)"), "(2x / (x + 1)(x + -1))");
+        assert_eq!(eval("1/(x+1) - 1/(x-1)"), "-(2 / (x + 1)(x + -1))");
+        assert_eq!(eval("x/2 - 1/x"), "(((x ^ 2) + -2) / 2x)");
     }
 }
@@ -0,0 +1,83 @@
+use std::f64::consts::PI;
+
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::{One, Signed, ToPrimitive, Zero};
+
+
+/// Factorials of integers above this are left unevaluated instead of expanding into enormous literals.
+pub const MAX_EXACT_FACTORIAL: u64 = 10_000;
+
+pub fn factorial(n: u64) -> BigInt {
+    (2..=n).fold(BigInt::one(), |acc, i| acc * i)
+}
+
+pub fn double_factorial(n: u64) -> BigInt {
+    (1..=n).rev().step_by(2).fold(BigInt::one(), |acc, i| acc * i)
+}
+
+/// The binomial coefficient, extended to negative `n` through `C(n, k) = (-1)^k C(k - n - 1, k)`.
+///
+/// Returns `None` when `k` is too large to evaluate exactly.
+pub fn binomial(n: &BigInt, k: &BigInt) -> Option<BigInt> {
+    if k.is_negative() {
+        return Some(BigInt::zero());
+    }
+
+    if n.is_negative() {
+        let c = binomial(&(k - n - 1), k)?;
+        return Some(if (k % 2u32).is_zero() { c } else { -c });
+    }
+
+    if k > n {
+        return Some(BigInt::zero());
+    }
+
+    let k = k.min(&(n - k)).to_u64().filter(|k| *k <= MAX_EXACT_FACTORIAL)?;
+    Some((0..k).fold(BigInt::one(), |acc, i| acc * (n - i) / (i + 1)))
+}
+
+/// The Bernoulli numbers `B_0` through `B_n`, with `B_1 = -1/2`.
+pub fn bernoulli(n: usize) -> Vec<BigRational> {
+    let mut b: Vec<BigRational> = Vec::with_capacity(n + 1);
+    for m in 0..=n {
+        let sum = (0..m).fold(BigRational::zero(), |acc, k| {
+            acc + BigRational::from_integer(binomial(&BigInt::from(m + 1), &BigInt::from(k)).unwrap()) * &b[k]
+        });
+        b.push(if m == 0 { BigRational::one() } else { -sum / BigInt::from(m + 1) });
+    }
+    b
+}
+
+
+/// Coefficients `B_2k / (2k (2k - 1))` of Stirling's series for `ln Γ`.
+const STIRLING_COEFFICIENTS: [f64; 7] = [
+    1.0 / 12.0,
+    -1.0 / 360.0,
+    1.0 / 1260.0,
+    -1.0 / 1680.0,
+    1.0 / 1188.0,
+    -691.0 / 360360.0,
+    1.0 / 156.0,
+];
+
+pub fn gamma(x: f64) -> f64 {
+    if x < 0.5 {
+        PI / ((PI * x).sin() * gamma(1.0 - x))
+    } else {
+        ln_gamma(x).exp()
+    }
+}
+
+/// `ln Γ(x)` for `x >= 0.5`, shifting the argument up until Stirling's series converges quickly.
+pub fn ln_gamma(mut x: f64) -> f64 {
+    let mut shift = 1.0;
+    while x < 15.0 {
+        shift *= x;
+        x += 1.0;
+    }
+
+    let inv = x.recip();
+    let series = STIRLING_COEFFICIENTS.iter().rev().fold(0.0, |acc, c| acc * inv * inv + c) * inv;
+    (x - 0.5) * x.ln() - x + 0.5 * (2.0 * PI).ln() + series - shift.ln()
+}
@@ -0,0 +1,406 @@
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::{One, Signed, ToPrimitive, Zero};
+
+use super::{number::Number, polynomial, rational::{self, Poly}, special, Expr};
+
+
+/// Sums and products with more terms than this are not expanded term by term.
+const MAX_EXPLICIT_TERMS: u64 = 10_000;
+
+/// Gosper's algorithm gives up on shifts between the roots of the ratio of neighbouring terms
+/// larger than this.
+const MAX_DISPERSION: u64 = 1000;
+
+/// Gosper's algorithm gives up on polynomial solutions of a higher degree than this.
+const MAX_GOSPER_DEGREE: i64 = 100;
+
+fn int(n: i64) -> Expr {
+    Expr::integer(BigInt::from(n))
+}
+
+fn add(a: Expr, b: Expr) -> Expr {
+    Expr::sum(a.boxed(), b.boxed())
+}
+
+fn sub(a: Expr, b: Expr) -> Expr {
+    Expr::difference(a.boxed(), b.boxed())
+}
+
+fn mul(a: Expr, b: Expr) -> Expr {
+    Expr::product(a.boxed(), b.boxed())
+}
+
+fn div(a: Expr, b: Expr) -> Expr {
+    Expr::ratio(a.boxed(), b.boxed())
+}
+
+fn pow(a: Expr, b: Expr) -> Expr {
+    Expr::power(a.boxed(), b.boxed())
+}
+
+/// `body` with `var` replaced by `value`, simplified.
+fn at(body: &Expr, var: &str, value: &Expr) -> Expr {
+    body.clone().substitute(var, value).simplify()
+}
+
+/// `body` with `var` replaced by `var + 1`, simplified.
+fn shifted(body: &Expr, var: &str) -> Expr {
+    at(body, var, &add(Expr::variable(var.to_string()), int(1)))
+}
+
+/// Whether `expr` is zero, reading it as a rational function of `var` if it is one, since
+/// simplifying does not bring `1 / (i + 1) - 1 / (1 + i)` to zero.
+fn is_zero(expr: &Expr, var: &str) -> bool {
+    let expr = expr.clone().simplify();
+    expr == int(0) || rational::from_expr(&expr, var).is_some_and(|(n, _)| n.is_empty())
+}
+
+/// The bounds, if they are integers close enough together to expand term by term.
+fn explicit_range(from: &Expr, to: &Expr) -> Option<(BigInt, BigInt)> {
+    let (a, b) = (from.as_integer()?, to.as_integer()?);
+    let count = (b - a + 1u32).to_i64()?;
+    (count <= MAX_EXPLICIT_TERMS as i64).then(|| (a.clone(), b.clone()))
+}
+
+fn count(from: &Expr, to: &Expr) -> Expr {
+    add(sub(to.clone(), from.clone()), int(1))
+}
+
+fn additive_terms(expr: &Expr, negate: bool, terms: &mut Vec<Expr>) {
+    match expr {
+        Expr::Sum { left, right } => {
+            additive_terms(left, negate, terms);
+            additive_terms(right, negate, terms);
+        },
+        Expr::Difference { left, right } => {
+            additive_terms(left, negate, terms);
+            additive_terms(right, !negate, terms);
+        },
+        Expr::Negation(node) => additive_terms(node, !negate, terms),
+        other if negate => terms.push(Expr::negation(other.clone().boxed())),
+        other => terms.push(other.clone()),
+    }
+}
+
+fn factors(expr: &Expr, factors_out: &mut Vec<Expr>) {
+    match expr {
+        Expr::Product { left, right } => {
+            factors(left, factors_out);
+            factors(right, factors_out);
+        },
+        other => factors_out.push(other.clone()),
+    }
+}
+
+/// The sum of `body` for integer `var` from `from` to `to`, in closed form when one is found.
+pub fn sum(var: &str, from: Expr, to: Expr, body: Expr) -> Expr {
+    if let Some((a, b)) = explicit_range(&from, &to) {
+        let mut total: Option<Expr> = None;
+        let mut i = a;
+        while i <= b {
+            let term = at(&body, var, &Expr::integer(i.clone()));
+            total = Some(match total {
+                Some(total) => add(total, term).simplify(),
+                None => term,
+            });
+            i += 1;
+        }
+        return total.unwrap_or(int(0));
+    }
+
+    match sum_closed_form(var, &from, &to, &body) {
+        Some(closed) => closed.simplify(),
+        None => Expr::indexedsum(var.to_string(), from.boxed(), to.boxed(), body.boxed()),
+    }
+}
+
+/// Finds sums of constants, polynomials, telescoping differences, terms with a constant ratio
+/// between neighbours and other terms Gosper's algorithm sums, along with sums of these.
+fn sum_closed_form(var: &str, from: &Expr, to: &Expr, body: &Expr) -> Option<Expr> {
+    if !body.contains_var(var) {
+        return Some(mul(count(from, to), body.clone()));
+    }
+
+    if let Some(coeffs) = polynomial::coefficients(body, var) {
+        return Some(faulhaber(&coeffs, from, to));
+    }
+
+    let mut terms = Vec::new();
+    additive_terms(body, false, &mut terms);
+
+    // Fractions like `1 / (i (i + 1))` only telescope once split into `1 / i - 1 / (i + 1)`.
+    let parts = match terms.len() {
+        1 => rational::partial_fractions(body, var).unwrap_or_else(|| terms.clone()),
+        _ => terms.clone(),
+    };
+    if let [p, q] = parts.as_slice() {
+        for (p, q) in [(p, q), (q, p)] {
+            // Σ f(i) - f(i + 1) = f(a) - f(b + 1), where `f` is `q` and `p` is `-f(i + 1)`
+            if is_zero(&add(p.clone(), shifted(q, var)), var) {
+                return Some(sub(at(q, var, from), at(q, var, &add(to.clone(), int(1)))));
+            }
+        }
+    }
+
+    let ratio = div(shifted(body, var), body.clone()).simplify();
+    if !ratio.contains_var(var) && ratio != int(1) {
+        // Σ c r^i = c r^a (1 - r^(b - a + 1)) / (1 - r)
+        return Some(mul(
+            at(body, var, from),
+            div(sub(int(1), pow(ratio.clone(), count(from, to))), sub(int(1), ratio)),
+        ));
+    }
+
+    if let Some(z) = gosper(var, body, &ratio) {
+        return Some(sub(at(&z, var, &add(to.clone(), int(1))), at(&z, var, from)));
+    }
+
+    if terms.len() > 1 {
+        return terms.iter()
+            .map(|term| sum_closed_form(var, from, to, term))
+            .reduce(|acc, x| Some(add(acc?, x?)))?;
+    }
+
+    None
+}
+
+/// Finds `z` with `z(i + 1) - z(i) = t(i)` by Gosper's algorithm, for a term `t` whose `ratio` of
+/// neighbours `t(i + 1) / t(i)` is a rational function of `i`.
+///
+/// The ratio is written as `a(i) / b(i) · c(i + 1) / c(i)`, with no root of `a` an integer above a
+/// root of `b`. Then `z = b(i - 1) x(i) / c(i) · t(i)` for a polynomial `x` solving
+/// `a(i) x(i + 1) - b(i - 1) x(i) = c(i)`, if there is one.
+fn gosper(var: &str, t: &Expr, ratio: &Expr) -> Option<Expr> {
+    let (mut a, mut b) = rational::from_expr(ratio, var)?;
+    if a.is_empty() {
+        return None;
+    }
+    let mut c = rational::constant(BigRational::one());
+
+    // Roots of `a` and `b` are bounded by one more than the largest ratio of a coefficient to the
+    // leading one, so no shift between them is larger than the sum of the bounds.
+    let bound = |p: &Poly| p.iter().map(|x| (x / p.last().unwrap()).abs()).max().unwrap() + BigRational::one();
+    let max_shift = (bound(&a) + bound(&b)).floor().to_integer().to_u64().filter(|h| *h <= MAX_DISPERSION)?;
+    for h in 1..=max_shift {
+        let h = BigRational::from_integer(BigInt::from(h));
+        let g = rational::gcd(&a, &rational::shift(&b, &h));
+        if g.len() <= 1 {
+            continue;
+        }
+        a = rational::div_rem(&a, &g).0;
+        b = rational::div_rem(&b, &rational::shift(&g, &-&h)).0;
+        let mut j = BigRational::one();
+        while j <= h {
+            c = rational::mul(&c, &rational::shift(&g, &-&j));
+            j += BigRational::one();
+        }
+    }
+
+    let b1 = rational::shift(&b, &-BigRational::one());
+    let x = gosper_polynomial(&a, &b1, &c)?;
+    let z = mul(div(rational::to_expr(&rational::mul(&b1, &x), var), rational::to_expr(&c, var)), t.clone()).simplify();
+    // Rational antidifferences come out tidier as a single reduced fraction.
+    Some(match rational::from_expr(&z, var) {
+        Some((n, d)) => div(rational::to_expr(&n, var), rational::to_expr(&d, var)).simplify(),
+        None => z,
+    })
+}
+
+/// A polynomial `x` with `a(i) x(i + 1) - b(i) x(i) = c(i)`, if there is one.
+fn gosper_polynomial(a: &Poly, b: &Poly, c: &Poly) -> Option<Poly> {
+    let degree = |p: &Poly| rational::degree(p).map(|d| d as i64);
+    let (plus, minus) = (rational::add(a, b), rational::sub(a, b));
+    let degree_c = degree(c)?;
+
+    // The degree of `x` makes the leading terms of both sides agree. When those of `a` and `b`
+    // cancel, the terms below them can cancel too for one more degree, found from their coefficients.
+    let max_degree = if degree(&minus) >= degree(&plus) {
+        degree_c - degree(&minus)?
+    } else {
+        let d = degree(a)?;
+        let below = |p: &Poly| if d > 0 { rational::coeff(p, d as usize - 1) } else { BigRational::zero() };
+        let e = (below(b) - below(a)) / a.last()?;
+        let e = e.is_integer().then(|| e.to_integer().to_i64()).flatten().unwrap_or(-1);
+        (degree_c - d + 1).max(e)
+    };
+    if !(0..=MAX_GOSPER_DEGREE).contains(&max_degree) {
+        return None;
+    }
+
+    // Column `j` holds the coefficients of `a(i) (i + 1)^j - b(i) i^j`, the image of `x = i^j`.
+    let columns: Vec<Poly> = (0..=max_degree as usize)
+        .map(|j| {
+            let mut power = vec![BigRational::zero(); j];
+            power.push(BigRational::one());
+            rational::sub(&rational::mul(a, &rational::shift(&power, &BigRational::one())), &rational::mul(b, &power))
+        })
+        .collect();
+    let rows = columns.iter().map(Vec::len).chain([c.len()]).max().unwrap();
+    let system: Vec<Vec<BigRational>> = (0..rows)
+        .map(|m| columns.iter().map(|col| rational::coeff(col, m)).chain([rational::coeff(c, m)]).collect())
+        .collect();
+    solve(system, columns.len())
+}
+
+/// A solution of the linear system with the augmented `rows` in `n` unknowns, taking free unknowns
+/// as zero, or `None` if it is inconsistent.
+fn solve(mut rows: Vec<Vec<BigRational>>, n: usize) -> Option<Vec<BigRational>> {
+    let mut pivots = Vec::new();
+    for col in 0..n {
+        let Some(p) = (pivots.len()..rows.len()).find(|&r| !rows[r][col].is_zero()) else { continue };
+        rows.swap(pivots.len(), p);
+        let pivot = rows[pivots.len()].clone();
+        for (r, row) in rows.iter_mut().enumerate() {
+            if r != pivots.len() && !row[col].is_zero() {
+                let f = &row[col] / &pivot[col];
+                for (x, y) in row.iter_mut().zip(&pivot) {
+                    *x -= &f * y;
+                }
+            }
+        }
+        pivots.push(col);
+    }
+
+    if rows[pivots.len()..].iter().any(|row| !row[n].is_zero()) {
+        return None;
+    }
+    let mut x = vec![BigRational::zero(); n];
+    for (r, &col) in pivots.iter().enumerate() {
+        x[col] = &rows[r][n] / &rows[r][col];
+    }
+    Some(x)
+}
+
+/// Sums a polynomial with Faulhaber's formula `Σ_{i=1}^{n} i^p = 1/(p + 1) Σ_j C(p + 1, j) B_j n^(p + 1 - j)`,
+/// taking `B_1 = 1/2`.
+fn faulhaber(coeffs: &[Expr], from: &Expr, to: &Expr) -> Expr {
+    let bernoulli = special::bernoulli(coeffs.len());
+    let power_sum = |p: usize, n: Expr| -> Expr {
+        (0..=p)
+            .map(|j| {
+                let b = if j == 1 { -bernoulli[1].clone() } else { bernoulli[j].clone() };
+                let c = BigRational::from_integer(special::binomial(&BigInt::from(p + 1), &BigInt::from(j)).unwrap()) * b / BigInt::from(p + 1);
+                mul(Number::Rational(c).into_expr(), pow(n.clone(), int((p + 1 - j) as i64)))
+            })
+            .reduce(add)
+            .unwrap()
+    };
+
+    coeffs.iter()
+        .enumerate()
+        .map(|(p, c)| mul(c.clone(), sub(power_sum(p, to.clone()), power_sum(p, sub(from.clone(), int(1))))))
+        .reduce(add)
+        .unwrap_or(int(0))
+}
+
+/// The product of `body` for integer `var` from `from` to `to`, in closed form when one is found.
+pub fn product(var: &str, from: Expr, to: Expr, body: Expr) -> Expr {
+    if let Some((a, b)) = explicit_range(&from, &to) {
+        let mut total: Option<Expr> = None;
+        let mut i = a;
+        while i <= b {
+            let term = at(&body, var, &Expr::integer(i.clone()));
+            total = Some(match total {
+                Some(total) => mul(total, term).simplify(),
+                None => term,
+            });
+            i += 1;
+        }
+        return total.unwrap_or(int(1));
+    }
+
+    match product_closed_form(var, &from, &to, &body) {
+        Some(closed) => closed.simplify(),
+        None => Expr::indexedproduct(var.to_string(), from.boxed(), to.boxed(), body.boxed()),
+    }
+}
+
+/// Finds products of constants, linear factors, powers with a summable exponent and telescoping
+/// ratios, along with products of these.
+fn product_closed_form(var: &str, from: &Expr, to: &Expr, body: &Expr) -> Option<Expr> {
+    if !body.contains_var(var) {
+        return Some(pow(body.clone(), count(from, to)));
+    }
+
+    if let Some(coeffs) = polynomial::coefficients(body, var) {
+        if let [c, m] = coeffs.as_slice() {
+            // Π (m i + c) = m^(b - a + 1) Π (i + s), with s = c / m
+            let s = div(c.clone(), m.clone()).simplify();
+            let rising = if Number::from_expr(&s).is_some_and(|n| matches!(n, Number::Rational(r) if r.is_integer())) {
+                div(
+                    Expr::factorial(add(to.clone(), s.clone()).boxed()),
+                    Expr::factorial(sub(add(from.clone(), s), int(1)).boxed()),
+                )
+            } else {
+                div(
+                    Expr::gamma(add(add(to.clone(), s.clone()), int(1)).boxed()),
+                    Expr::gamma(add(from.clone(), s).boxed()),
+                )
+            };
+            return Some(mul(pow(m.clone(), count(from, to)), rising));
+        }
+    }
+
+    if let Expr::Power { base, exp } = body {
+        if !base.contains_var(var) {
+            return Some(pow(base.clone().into_inner(), sum_closed_form(var, from, to, exp)?));
+        }
+    }
+
+    if let Expr::Ratio { numerator, denominator } = body {
+        // Π f(i + 1) / f(i) = f(b + 1) / f(a)
+        if **numerator == shifted(denominator, var) {
+            return Some(div(at(denominator, var, &add(to.clone(), int(1))), at(denominator, var, from)));
+        }
+        // Π f(i) / f(i + 1) = f(a) / f(b + 1)
+        if **denominator == shifted(numerator, var) {
+            return Some(div(at(numerator, var, from), at(numerator, var, &add(to.clone(), int(1)))));
+        }
+    }
+
+    let mut parts = Vec::new();
+    factors(body, &mut parts);
+    if parts.len() > 1 {
+        return parts.iter()
+            .map(|factor| product_closed_form(var, from, to, factor))
+            .reduce(|acc, x| Some(mul(acc?, x?)))?;
+    }
+
+    if let Expr::Ratio { numerator, denominator } = body {
+        return Some(div(
+            product_closed_form(var, from, to, numerator)?,
+            product_closed_form(var, from, to, denominator)?,
+        ));
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::eval;
+
+    #[test]
+    fn finite_ranges() {
+        assert_eq!(eval("sum:i:1:10[i]"), "55");
+        assert_eq!(eval("prod:i:1:5[2]"), "32");
+    }
+
+    #[test]
+    fn polynomial_sums() {
+        assert_eq!(eval("sum:k:1:n[k^2]"), "((((n ^ 3) / 3) + ((n ^ 2) / 2)) + (n / 6))");
+        assert_eq!(eval("prod:k:1:n[k]"), "n!");
+    }
+
+    #[test]
+    fn telescoping_and_hypergeometric_sums() {
+        assert_eq!(eval("sum:i:1:n[1/(i(i+1))]"), "(1 + -(1 / (n + 1)))");
+        assert_eq!(eval("sum:i:1:n[i*2^i]"), "((-1 + n)(2 ^ (n + 1)) + 2)");
+    }
+
+    #[test]
+    fn infinite_sums() {
+        assert_eq!(eval("sum:i:1:inf[1/2^i]"), "1");
+    }
+}
@@ -0,0 +1,215 @@
+use std::{borrow::Cow, cell::RefCell};
+
+use super::Expr;
+
+
+/// Stands in for the rewritten part of an expression while the rest of it is displayed.
+const MARKER: &str = "\u{fffc}";
+
+/// A traced call that rewrote `before` into `after`, along with the traced calls it made on the way.
+struct Frame {
+    name: Cow<'static, str>,
+    /// Whether `name` was given by [`step`] rather than being the default of the call.
+    named: bool,
+    before: Expr,
+    after: Expr,
+    inner: Vec<Frame>,
+}
+
+impl Frame {
+    fn new(name: &'static str, before: Expr) -> Self {
+        Self { name: Cow::Borrowed(name), named: false, after: before.clone(), before, inner: Vec::new() }
+    }
+
+    /// The name of the step, falling back to that of the last named call inside it.
+    fn name(&self) -> String {
+        match self.named {
+            true => self.name.to_string(),
+            false => self.inner.iter().rev().find_map(|f| f.named.then(|| f.name.to_string())).unwrap_or(self.name.to_string()),
+        }
+    }
+
+    /// Finds an unused call inside this one that started from `expr`, skipping the calls inside
+    /// those already used.
+    fn find<'a>(&'a self, expr: &Expr, used: &mut Vec<*const Frame>) -> Option<&'a Frame> {
+        for frame in &self.inner {
+            if used.contains(&(frame as *const Frame)) {
+                continue;
+            }
+            if frame.before == *expr {
+                used.push(frame);
+                return Some(frame);
+            }
+            if let Some(found) = frame.find(expr, used) {
+                return Some(found);
+            }
+        }
+        None
+    }
+
+    /// Lays out the steps of the call, those rewriting each child first.
+    fn replay(&self, path: Vec<usize>, steps: &mut Vec<Step>) {
+        let mut used = Vec::new();
+        let mut current = self.before.clone();
+        for (i, child) in self.before.children().into_iter().enumerate() {
+            let Some(inner) = self.find(child, &mut used) else { continue };
+            inner.replay([path.clone(), vec![i]].concat(), steps);
+            current = replace_child(current, i, inner.after.clone());
+        }
+
+        if current != self.after {
+            steps.push(Step { name: self.name(), path, before: current, after: self.after.clone() });
+        }
+    }
+}
+
+/// One step of a derivation, rewriting the part of the whole expression found by following `path`.
+pub struct Step {
+    pub name: String,
+    /// The positions of the children leading to the rewritten part, as in [`Expr::children`].
+    pub path: Vec<usize>,
+    pub before: Expr,
+    pub after: Expr,
+}
+
+impl Step {
+    /// Applies the step to the whole expression.
+    pub fn apply(&self, expr: Expr) -> Expr {
+        replace_at(expr, &self.path, self.after.clone())
+    }
+}
+
+thread_local! {
+    /// The traced calls in progress, innermost last, or `None` when no derivation is being recorded.
+    static STACK: RefCell<Option<Vec<Frame>>> = const { RefCell::new(None) };
+}
+
+fn replace_child(expr: Expr, index: usize, mut value: Expr) -> Expr {
+    let mut i = 0;
+    expr.map_children(|child| {
+        i += 1;
+        if i - 1 == index { std::mem::replace(&mut value, Expr::Infinity) } else { child }
+    })
+}
+
+fn replace_at(expr: Expr, path: &[usize], value: Expr) -> Expr {
+    match path.split_first() {
+        None => value,
+        Some((&index, rest)) => {
+            let mut i = 0;
+            let mut value = Some(value);
+            expr.map_children(|child| {
+                i += 1;
+                if i - 1 == index { replace_at(child, rest, value.take().unwrap()) } else { child }
+            })
+        },
+    }
+}
+
+/// Whether a derivation is being recorded.
+pub fn is_recording() -> bool {
+    STACK.with(|s| s.borrow().is_some())
+}
+
+/// Runs `f` on `expr`, returning its result along with the steps that led there.
+pub fn derive(expr: Expr, f: impl FnOnce(Expr) -> Expr) -> (Expr, Vec<Step>) {
+    let outer = STACK.with(|s| s.borrow_mut().replace(vec![Frame::new("", expr.clone())]));
+    let result = f(expr.clone());
+    let root = STACK.with(|s| std::mem::replace(&mut *s.borrow_mut(), outer)).and_then(|mut stack| stack.pop()).unwrap();
+
+    // Calls on expressions other than the current one were made in passing, and are already
+    // accounted for by the steps around them.
+    let mut steps = Vec::new();
+    let mut current = expr;
+    for frame in &root.inner {
+        if frame.before != current {
+            continue;
+        }
+        frame.replay(Vec::new(), &mut steps);
+        current = frame.after.clone();
+    }
+
+    (result, steps)
+}
+
+/// Applies `rewrite` to `expr`, recording the call under `name` unless [`step`] names it, when a
+/// derivation is being recorded.
+pub fn scope(expr: Expr, name: &'static str, rewrite: impl FnOnce(Expr) -> Expr) -> Expr {
+    let tracing = STACK.with(|s| match &mut *s.borrow_mut() {
+        Some(stack) => {
+            stack.push(Frame::new(name, expr.clone()));
+            true
+        },
+        None => false,
+    });
+    if !tracing {
+        return rewrite(expr);
+    }
+
+    let after = rewrite(expr);
+    STACK.with(|s| {
+        let mut s = s.borrow_mut();
+        let stack = s.as_mut().unwrap();
+        let mut frame = stack.pop().unwrap();
+        if frame.before != after {
+            frame.after = after.clone();
+            stack.last_mut().unwrap().inner.push(frame);
+        }
+    });
+    after
+}
+
+/// Names the rewrite made by the innermost traced call, passing `expr` through.
+pub fn step(name: impl Into<Cow<'static, str>>, expr: Expr) -> Expr {
+    STACK.with(|s| if let Some(frame) = s.borrow_mut().as_mut().and_then(|stack| stack.last_mut()) {
+        frame.name = name.into();
+        frame.named = true;
+    });
+    expr
+}
+
+/// Displays `expr` split around the part found by following `path`, as the text before it, the part
+/// itself and the text after it.
+pub fn split_display(expr: &Expr, path: &[usize]) -> Option<(String, String, String)> {
+    let part = path.iter().try_fold(expr, |e, &i| e.children().get(i).copied())?;
+    let marked = replace_at(expr.clone(), path, Expr::variable(MARKER.to_string())).to_string();
+    let (before, after) = marked.split_once(MARKER)?;
+    let whole = expr.to_string();
+    let part = part.to_string();
+
+    (whole.len() == before.len() + part.len() + after.len() && whole.starts_with(before) && whole.ends_with(after))
+        .then(|| (before.to_string(), part, after.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{derive, split_display};
+    use crate::{session::Session, testing::parse};
+
+    /// The names of the steps `session` takes to simplify `input`, checking that they replay to its result.
+    fn steps(input: &str) -> Vec<String> {
+        let session = Session::new();
+        let expr = parse(input);
+        let (result, steps) = derive(expr.clone(), |expr| session.simplify(expr));
+        let replayed = steps.iter().fold(expr, |expr, step| step.apply(expr));
+        assert!(replayed == result, "{} replays to {} rather than {}", input, replayed, result);
+        steps.into_iter().map(|step| step.name).collect()
+    }
+
+    #[test]
+    fn steps_replay_to_the_result() {
+        assert_eq!(steps("2 + 3*4"), ["multiply numbers", "add numbers"]);
+        assert!(steps("x").is_empty());
+    }
+
+    #[test]
+    fn refine_steps_are_named() {
+        assert_eq!(steps("sqrt[x^2]"), ["ᵏ√(b^(k j)) = |b|^j for even k"]);
+    }
+
+    #[test]
+    fn parts_are_split_out_of_the_display() {
+        let expr = parse("2 + sin[x]");
+        assert_eq!(split_display(&expr, &[1]), Some(("(2 + ".to_string(), "sin[x]".to_string(), ")".to_string())));
+    }
+}
@@ -48,6 +48,9 @@ impl<'a> Lexer<'a> {
                 break;
             }
             let string = &self.source[self.position.index..];
+            if string.starts_with('"') && !string[1..].contains('"') {
+                return err!(Syntax, "unterminated string", Span::new_single(self.position));
+            }
             
             let match_set = self.regex_set.matches(string);
             let Some((start, len, i)) = match_set
@@ -103,3 +106,36 @@ impl<'a> Lexer<'a> {
         Ok(tokens)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{token::TokenType, Lexer};
+    use crate::prelude::*;
+
+    fn error(input: &str) -> (String, Span) {
+        match Lexer::new(input).tokenize() {
+            Ok(_) => panic!("{} tokenized", input),
+            Err(Error::UnknownCharacter(details, span) | Error::Syntax(details, span) | Error::InvalidCall(details, span) | Error::Domain(details, span)) => (details, span),
+        }
+    }
+
+    #[test]
+    fn bangs_are_read_greedily() {
+        let Ok(tokens) = Lexer::new("5!!!").tokenize() else { panic!("cannot tokenize") };
+        let kinds: Vec<&TokenType> = tokens.iter().map(|token| &token.ty).collect();
+        assert!(matches!(kinds[..], [TokenType::Integer(_), TokenType::DoubleBang, TokenType::Bang, TokenType::Eof]));
+    }
+
+    #[test]
+    fn strings_must_be_closed() {
+        let (details, span) = error("plot:x:0:1:\"out.svg[x]");
+        assert_eq!(details, "unterminated string");
+        // It points at the opening quote.
+        assert_eq!(span.pos_1.index, 11);
+    }
+
+    #[test]
+    fn unknown_characters() {
+        assert_eq!(error("1 # 2").0, "'#' is not a valid character");
+    }
+}
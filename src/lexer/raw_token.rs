@@ -3,8 +3,10 @@ use fancy_regex::Regex;
 
 #[derive(Debug, Clone, Copy, IterEnum)]
 pub enum RawTokenType {
+    Integer,
     Decimal,
     Identifier,
+    Text,
     Add,
     Sub,
     Mul,
@@ -16,11 +18,14 @@ pub enum RawTokenType {
     LessThanEq,
     Equals,
     NotEquals,
+    Arrow,
     Pipe,
     Bang,
+    DoubleBang,
     Comma,
     Semicolon,
     Colon,
+    Assign,
     Tick,
     LBrace,
     RBrace,
@@ -36,8 +41,10 @@ pub enum RawTokenType {
 impl RawTokenType {
     pub fn regex(self) -> Option<Regex> {
         match self {
-            RawTokenType::Decimal => Some(Regex::new(r"([0-9]+\.?([0-9]+)?|\.[0-9]+)").unwrap()),
+            RawTokenType::Integer => Some(Regex::new(r"([0-9]+)").unwrap()),
+            RawTokenType::Decimal => Some(Regex::new(r"([0-9]+\.([0-9]+)?|\.[0-9]+)").unwrap()),
             RawTokenType::Identifier => Some(Regex::new(r"([a-zA-Z_][a-zA-Z0-9_]*)").unwrap()),
+            RawTokenType::Text => Some(Regex::new(r#""[^"]*""#).unwrap()),
             RawTokenType::Add => Some(Regex::new(r"\+").unwrap()),
             RawTokenType::Sub => Some(Regex::new(r"-").unwrap()),
             RawTokenType::Mul => Some(Regex::new(r"\*").unwrap()),
@@ -49,11 +56,14 @@ impl RawTokenType {
             RawTokenType::LessThanEq => Some(Regex::new(r"<=").unwrap()),
             RawTokenType::Equals => Some(Regex::new(r"=").unwrap()),
             RawTokenType::NotEquals => Some(Regex::new(r"!=").unwrap()),
+            RawTokenType::Arrow => Some(Regex::new(r"->").unwrap()),
             RawTokenType::Pipe => Some(Regex::new(r"\|").unwrap()),
             RawTokenType::Bang => Some(Regex::new(r"!").unwrap()),
+            RawTokenType::DoubleBang => Some(Regex::new(r"!!").unwrap()),
             RawTokenType::Comma => Some(Regex::new(r",").unwrap()),
             RawTokenType::Semicolon => Some(Regex::new(r";").unwrap()),
             RawTokenType::Colon => Some(Regex::new(r":").unwrap()),
+            RawTokenType::Assign => Some(Regex::new(r":=").unwrap()),
             RawTokenType::Tick => Some(Regex::new(r"'").unwrap()),
             RawTokenType::LBrace => Some(Regex::new(r"{").unwrap()),
             RawTokenType::RBrace => Some(Regex::new(r"}").unwrap()),
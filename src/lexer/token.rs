@@ -1,3 +1,4 @@
+use num_bigint::BigInt;
 use proc_macros::StringifyEnum;
 
 use crate::error::span::Span;
@@ -13,8 +14,10 @@ pub struct Token {
 impl Token {
     pub fn new(raw: RawTokenType, span: Span, text: &str) -> Self {
         let ty = match raw {
+            RawTokenType::Integer => TokenType::Integer(text.parse().unwrap()),
             RawTokenType::Decimal => TokenType::Decimal(text.parse().unwrap()),
             RawTokenType::Identifier => TokenType::Identifier(text.to_string()),
+            RawTokenType::Text => TokenType::Text(text[1..text.len() - 1].to_string()),
             _ => raw.into()
         };
 
@@ -34,8 +37,13 @@ impl std::fmt::Debug for Token {
 
 #[derive(Clone, StringifyEnum)]
 pub enum TokenType {
+    /// An integer literal, read exactly however long it is.
+    Integer(BigInt),
+    /// A literal with a decimal point.
     Decimal(f64),
     Identifier(String),
+    /// A quoted string, without its quotes.
+    Text(String),
 
     /// +
     Add,
@@ -60,17 +68,23 @@ pub enum TokenType {
     Equals,
     /// !=
     NotEquals,
+    /// ->
+    Arrow,
 
     /// |
     Pipe,
     /// !
     Bang,
+    /// !!
+    DoubleBang,
     /// ,
     Comma,
     /// ;
     Semicolon,
     /// :
     Colon,
+    /// :=
+    Assign,
     /// '
     Tick,
 
@@ -95,8 +109,10 @@ pub enum TokenType {
 impl std::fmt::Debug for TokenType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
+            TokenType::Integer(val) => write!(f, "Integer({})", val),
             TokenType::Decimal(val) => write!(f, "Decimal({})", val),
             TokenType::Identifier(val) => write!(f, "Ident({})", val),
+            TokenType::Text(val) => write!(f, "Text({:?})", val),
 
             no_val => write!(f, "{}", no_val.stringify_field())
         }
@@ -106,8 +122,10 @@ impl std::fmt::Debug for TokenType {
 impl std::fmt::Display for TokenType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
+            TokenType::Integer(val) => write!(f, "{}", val),
             TokenType::Decimal(val) => write!(f, "{}", val),
             TokenType::Identifier(val) => write!(f, "{}", val),
+            TokenType::Text(val) => write!(f, "{:?}", val),
 
             no_val => write!(f, "{}", no_val.stringify_pretty())
         }
@@ -117,8 +135,10 @@ impl std::fmt::Display for TokenType {
 impl PartialEq for TokenType {
     fn eq(&self, other: &Self) -> bool {
         matches!((self, other), 
+            (&Self::Integer(_), &Self::Integer(_)) |
             (&Self::Decimal(_), &Self::Decimal(_)) | 
             (&Self::Identifier(_), &Self::Identifier(_)) |
+            (&Self::Text(_), &Self::Text(_)) |
             (&Self::Add, &Self::Add) |
             (&Self::Sub, &Self::Sub) |
             (&Self::Mul, &Self::Mul) |
@@ -130,11 +150,14 @@ impl PartialEq for TokenType {
             (&Self::LessThanEq, &Self::LessThanEq) |
             (&Self::Equals, &Self::Equals) |
             (&Self::NotEquals, &Self::NotEquals) |
+            (&Self::Arrow, &Self::Arrow) |
             (&Self::Pipe, &Self::Pipe) |
             (&Self::Bang, &Self::Bang) |
+            (&Self::DoubleBang, &Self::DoubleBang) |
             (&Self::Comma, &Self::Comma) |
             (&Self::Semicolon, &Self::Semicolon) |
             (&Self::Colon, &Self::Colon) |
+            (&Self::Assign, &Self::Assign) |
             (&Self::Tick, &Self::Tick) |
             (&Self::LBrace, &Self::LBrace) |
             (&Self::RBrace, &Self::RBrace) |
@@ -152,8 +175,10 @@ impl PartialEq for TokenType {
 impl From<RawTokenType> for TokenType {
     fn from(value: RawTokenType) -> Self {
         match value {
+            RawTokenType::Integer => Self::Integer(BigInt::default()),
             RawTokenType::Decimal => Self::Decimal(0.0),
             RawTokenType::Identifier => Self::Identifier(String::new()),
+            RawTokenType::Text => Self::Text(String::new()),
             RawTokenType::Add => Self::Add,
             RawTokenType::Sub => Self::Sub,
             RawTokenType::Mul => Self::Mul,
@@ -165,11 +190,14 @@ impl From<RawTokenType> for TokenType {
             RawTokenType::LessThanEq => Self::LessThanEq,
             RawTokenType::Equals => Self::Equals,
             RawTokenType::NotEquals => Self::NotEquals,
+            RawTokenType::Arrow => Self::Arrow,
             RawTokenType::Pipe => Self::Pipe,
             RawTokenType::Bang => Self::Bang,
+            RawTokenType::DoubleBang => Self::DoubleBang,
             RawTokenType::Comma => Self::Comma,
             RawTokenType::Semicolon => Self::Semicolon,
             RawTokenType::Colon => Self::Colon,
+            RawTokenType::Assign => Self::Assign,
             RawTokenType::Tick => Self::Tick,
             RawTokenType::LBrace => Self::LBrace,
             RawTokenType::RBrace => Self::RBrace,
@@ -187,8 +215,10 @@ impl From<RawTokenType> for TokenType {
 impl PartialEq<RawTokenType> for TokenType {
     fn eq(&self, rhs: &RawTokenType) -> bool {
         matches!((self, rhs), 
+            (&Self::Integer(_), &RawTokenType::Integer) |
             (&Self::Decimal(_), &RawTokenType::Decimal) | 
             (&Self::Identifier(_), &RawTokenType::Identifier) |
+            (&Self::Text(_), &RawTokenType::Text) |
             (&Self::Add, &RawTokenType::Add) | 
             (&Self::Sub, &RawTokenType::Sub) | 
             (&Self::Mul, &RawTokenType::Mul) | 
@@ -200,11 +230,14 @@ impl PartialEq<RawTokenType> for TokenType {
             (&Self::LessThanEq, &RawTokenType::LessThanEq) | 
             (&Self::Equals, &RawTokenType::Equals) | 
             (&Self::NotEquals, &RawTokenType::NotEquals) | 
+            (&Self::Arrow, &RawTokenType::Arrow) | 
             (&Self::Pipe, &RawTokenType::Pipe) | 
             (&Self::Bang, &RawTokenType::Bang) | 
+            (&Self::DoubleBang, &RawTokenType::DoubleBang) | 
             (&Self::Comma, &RawTokenType::Comma) | 
             (&Self::Semicolon, &RawTokenType::Semicolon) | 
             (&Self::Colon, &RawTokenType::Colon) | 
+            (&Self::Assign, &RawTokenType::Assign) | 
             (&Self::Tick, &RawTokenType::Tick) | 
             (&Self::LBrace, &RawTokenType::LBrace) | 
             (&Self::RBrace, &RawTokenType::RBrace) | 
@@ -5,17 +5,31 @@ mod error;
 mod parser;
 mod strategies;
 mod expr;
+mod plot;
 mod prelude;
+mod session;
 mod utils;
+#[cfg(test)]
+mod testing;
 
-use expr::Expr;
-use lexer::token::Token;
+use std::time::Instant;
+
+use expr::{egraph::Cost, trace::{self, Step}, Expr};
+use lexer::token::{Token, TokenType};
 use parser::node::Node;
+use plot::Plot;
 use rustyline::{error::ReadlineError, history::DefaultHistory, Config, EditMode, Editor};
-use termion::color;
+use session::{Session, MAX_REWRITE_PASSES};
+use termion::{color, style};
 
 use crate::{lexer::Lexer, strategies::{print_runstrats, select_runstrats, RunStrategies}, parser::Parser};
 
+/// Points `:bench` evaluates an expression at.
+const BENCH_POINTS: usize = 1_000_000;
+
+/// Relative difference up to which `:bench` takes the compiled and tree-walking results to agree.
+const BENCH_TOLERANCE: f64 = 1e-9;
+
 fn tokenize(input: &str) -> Option<Vec<Token>> {
     let mut lexer = Lexer::new(input);
     match lexer.tokenize() {
@@ -29,21 +43,13 @@ fn tokenize(input: &str) -> Option<Vec<Token>> {
     }
 }
 
-fn parse(input: &str, tokens: &[Token]) -> Option<Node> {
+fn parse(tokens: &[Token]) -> Vec<prelude::Result<Node>> {
     let mut parser = Parser::new(tokens.to_vec());
-    match parser.parse() {
-        Ok(ast) => {
-            Some(ast)
-        },
-        Err(err) => {
-            err.print(input);
-            None
-        }
-    }
+    parser.parse()
 }
 
-fn to_expr(input: &str, ast: Node) -> Option<Expr> {
-    match Expr::convert(ast) {
+fn to_expr(input: &str, ast: Node, session: &Session) -> Option<Expr> {
+    match Expr::convert(ast, session) {
         Ok(expr) => {
             Some(expr)
         },
@@ -54,25 +60,260 @@ fn to_expr(input: &str, ast: Node) -> Option<Expr> {
     }
 }
 
-fn run(input: &str, opts: RunStrategies) {
+fn run(input: &str, opts: RunStrategies, session: &mut Session) {
     let Some(tokens) = tokenize(input) else { return };
-    let Some(ast) = parse(input, &tokens) else { return };
-    let Some(expr) = to_expr(input, ast.clone()) else { return };
-    match opts {
-        RunStrategies::Tokenize => {
+    if let RunStrategies::Tokenize = opts {
+        println!();
+        println!("{:?}", tokens);
+        println!();
+        return;
+    }
+
+    for stmt in parse(&tokens) {
+        let ast = match stmt {
+            Ok(ast) => ast,
+            Err(err) => {
+                err.print(input);
+                continue;
+            }
+        };
+        if let RunStrategies::ShowAST = opts {
             println!();
-            println!("{:?}", tokens);
+            println!("{}", ast);
+            continue;
+        }
+
+        if let Node::Assign { name, value, .. } = ast {
+            let Some(value) = to_expr(input, *value, session) else { continue };
+            let name = format!("{}", name.ty);
+            let (value, settled) = session.bind(name.clone(), value);
             println!();
+            print_labeled(&format!("{} := ", name), value);
+            println!();
+            if !settled {
+                unsettled_warning();
+            }
+            continue;
+        }
+
+        if let Node::Call { name, params, args, span } = &ast {
+            if matches!(&name.ty, TokenType::Identifier(name) if name == "assume") {
+                match session.assume(args.clone(), *span) {
+                    Ok(facts) => {
+                        println!();
+                        facts.iter().for_each(|fact| println!("assuming {}", fact));
+                        println!();
+                    },
+                    Err(err) => err.print(input),
+                }
+                continue;
+            }
+            if matches!(&name.ty, TokenType::Identifier(name) if name == "rule") {
+                match session.add_rule(args.clone(), *span) {
+                    Ok(rule) => {
+                        println!();
+                        println!("rule {}", rule);
+                        println!();
+                    },
+                    Err(err) => err.print(input),
+                }
+                continue;
+            }
+            if matches!(&name.ty, TokenType::Identifier(name) if name == "plot") {
+                match Plot::new(params, args, *span, session) {
+                    Ok(plot) => if let Some(file) = &plot.file {
+                        match plot.write_svg(file, *span) {
+                            Ok(()) => {
+                                println!();
+                                println!("wrote {}", file);
+                                println!();
+                            },
+                            Err(err) => err.print(input),
+                        }
+                    } else {
+                        let width = termion::terminal_size().map_or(plot::DEFAULT_WIDTH, |(width, _)| width as usize);
+                        println!();
+                        print!("{}", plot.render(width));
+                        println!();
+                    },
+                    Err(err) => err.print(input),
+                }
+                continue;
+            }
+        }
+
+        let Some(expr) = to_expr(input, ast, session) else { continue };
+        match opts {
+            RunStrategies::Tokenize | RunStrategies::ShowAST => unreachable!(),
+            RunStrategies::Simplify => {
+                let (result, settled) = session.simplify_settled(expr);
+                println!();
+                println!("{:#}", result);
+                println!();
+                if !settled {
+                    unsettled_warning();
+                }
+            },
+            RunStrategies::ShowSteps => {
+                let mut settled = true;
+                let (result, steps) = trace::derive(expr.clone(), |expr| {
+                    let (result, done) = session.simplify_settled(expr);
+                    settled = done;
+                    result
+                });
+                println!();
+                print_steps(&expr, &steps, &result);
+                println!();
+                if !settled {
+                    unsettled_warning();
+                }
+            }
+        }
+    }
+}
+
+/// Prints `expr` after `prefix`, lining up the rest of multi-line values like matrices under the first.
+fn print_labeled(prefix: &str, expr: &Expr) {
+    let indent = " ".repeat(prefix.chars().count());
+    for (i, line) in format!("{:#}", expr).lines().enumerate() {
+        println!("{}{}", if i == 0 { prefix } else { &indent }, line);
+    }
+}
+
+/// Prints the derivation of `result` from `expr` a line per step, highlighting the part each step rewrote.
+fn print_steps(expr: &Expr, steps: &[Step], result: &Expr) {
+    println!("  {}", expr);
+    let mut current = expr.clone();
+    for step in steps {
+        current = step.apply(current);
+        let line = match trace::split_display(&current, &step.path) {
+            Some((before, part, after)) => format!("{}{}{}{}{}{}", before, color::Fg(color::Yellow), style::Bold, part, style::Reset, after),
+            None => current.to_string(),
+        };
+        println!("= {}   {}{}{}", line, color::Fg(color::LightBlack), step.name, color::Fg(color::Reset));
+    }
+
+    if current != *result {
+        println!("= {}", result);
+    }
+}
+
+/// Times evaluating `src` at many points by walking the tree against running its compiled bytecode.
+fn bench(src: &str, session: &Session) {
+    let Some(tokens) = tokenize(src) else { return };
+    let mut stmts = parse(&tokens);
+    if stmts.len() > 1 {
+        return command_error("expected a single expression");
+    }
+    let ast = match stmts.pop() {
+        Some(Ok(ast)) => ast,
+        Some(Err(err)) => return err.print(src),
+        None => return command_error("expected an expression"),
+    };
+    let Some(expr) = to_expr(src, ast, session) else { return };
+    let expr = session.simplify(expr);
+    let vars = expr.variables();
+    let names: Vec<&str> = vars.iter().map(String::as_str).collect();
+    let Some(compiled) = expr.compile(&names) else {
+        return command_error(&format!("cannot evaluate {} numerically", expr));
+    };
+
+    let args: Vec<Vec<f64>> = (0..vars.len())
+        .map(|i| (0..BENCH_POINTS).map(|j| 0.5 + (i + 1) as f64 * j as f64 / BENCH_POINTS as f64).collect())
+        .collect();
+
+    // Both sides get their buffers up front, so that only the evaluation itself is timed.
+    let mut point: Vec<(&str, f64)> = names.iter().map(|name| (*name, 0.0)).collect();
+    let mut walked = vec![0.0; BENCH_POINTS];
+    let start = Instant::now();
+    for (j, result) in walked.iter_mut().enumerate() {
+        for ((_, value), values) in point.iter_mut().zip(&args) {
+            *value = values[j];
+        }
+        *result = expr.evaluate(&point).unwrap_or(f64::NAN);
+    }
+    let walk_time = start.elapsed();
+
+    let columns: Vec<&[f64]> = args.iter().map(Vec::as_slice).collect();
+    let mut results = vec![0.0; BENCH_POINTS];
+    let start = Instant::now();
+    compiled.eval(&columns, &mut results);
+    let compiled_time = start.elapsed();
+
+    let agree = walked.iter().zip(&results).all(|(a, b)| {
+        (a.is_nan() && b.is_nan()) || a == b || (a - b).abs() <= BENCH_TOLERANCE * a.abs().max(b.abs())
+    });
+    println!();
+    println!("{:#} at {} points", expr, BENCH_POINTS);
+    println!("tree walking  {:>10.2?}", walk_time);
+    println!("compiled      {:>10.2?}   {:.1}x faster", compiled_time, walk_time.as_secs_f64() / compiled_time.as_secs_f64());
+    println!();
+    if !agree {
+        command_error("compiled results differ from tree walking");
+    }
+}
+
+fn command_error(details: &str) {
+    println!("\n{}{}error{}: {}{}\n", color::Fg(color::Red), style::Bold, color::Fg(color::Reset), details, style::Reset);
+}
+
+/// Warns that the user's rules were cut off before reaching a fixed point, so the result shown is
+/// wherever they stopped.
+fn unsettled_warning() {
+    println!(
+        "{}{}warning{}: the rules did not settle after {} passes, so this is where they stopped{}\n",
+        color::Fg(color::Yellow), style::Bold, color::Fg(color::Reset), MAX_REWRITE_PASSES, style::Reset,
+    );
+}
+
+/// Runs a REPL command like `:vars`, `:clear x`, `:forget x`, `:rules clear`, `:set rationalize on`, `:set egraph size` or
+/// `:bench sin[x] y`.
+fn run_command(input: &str, session: &mut Session) {
+    let mut words = input.trim_start_matches(':').split_whitespace();
+    match (words.next(), words.next()) {
+        (Some("bench"), Some(_)) => bench(input.trim_start_matches(':').trim_start().trim_start_matches("bench"), session),
+        (Some("set"), Some(setting)) => match (setting, words.next()) {
+            ("rationalize", Some("on")) => session.rationalize = true,
+            ("rationalize", Some("off")) => session.rationalize = false,
+            ("rationalize", _) => command_error("expected 'on' or 'off'"),
+            ("egraph", Some("off")) => session.egraph = None,
+            ("egraph", cost) => match cost.and_then(Cost::from_name) {
+                Some(cost) => session.egraph = Some(cost),
+                None => command_error("expected 'off', 'size', 'depth' or 'radicals'"),
+            },
+            _ => command_error(&format!("unknown setting '{}'", setting)),
         },
-        RunStrategies::ShowAST => {
+        (Some("vars"), None) => {
+            println!();
+            for (name, value) in session.vars() {
+                print_labeled(&format!("{} := ", name), value);
+            }
             println!();
-            println!("{}", ast);
         },
-        RunStrategies::Simplify => {
+        (Some("assumptions"), None) => {
             println!();
-            println!("{}", expr.simplify());
+            session.assumptions.facts().iter().for_each(|fact| println!("{}", fact));
             println!();
-        }
+        },
+        (Some("rules"), None) => {
+            println!();
+            session.rules.iter().for_each(|rule| println!("{}", rule));
+            println!();
+        },
+        (Some("rules"), Some("clear")) => session.rules.clear(),
+        (Some("forget"), None) => session.assumptions.clear(),
+        (Some("forget"), Some(name)) => {
+            if !session.assumptions.forget(name) {
+                command_error(&format!("nothing is assumed about '{}'", name));
+            }
+        },
+        (Some("clear"), None) => session.clear(),
+        (Some("clear"), Some(name)) => {
+            if session.unbind(name).is_none() {
+                command_error(&format!("variable '{}' is not bound", name));
+            }
+        },
+        _ => command_error(&format!("unknown command '{}'", input.trim())),
     }
 }
 
@@ -83,14 +324,20 @@ fn main() {
             .build()
     ).unwrap();
 
+    let mut session = Session::new();
+
     println!();
 
     loop {
         match stdin.readline(">> ") {
             Ok(input) => {
+                if input.trim_start().starts_with(':') {
+                    run_command(input.trim_start(), &mut session);
+                    continue;
+                }
                 print_runstrats();
                 let Ok(opts) = select_runstrats(&mut stdin, 0) else { break };
-                run(&input, opts);
+                run(&input, opts, &mut session);
             },
             Err(ReadlineError::Interrupted) => {
                 println!("^C");
@@ -9,6 +9,8 @@ pub struct Parser {
     pub token_index: usize,
     pub current_token: Token,
     advance_count: usize,
+    /// Set while parsing the parameters of a call, where `name[` starts the call's arguments rather than a nested call.
+    in_params: bool,
 }
 
 impl Parser {
@@ -18,6 +20,7 @@ impl Parser {
             tokens, 
             token_index: 0,
             advance_count: 0,
+            in_params: false,
         }
     }
 
@@ -37,36 +40,76 @@ impl Parser {
         }
     }
 
-    pub fn parse(&mut self) -> Result<Node> {
-        let res = self.stmt()?;
-        if self.current_token.ty != TokenType::Eof {
-            return err!(Syntax, "expected '+', '-', '*', '/', or '^'", self.current_token.span);
+    /// Parses a sequence of `;`-separated statements.
+    ///
+    /// A statement that fails to parse is reported in its place, and parsing resumes after the next `;`
+    /// outside of brackets.
+    pub fn parse(&mut self) -> Vec<Result<Node>> {
+        let mut stmts = Vec::new();
+
+        loop {
+            while tteq!(self.current_token.ty => Semicolon) {
+                self.advance();
+            }
+
+            if tteq!(self.current_token.ty => Eof) {
+                break;
+            }
+
+            let start = self.token_index;
+            let res = self.assignment().and_then(|stmt| if tteq!(self.current_token.ty => Semicolon, Eof) {
+                Ok(stmt)
+            } else {
+                err!(Syntax, "expected '+', '-', '*', '/', '^' or ';'", self.current_token.span)
+            });
+
+            if res.is_err() {
+                // The error can come from inside brackets, so the statement is skipped from its start to
+                // keep count of them.
+                while self.token_index > start {
+                    self.back();
+                }
+                let mut depth = 0usize;
+                while ttne!(self.current_token.ty => Eof) && (depth > 0 || ttne!(self.current_token.ty => Semicolon)) {
+                    if tteq!(self.current_token.ty => LParen, LBracket, LBrace) {
+                        depth += 1;
+                    } else if tteq!(self.current_token.ty => RParen, RBracket, RBrace) {
+                        depth = depth.saturating_sub(1);
+                    }
+                    self.advance();
+                }
+            }
+
+            stmts.push(res);
         }
-        Ok(res)
+
+        stmts
     }
 
     fn atom(&mut self) -> Result<Node> {
         let token = self.current_token.clone();
 
-        if tteq!(token.ty => Decimal) {
+        if tteq!(token.ty => Integer, Decimal) {
             self.advance();
             return Ok(Node::Constant { token: token.clone() });
         }
 
-        if tteq!(token.ty => Identifier) {
+        if tteq!(token.ty => Text) {
             self.advance();
-            return Ok(Node::Variable { name: token.clone() });
+            return Ok(Node::Text { token });
         }
 
-        if tteq!(token.ty => Add, Sub) {
+        if tteq!(token.ty => Identifier) {
             self.advance();
-            let node = self.atom()?;
-            return Ok(Node::UnaryOp { token: token.clone(), node: Box::new(node) });
+            return Ok(Node::Variable { name: token.clone() });
         }
 
         if tteq!(token.ty => LParen) {
             self.advance();
-            let expr = self.expr()?;
+            let in_params = std::mem::replace(&mut self.in_params, false);
+            let expr = self.expr();
+            self.in_params = in_params;
+            let expr = expr?;
             if tteq!(self.current_token.ty => RParen) {
                 self.advance();
                 return Ok(expr);
@@ -74,23 +117,50 @@ impl Parser {
             return err!(Syntax, "expected ')'", self.current_token.span);
         }
 
-        err!(Syntax, "expected decimal, '+', '-' or '('", self.current_token.span)
+        if tteq!(token.ty => LBracket) && !self.in_params {
+            let in_params = std::mem::replace(&mut self.in_params, false);
+            let items = self.call_args();
+            self.in_params = in_params;
+            return Ok(Node::List { items: items?, span: Span::new(token.span.pos_1, self.tokens[self.token_index - 1].span.pos_2) });
+        }
+
+        err!(Syntax, "expected decimal, '+', '-', '(' or '['", self.current_token.span)
     }
 
     fn call(&mut self) -> Result<Node> {
         let call_start = self.current_token.span.pos_1;
         let atom = self.atom()?;
 
+        if self.in_params {
+            return Ok(atom);
+        }
+
         if let Node::Variable { name } = atom.clone() {
+            if tteq!(self.current_token.ty => Tick) {
+                let mut order = 0;
+                while tteq!(self.current_token.ty => Tick) {
+                    self.advance();
+                    order += 1;
+                }
+
+                let args = if tteq!(self.current_token.ty => LBracket) {
+                    self.call_args()?
+                } else {
+                    Vec::new()
+                };
+
+                return Ok(Node::Derivative { name, order, args, span: Span::new(call_start, self.tokens[self.token_index - 1].span.pos_2) });
+            }
+
             let mut params = Vec::new();
             if tteq!(self.current_token.ty => Colon) {
                 self.advance();
                 
-                params.push(self.expr()?);
+                params.push(self.param()?);
 
                 while tteq!(self.current_token.ty => Colon) {
                     self.advance();
-                    params.push(self.expr()?);
+                    params.push(self.param()?);
                 }
 
                 if ttne!(self.current_token.ty => LBracket) {
@@ -99,38 +169,86 @@ impl Parser {
             }
 
             if tteq!(self.current_token.ty => LBracket) {
-                self.advance();
-                let mut args = Vec::new();
+                let args = self.call_args()?;
+                return Ok(Node::Call { name, args, params, span: Span::new(call_start, self.current_token.span.pos_2 ) });
+            }
+        }
 
-                if tteq!(self.current_token.ty => RBracket) {
-                    self.advance();
-                } else {
-                    args.push(self.expr()?);
+        Ok(atom)
+    }
 
-                    while tteq!(self.current_token.ty => Comma) {
-                        self.advance();
-                        args.push(self.expr()?);
-                    }
+    fn param(&mut self) -> Result<Node> {
+        if tteq!(self.current_token.ty => Add, Sub) && self.tokens.get(self.token_index + 1).is_some_and(|x| tteq!(x.ty => Colon, LBracket)) {
+            let token = self.current_token.clone();
+            self.advance();
+            return Ok(Node::Sign { token });
+        }
 
-                    if ttne!(self.current_token.ty => RBracket) {
-                        return err!(Syntax, "expected ',' or ']'", self.current_token.span);
-                    }
-                    self.advance();
-                }
+        self.in_params = true;
+        let param = self.expr();
+        self.in_params = false;
+        param
+    }
 
-                return Ok(Node::Call { name, args, params, span: Span::new(call_start, self.current_token.span.pos_2 ) });
+    fn call_args(&mut self) -> Result<Vec<Node>> {
+        self.advance();
+        let mut args = Vec::new();
+
+        if tteq!(self.current_token.ty => RBracket) {
+            self.advance();
+        } else {
+            args.push(self.arg()?);
+
+            while tteq!(self.current_token.ty => Comma) {
+                self.advance();
+                args.push(self.arg()?);
             }
+
+            if ttne!(self.current_token.ty => RBracket) {
+                return err!(Syntax, "expected ',' or ']'", self.current_token.span);
+            }
+            self.advance();
         }
 
-        Ok(atom)
+        Ok(args)
+    }
+
+    /// Parses a call argument, which may also be a rewrite rule like `sin[a]^2 + cos[a]^2 -> 1`.
+    fn arg(&mut self) -> Result<Node> {
+        self.bin_op(Self::stmt, Self::stmt, &[TokenType::Arrow])
+    }
+
+    fn postfix(&mut self) -> Result<Node> {
+        let postfix_start = self.current_token.span.pos_1;
+        let mut node = self.call()?;
+
+        while tteq!(self.current_token.ty => Bang, DoubleBang) {
+            let token = self.current_token.clone();
+            self.advance();
+            node = Node::PostfixOp { span: Span::new(postfix_start, token.span.pos_2), token, node: Box::new(node) };
+        }
+
+        Ok(node)
     }
 
     fn factor(&mut self) -> Result<Node> {
-        self.bin_op(Self::call, Self::call, &[TokenType::Pow])
+        self.bin_op(Self::postfix, Self::unary, &[TokenType::Pow])
+    }
+
+    /// Parses a factor with any number of signs in front, which apply after its postfix operators and
+    /// powers, so that `-3!` is `-(3!)` and `-x^2` is `-(x^2)`.
+    fn unary(&mut self) -> Result<Node> {
+        let token = self.current_token.clone();
+        if tteq!(token.ty => Add, Sub) {
+            self.advance();
+            let node = self.unary()?;
+            return Ok(Node::UnaryOp { token, node: Box::new(node) });
+        }
+        self.factor()
     }
 
     fn term(&mut self) -> Result<Node> {
-        let mut left = self.factor()?;
+        let mut left = self.unary()?;
 
         loop {
             let mut should_break = false;
@@ -138,7 +256,7 @@ impl Parser {
             if tteq!(self.current_token.ty => Mul, Div) {
                 let token = self.current_token.clone();
                 self.advance();
-                let right = self.factor()?;
+                let right = self.unary()?;
                 left = Node::BinaryOp { token, left: Box::new(left), right: Box::new(right) }
             } else {
                 should_break = true;
@@ -174,6 +292,39 @@ impl Parser {
         self.bin_op(Self::term, Self::term, &[TokenType::Add, TokenType::Sub])
     }
 
+    fn assignment(&mut self) -> Result<Node> {
+        let assign_start = self.current_token.span.pos_1;
+
+        let is_let = matches!(&self.current_token.ty, TokenType::Identifier(kw) if kw == "let");
+        let is_walrus = tteq!(self.current_token.ty => Identifier)
+            && self.tokens.get(self.token_index + 1).is_some_and(|t| tteq!(t.ty => Assign));
+
+        if !is_let && !is_walrus {
+            return self.stmt();
+        }
+
+        if is_let {
+            self.advance();
+            if ttne!(self.current_token.ty => Identifier) {
+                return err!(Syntax, "expected variable name", self.current_token.span);
+            }
+        }
+        if matches!(&self.current_token.ty, TokenType::Identifier(name) if name == "let") {
+            return err!(Syntax, "'let' is a keyword and cannot be a variable name", self.current_token.span);
+        }
+
+        let name = self.current_token.clone();
+        self.advance();
+
+        if is_let && ttne!(self.current_token.ty => Equals) {
+            return err!(Syntax, "expected '='", self.current_token.span);
+        }
+        self.advance();
+
+        let value = self.stmt()?;
+        Ok(Node::Assign { name, value: Box::new(value), span: Span::new(assign_start, self.tokens[self.token_index - 1].span.pos_2) })
+    }
+
     fn stmt(&mut self) -> Result<Node> {
         self.bin_op(Self::expr, Self::expr, &[
             TokenType::Equals,
@@ -202,3 +353,47 @@ impl Parser {
         Ok(left)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Parser;
+    use crate::{lexer::Lexer, testing::{error, eval}};
+
+    /// Which statements of `input` parse.
+    fn parsed(input: &str) -> Vec<bool> {
+        let Ok(tokens) = Lexer::new(input).tokenize() else { panic!("cannot tokenize {}", input) };
+        Parser::new(tokens).parse().iter().map(Result::is_ok).collect()
+    }
+
+    #[test]
+    fn statements_split_at_semicolons() {
+        assert_eq!(parsed("1 + 1; 2 * 3"), [true, true]);
+        assert_eq!(parsed(";;5;"), [true]);
+        assert_eq!(eval("1 + 1; 2 * 3"), "6");
+    }
+
+    #[test]
+    fn errors_resync_at_top_level_semicolons() {
+        assert_eq!(parsed("1 +; 4"), [false, true]);
+        assert_eq!(parsed("f[1; 2]; 3"), [false, true]);
+        assert_eq!(parsed("(1; 2); 3; 4"), [false, true, true]);
+    }
+
+    #[test]
+    fn signs_apply_after_postfix_operators_and_powers() {
+        assert_eq!(eval("-3!"), "-6");
+        assert_eq!(eval("-2^2"), "-4");
+        assert_eq!(eval("2^-1"), "(1 / 2)");
+        assert_eq!(eval("2*-3"), "-6");
+        assert_eq!(eval("--3"), "3");
+    }
+
+    #[test]
+    fn double_factorials_parse_back() {
+        assert_eq!(eval("3!!"), "3");
+        assert_eq!(eval("(3!)!"), "720");
+        assert_eq!(eval("5!!"), "15");
+        assert_eq!(eval("n!!"), "n!!");
+        assert_eq!(error("(-3)!!"), "double factorial is undefined for integer -3");
+    }
+}
@@ -17,15 +17,44 @@ pub enum Node {
         token: Token,
         node: Box<Node>,
     },
+    PostfixOp {
+        token: Token,
+        node: Box<Node>,
+        span: Span,
+    },
     Call {
         name: Token,
         params: Vec<Node>,
         args: Vec<Node>,
         span: Span,
     },
+    Derivative {
+        name: Token,
+        order: usize,
+        args: Vec<Node>,
+        span: Span,
+    },
     Variable {
         name: Token,
     },
+    /// A bracketed list like `[1, 2]`, only allowed as the row of a matrix.
+    List {
+        items: Vec<Node>,
+        span: Span,
+    },
+    /// A quoted string, only allowed as a call parameter like the file in `plot:x:0:10:"out.svg"[...]`.
+    Text {
+        token: Token,
+    },
+    /// A bare `+` or `-`, only allowed as a call parameter like the direction in `lim:x:0:+[...]`.
+    Sign {
+        token: Token,
+    },
+    Assign {
+        name: Token,
+        value: Box<Node>,
+        span: Span,
+    },
 }
 
 impl Node {
@@ -34,8 +63,14 @@ impl Node {
             Node::Constant { token } => write!(f, "{}{:?}{}", color::Fg(color::Yellow), token.ty, color::Fg(color::Reset)),
             Node::BinaryOp { token, .. } => write!(f, "{}{:?}{}", color::Fg(color::LightGreen), token.ty, color::Fg(color::Reset)),
             Node::UnaryOp { token, .. } => write!(f, "{}{:?}{}", color::Fg(color::LightBlue), token.ty, color::Fg(color::Reset)),
+            Node::PostfixOp { token, .. } => write!(f, "{}{:?}{}", color::Fg(color::LightBlue), token.ty, color::Fg(color::Reset)),
             Node::Call { name, params, .. } => write!(f, "{}Call({})\n{:?}{}", color::Fg(color::LightRed), name.ty, params, color::Fg(color::Reset)),
-            Node::Variable { name } => write!(f, "{}Var({}){}", color::Fg(color::LightMagenta), name.ty, color::Fg(color::Reset))
+            Node::Derivative { name, order, .. } => write!(f, "{}Derivative({}{}){}", color::Fg(color::LightRed), name.ty, "'".repeat(*order), color::Fg(color::Reset)),
+            Node::Variable { name } => write!(f, "{}Var({}){}", color::Fg(color::LightMagenta), name.ty, color::Fg(color::Reset)),
+            Node::Sign { token } => write!(f, "{}{:?}{}", color::Fg(color::LightBlue), token.ty, color::Fg(color::Reset)),
+            Node::Text { token } => write!(f, "{}{:?}{}", color::Fg(color::Yellow), token.ty, color::Fg(color::Reset)),
+            Node::List { .. } => write!(f, "{}List{}", color::Fg(color::LightRed), color::Fg(color::Reset)),
+            Node::Assign { name, .. } => write!(f, "{}Assign({}){}", color::Fg(color::LightCyan), name.ty, color::Fg(color::Reset)),
         }
     }
 
@@ -44,8 +79,14 @@ impl Node {
             Node::Constant { .. } => vec![],
             Node::BinaryOp { left, right, .. } => vec![*left.clone(), *right.clone()],
             Node::UnaryOp { node, .. } => vec![*node.clone()],
+            Node::PostfixOp { node, .. } => vec![*node.clone()],
             Node::Call { args, .. } => args.to_vec(),
+            Node::Derivative { args, .. } => args.to_vec(),
             Node::Variable { .. } => vec![],
+            Node::Sign { .. } => vec![],
+            Node::Text { .. } => vec![],
+            Node::List { items, .. } => items.to_vec(),
+            Node::Assign { value, .. } => vec![*value.clone()],
         }
     }
 }
@@ -56,8 +97,14 @@ impl std::fmt::Debug for Node {
             Node::Constant { token } => write!(f, "{}", token.ty),
             Node::BinaryOp { token, left, right } => write!(f, "({:?} {} {:?})", left, token.ty, right),
             Node::UnaryOp { token, node } => write!(f, "{}{:?}", token.ty, node),
+            Node::PostfixOp { token, node, .. } => write!(f, "{:?}{}", node, token.ty),
             Node::Call { name, args, .. } => write!(f, "{:?}{:?}", name, args),
+            Node::Derivative { name, order, args, .. } => write!(f, "{:?}{}{:?}", name, "'".repeat(*order), args),
             Node::Variable { name } => write!(f, "{}", name.ty),
+            Node::Sign { token } => write!(f, "{}", token.ty),
+            Node::Text { token } => write!(f, "{}", token.ty),
+            Node::List { items, .. } => write!(f, "{:?}", items),
+            Node::Assign { name, value, .. } => write!(f, "{} := {:?}", name.ty, value),
         }
     }
 }
@@ -0,0 +1,322 @@
+pub mod svg;
+
+use termion::color;
+
+use crate::{expr::{self, compile::CompiledFn, Expr}, lexer::token::{Token, TokenType}, parser::node::Node, session::Session};
+use crate::prelude::*;
+
+
+/// Rows of characters the plot area takes, each holding four rows of braille dots.
+const ROWS: usize = 16;
+
+/// Width assumed when the terminal does not report its size.
+pub const DEFAULT_WIDTH: usize = 80;
+
+/// The plot area is never narrower than this many characters, even in a narrow terminal.
+const MIN_COLUMNS: usize = 16;
+
+/// Points sampled to check that each expression is defined somewhere on the interval, and to fit
+/// the vertical axis to its values.
+const CHECK_SAMPLES: usize = 1000;
+
+/// Share of the samples on either end of the range of values left out when fitting the vertical
+/// axis, so that the values near a pole do not flatten the rest of the graph.
+const OUTLIER_SHARE: f64 = 0.02;
+
+/// Jumps between neighbouring samples larger than this share of the vertical axis are only drawn
+/// if the function passes between them at the midpoint, as otherwise they are discontinuities.
+const MAX_SMOOTH_JUMP: f64 = 0.25;
+
+/// The colors of the plotted expressions, in order, cycling after the last.
+const COLORS: [color::AnsiValue; 6] = [
+    color::AnsiValue(6),
+    color::AnsiValue(3),
+    color::AnsiValue(5),
+    color::AnsiValue(2),
+    color::AnsiValue(1),
+    color::AnsiValue(4),
+];
+
+/// The bits of the braille dots in a character, by row and column.
+const DOTS: [[u8; 2]; 4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+
+/// Graphs of expressions in one variable over an interval, drawn with braille characters or written
+/// to an SVG file.
+pub struct Plot {
+    var: String,
+    from: f64,
+    to: f64,
+    series: Vec<(Expr, CompiledFn)>,
+    /// The range of the vertical axis.
+    range: (f64, f64),
+    /// The SVG file to write the plot to, rather than drawing it in the terminal.
+    pub file: Option<String>,
+}
+
+/// A step between axis ticks of 1, 2 or 5 times a power of 10, giving about `count` ticks over
+/// `span`.
+fn tick_step(span: f64, count: usize) -> f64 {
+    let rough = span / count.max(1) as f64;
+    let power = 10f64.powf(rough.log10().floor());
+    [1.0, 2.0, 5.0, 10.0].into_iter().map(|m| m * power).find(|step| *step >= rough).unwrap_or(10.0 * power)
+}
+
+fn ticks(from: f64, to: f64, count: usize) -> (Vec<f64>, f64) {
+    let step = tick_step(to - from, count);
+    let first = (from / step).ceil() as i64;
+    let last = (to / step).floor() as i64;
+    ((first..=last).map(|i| i as f64 * step).collect(), step)
+}
+
+fn tick_label(v: f64, step: f64) -> String {
+    let decimals = (-step.log10().floor()).max(0.0) as usize;
+    match format!("{:.*}", decimals, v) {
+        zero if zero.trim_start_matches('-').chars().all(|c| c == '0' || c == '.') => zero.trim_start_matches('-').to_string(),
+        label => label,
+    }
+}
+
+/// The range of the vertical axis, covering the values sampled apart from outliers, or `None` if
+/// none are finite.
+fn value_range(samples: &[Vec<f64>]) -> Option<(f64, f64)> {
+    let mut values: Vec<f64> = samples.iter().flatten().copied().filter(|v| v.is_finite()).collect();
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_by(f64::total_cmp);
+
+    let quantile = |q: f64| values[((values.len() - 1) as f64 * q).round() as usize];
+    let (low, high) = (quantile(OUTLIER_SHARE), quantile(1.0 - OUTLIER_SHARE));
+    let spread = high - low;
+    let (min, max) = (values[0].max(low - spread), values[values.len() - 1].min(high + spread));
+    Some(match max - min {
+        0.0 => (min - 1.0, max + 1.0),
+        _ => (min, max),
+    })
+}
+
+impl Plot {
+    /// Reads a plot like `plot:x:-5:5[sin[x], cos[x]]` or `plot:x:-5:5:"out.svg"[sin[x]]` from the
+    /// parameters and arguments of the call.
+    pub fn new(params: &[Node], args: &[Node], span: Span, session: &Session) -> Result<Self> {
+        if args.is_empty() { return err!(InvalidCall, "expected at least 1 argument", span) };
+        if !(3..=4).contains(&params.len()) { return err!(InvalidCall, "expected 3 or 4 parameters, got {}", span; params.len()) };
+        let var = expr::param_var(&params[0], span)?;
+        let file = match params.get(3) {
+            None => None,
+            Some(Node::Text { token: Token { ty: TokenType::Text(path), .. } }) => Some(path.clone()),
+            Some(_) => return err!(InvalidCall, "expected a quoted file name like \"out.svg\"", span),
+        };
+        let bound = |node: &Node| -> Result<f64> {
+            let bound = Expr::convert(node.clone(), session)?.simplify();
+            match bound.to_f64() {
+                Some(v) if v.is_finite() => Ok(v),
+                _ => err!(InvalidCall, "expected a finite numeric bound, got {}", span; bound),
+            }
+        };
+        let (from, to) = (bound(&params[1])?, bound(&params[2])?);
+        if from >= to { return err!(Domain, "the lower bound {} is not below the upper bound {}", span; from, to) };
+
+        let series = args.iter()
+            .map(|arg| {
+                let expr = session.simplify(expr::scoped_arg(arg.clone(), &[&var], session)?);
+                let Some(compiled) = expr.compile(&[&var]) else {
+                    return err!(InvalidCall, "cannot evaluate {} numerically for values of {}", span; expr, var);
+                };
+                Ok((expr, compiled))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut plot = Plot { var, from, to, series, range: (0.0, 0.0), file };
+        let samples = plot.sample(CHECK_SAMPLES);
+        for (samples, (expr, _)) in samples.iter().zip(&plot.series) {
+            if !samples.iter().any(|v| v.is_finite()) {
+                return err!(Domain, "{} is undefined everywhere from {} to {}", span; expr, from, to);
+            }
+        }
+        let Some(range) = value_range(&samples) else {
+            return err!(Domain, "nothing to plot from {} to {}", span; from, to);
+        };
+        plot.range = range;
+        Ok(plot)
+    }
+
+    fn x(&self, i: f64, count: usize) -> f64 {
+        self.from + (i + 0.5) / count as f64 * (self.to - self.from)
+    }
+
+    /// The values of each expression at `count` evenly spaced points.
+    fn sample(&self, count: usize) -> Vec<Vec<f64>> {
+        let xs: Vec<f64> = (0..count).map(|i| self.x(i as f64, count)).collect();
+        self.series.iter()
+            .map(|(_, f)| {
+                let mut values = vec![0.0; count];
+                f.eval(&[&xs], &mut values);
+                values
+            })
+            .collect()
+    }
+
+    /// Renders the graphs `width` characters wide, with axes, tick labels and a legend.
+    pub fn render(&self, width: usize) -> String {
+        let (low, high) = self.range;
+        let (y_ticks, y_step) = ticks(low, high, ROWS / 4);
+        let y_labels: Vec<String> = y_ticks.iter().map(|&v| tick_label(v, y_step)).collect();
+        let label_width = y_labels.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+
+        let columns = width.saturating_sub(label_width + 2).max(MIN_COLUMNS);
+        let (dots_wide, dots_high) = (2 * columns, 4 * ROWS);
+        let row_of = |v: f64| (high - v) / (high - low) * (dots_high - 1) as f64;
+        let column_of = |x: f64| ((x - self.from) / (self.to - self.from) * dots_wide as f64 - 0.5).round();
+
+        let mut cells = vec![vec![0u8; columns]; ROWS];
+        let mut colors = vec![vec![None; columns]; ROWS];
+        for (s, values) in self.sample(dots_wide).iter().enumerate() {
+            let mut set = |column: usize, row: f64| {
+                let row = row.round() as usize;
+                cells[row / 4][column / 2] |= DOTS[row % 4][column % 2];
+                colors[row / 4][column / 2] = Some(s);
+            };
+            let in_range = |row: f64| (-0.5..dots_high as f64 - 0.5).contains(&row);
+
+            for (i, &y) in values.iter().enumerate() {
+                if !y.is_finite() {
+                    continue;
+                }
+                let row = row_of(y);
+                if in_range(row) {
+                    set(i, row);
+                }
+
+                let Some(&previous) = i.checked_sub(1).map(|j| &values[j]) else { continue };
+                let midpoint = self.series[s].1.call(&[self.x(i as f64 - 0.5, dots_wide)]);
+                let continuous = previous.is_finite()
+                    && ((y - previous).abs() <= MAX_SMOOTH_JUMP * (high - low) || (previous.min(y)..=previous.max(y)).contains(&midpoint));
+                if continuous {
+                    let (top, bottom) = (row_of(previous.max(y)).max(0.0), row_of(previous.min(y)).min((dots_high - 1) as f64));
+                    (top.round() as usize..=bottom.round() as usize).filter(|_| top <= bottom).for_each(|r| set(i, r as f64));
+                }
+            }
+        }
+
+        let axis_row = (low..=high).contains(&0.0).then(|| row_of(0.0).round() as usize / 4);
+        // The frame already marks zero when it is the left end.
+        let axis_column = (self.from..=self.to).contains(&0.0).then(|| column_of(0.0) as usize / 2).filter(|&c| c > 0);
+        let dim = color::Fg(color::LightBlack);
+        let reset = color::Fg(color::Reset);
+
+        let mut out = String::new();
+        for row in 0..ROWS {
+            let tick = y_ticks.iter().position(|&v| row_of(v).round() as usize / 4 == row);
+            let label = tick.map(|t| y_labels[t].as_str()).unwrap_or("");
+            out += &format!("{:>w$} {}{}{}", label, dim, if tick.is_some() { '┤' } else { '│' }, reset, w = label_width);
+
+            for column in 0..columns {
+                match (cells[row][column], colors[row][column]) {
+                    (0, _) => {
+                        let axis = match (axis_row == Some(row), axis_column == Some(column)) {
+                            (true, true) => '┼',
+                            (true, false) => '─',
+                            (false, true) => '│',
+                            (false, false) => ' ',
+                        };
+                        out += &format!("{}{}{}", dim, axis, reset);
+                    },
+                    (bits, s) => {
+                        let c = char::from_u32(0x2800 + bits as u32).unwrap();
+                        out += &format!("{}{}{}", color::Fg(COLORS[s.unwrap() % COLORS.len()]), c, reset);
+                    },
+                }
+            }
+            out += "\n";
+        }
+
+        let (x_ticks, x_step) = ticks(self.from, self.to, columns / 12);
+        let tick_columns: Vec<usize> = x_ticks.iter().map(|&x| (column_of(x).max(0.0) as usize / 2).min(columns - 1)).collect();
+        let axis: String = (0..columns).map(|c| if tick_columns.contains(&c) { '┬' } else { '─' }).collect();
+        out += &format!("{:w$} {}└{}{}\n", "", dim, axis, reset, w = label_width);
+
+        let mut labels = vec![' '; columns + label_width + 2];
+        let mut free_from = 0;
+        for (&x, &column) in x_ticks.iter().zip(&tick_columns) {
+            let label: Vec<char> = tick_label(x, x_step).chars().collect();
+            let start = (label_width + 1 + column).saturating_sub(label.len() / 2);
+            if start < free_from || start + label.len() > labels.len() {
+                continue;
+            }
+            labels[start..start + label.len()].copy_from_slice(&label);
+            free_from = start + label.len() + 1;
+        }
+        out += labels.iter().collect::<String>().trim_end();
+        out += "\n\n";
+
+        for (s, (expr, _)) in self.series.iter().enumerate() {
+            out += &format!("{}━━{} {:#}\n", color::Fg(COLORS[s % COLORS.len()]), reset, expr);
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{tick_label, tick_step, ticks, value_range, ROWS};
+    use crate::testing;
+
+    /// The text of a rendered plot, without its colors.
+    fn plain(text: &str) -> String {
+        let mut out = String::new();
+        let mut chars = text.chars();
+        while let Some(c) = chars.next() {
+            if c == '\x1b' {
+                chars.by_ref().find(|c| c.is_ascii_alphabetic());
+            } else {
+                out.push(c);
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn ticks_are_round_numbers() {
+        assert_eq!(tick_step(2.0, 4), 0.5);
+        assert_eq!(tick_step(7.0, 3), 5.0);
+        assert_eq!(ticks(-1.0, 1.0, 2), (vec![-1.0, 0.0, 1.0], 1.0));
+        assert_eq!(tick_label(0.5, 0.5), "0.5");
+        assert_eq!(tick_label(-1e-17, 0.5), "0.0");
+        assert_eq!(tick_label(20.0, 10.0), "20");
+    }
+
+    #[test]
+    fn outliers_do_not_stretch_the_axis() {
+        let mut values: Vec<f64> = (0..100).map(|i| i as f64 / 100.0).collect();
+        values.push(1e9);
+        values.push(f64::NAN);
+        let Some((low, high)) = value_range(&[values]) else { panic!("no range") };
+        assert!(low == 0.0 && high < 10.0, "[{}, {}]", low, high);
+        assert_eq!(value_range(&[vec![f64::NAN]]), None);
+        assert_eq!(value_range(&[vec![3.0, 3.0]]), Some((2.0, 4.0)));
+    }
+
+    #[test]
+    fn rendering() {
+        let Ok(plot) = testing::plot("plot:x:-1:1[x, x^2]") else { panic!("cannot plot") };
+        let out = plain(&plot.render(40));
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), ROWS + 5);
+        assert!(lines[..ROWS].iter().all(|line| line.chars().count() == 40), "{}", out);
+        assert_eq!(lines[ROWS + 1], "    -1                0               1");
+        assert_eq!(lines[ROWS + 3..], ["━━ x", "━━ (x ^ 2)"]);
+        // The line through the origin crosses the horizontal axis in the middle.
+        assert!(lines[8].starts_with(" 0.0 ┤────") && lines[8].contains('⣠'), "{}", out);
+    }
+
+    #[test]
+    fn invalid_plots() {
+        let error = |input: &str| testing::plot(input).err().unwrap_or_else(|| panic!("{} plotted", input));
+        assert_eq!(error("plot:x:1:-1[x]"), "the lower bound 1 is not below the upper bound -1");
+        assert_eq!(error("plot:x:0[x]"), "expected 3 or 4 parameters, got 2");
+        assert_eq!(error("plot:x:0:inf[x]"), "expected a finite numeric bound, got ∞");
+        assert_eq!(error("plot:x:-2:-1[ln[x]]"), "ln[x] is undefined everywhere from -2 to -1");
+        assert_eq!(error("plot:x:0:1[y]"), "cannot evaluate y numerically for values of x");
+    }
+}
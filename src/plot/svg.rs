@@ -0,0 +1,298 @@
+use super::{tick_label, ticks, Plot, MAX_SMOOTH_JUMP};
+use crate::prelude::*;
+
+
+/// Size of the image, in pixels.
+const WIDTH: f64 = 800.0;
+const HEIGHT: f64 = 500.0;
+
+/// Space left around the plot area for the tick and axis labels.
+const LEFT: f64 = 80.0;
+const RIGHT: f64 = 24.0;
+const TOP: f64 = 24.0;
+const BOTTOM: f64 = 64.0;
+
+/// Intervals the range is evenly split into before sampling more densely where the graphs bend.
+const INITIAL_SAMPLES: usize = 200;
+
+/// Times an interval between samples may be halved where a graph bends sharply.
+const MAX_REFINEMENTS: u32 = 12;
+
+/// Distance a graph may stray from a straight line between two samples, as a share of the height
+/// of the plot area, before the interval between them is halved.
+const FLATNESS: f64 = 0.001;
+
+/// Approximate width of a character of the labels, for fitting the legend around them.
+const CHAR_WIDTH: f64 = 7.5;
+
+/// The counterparts of [`super::COLORS`] in the SVG image.
+const COLORS: [&str; 6] = ["#1f77b4", "#d62728", "#2ca02c", "#ff7f0e", "#9467bd", "#8c564b"];
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// An SVG document, written an element at a time.
+struct Svg {
+    body: String,
+}
+
+impl Svg {
+    fn open(&mut self, name: &str, attrs: &[(&str, String)]) {
+        self.body += &format!("<{}", name);
+        for (key, value) in attrs {
+            self.body += &format!(" {}=\"{}\"", key, escape(value));
+        }
+        self.body += ">\n";
+    }
+
+    fn close(&mut self, name: &str) {
+        self.body += &format!("</{}>\n", name);
+    }
+
+    /// Writes an element, with `text` as its content if there is any.
+    fn element(&mut self, name: &str, attrs: &[(&str, String)], text: Option<&str>) {
+        self.open(name, attrs);
+        self.body.pop();
+        match text {
+            Some(text) => self.body += &format!("{}</{}>\n", escape(text), name),
+            None => {
+                self.body.pop();
+                self.body += "/>\n";
+            },
+        }
+    }
+
+    fn line(&mut self, (x1, y1): (f64, f64), (x2, y2): (f64, f64), stroke: &str) {
+        self.element("line", &[
+            ("x1", format!("{:.2}", x1)),
+            ("y1", format!("{:.2}", y1)),
+            ("x2", format!("{:.2}", x2)),
+            ("y2", format!("{:.2}", y2)),
+            ("stroke", stroke.to_string()),
+        ], None);
+    }
+
+    fn text(&mut self, (x, y): (f64, f64), anchor: &str, text: &str) {
+        self.element("text", &[("x", format!("{:.2}", x)), ("y", format!("{:.2}", y)), ("text-anchor", anchor.to_string())], Some(text));
+    }
+
+    fn finish(self) -> String {
+        format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\" font-family=\"sans-serif\" font-size=\"13\">\n{}</svg>\n",
+            self.body, w = WIDTH, h = HEIGHT,
+        )
+    }
+}
+
+/// Collects the points of a graph into runs that are drawn as connected lines.
+struct Runs {
+    runs: Vec<Vec<(f64, f64)>>,
+    current: Vec<(f64, f64)>,
+}
+
+impl Runs {
+    fn connect(&mut self, a: (f64, f64), b: (f64, f64)) {
+        if self.current.last() != Some(&a) {
+            self.split();
+            self.current.push(a);
+        }
+        self.current.push(b);
+    }
+
+    fn split(&mut self) {
+        if self.current.len() > 1 {
+            self.runs.push(std::mem::take(&mut self.current));
+        }
+        self.current.clear();
+    }
+}
+
+impl Plot {
+    /// Samples the graph of the `s`th expression between `a` and `b`, halving the interval while the
+    /// graph strays from a straight line or leaves its domain in between.
+    fn refine(&self, s: usize, a: (f64, f64), b: (f64, f64), depth: u32, span: f64, runs: &mut Runs) {
+        let x = (a.0 + b.0) / 2.0;
+        let m = (x, self.series[s].1.call(&[x]));
+        let finite = [a.1, m.1, b.1].map(f64::is_finite);
+        if finite == [false; 3] {
+            return runs.split();
+        }
+
+        let bent = finite == [true; 3] && (m.1 - (a.1 + b.1) / 2.0).abs() > FLATNESS * span;
+        if depth < MAX_REFINEMENTS && (bent || finite.contains(&false)) {
+            self.refine(s, a, m, depth + 1, span, runs);
+            self.refine(s, m, b, depth + 1, span, runs);
+            return;
+        }
+
+        let continuous = finite == [true; 3]
+            && ((b.1 - a.1).abs() <= MAX_SMOOTH_JUMP * span || (a.1.min(b.1)..=a.1.max(b.1)).contains(&m.1));
+        match continuous {
+            true => runs.connect(a, b),
+            false => runs.split(),
+        }
+    }
+
+    /// The graph of the `s`th expression as runs of points without discontinuities, sampled more
+    /// densely where it bends.
+    fn trace(&self, s: usize, span: f64) -> Vec<Vec<(f64, f64)>> {
+        let point = |i: usize| {
+            let x = self.from + i as f64 / INITIAL_SAMPLES as f64 * (self.to - self.from);
+            (x, self.series[s].1.call(&[x]))
+        };
+
+        let mut runs = Runs { runs: Vec::new(), current: Vec::new() };
+        for i in 0..INITIAL_SAMPLES {
+            self.refine(s, point(i), point(i + 1), 0, span, &mut runs);
+        }
+        runs.split();
+        runs.runs
+    }
+
+    /// Renders the plot as an SVG image with a grid, tick and axis labels, and a legend.
+    pub fn to_svg(&self) -> String {
+        let (low, high) = self.range;
+        let (right, bottom) = (WIDTH - RIGHT, HEIGHT - BOTTOM);
+        let to_x = |x: f64| LEFT + (x - self.from) / (self.to - self.from) * (right - LEFT);
+        // Points far off the plot area are pulled in, as the area is clipped anyway.
+        let to_y = |y: f64| (TOP + (high - y) / (high - low) * (bottom - TOP)).clamp(TOP - HEIGHT, bottom + HEIGHT);
+
+        let mut svg = Svg { body: String::new() };
+        svg.element("rect", &[("width", WIDTH.to_string()), ("height", HEIGHT.to_string()), ("fill", "white".to_string())], None);
+        svg.open("defs", &[]);
+        svg.open("clipPath", &[("id", "area".to_string())]);
+        svg.element("rect", &[
+            ("x", LEFT.to_string()),
+            ("y", TOP.to_string()),
+            ("width", (right - LEFT).to_string()),
+            ("height", (bottom - TOP).to_string()),
+        ], None);
+        svg.close("clipPath");
+        svg.close("defs");
+
+        let (x_ticks, x_step) = ticks(self.from, self.to, 10);
+        let (y_ticks, y_step) = ticks(low, high, 8);
+        for &x in &x_ticks {
+            svg.line((to_x(x), TOP), (to_x(x), bottom), if x == 0.0 { "#888888" } else { "#e5e5e5" });
+            svg.text((to_x(x), bottom + 20.0), "middle", &tick_label(x, x_step));
+        }
+        for &y in &y_ticks {
+            svg.line((LEFT, to_y(y)), (right, to_y(y)), if y == 0.0 { "#888888" } else { "#e5e5e5" });
+            svg.text((LEFT - 8.0, to_y(y) + 4.5), "end", &tick_label(y, y_step));
+        }
+        svg.element("rect", &[
+            ("x", LEFT.to_string()),
+            ("y", TOP.to_string()),
+            ("width", (right - LEFT).to_string()),
+            ("height", (bottom - TOP).to_string()),
+            ("fill", "none".to_string()),
+            ("stroke", "#333333".to_string()),
+        ], None);
+
+        svg.text(((LEFT + right) / 2.0, HEIGHT - 16.0), "middle", &self.var);
+        if let [(expr, _)] = self.series.as_slice() {
+            let (x, y) = (22.0, (TOP + bottom) / 2.0);
+            svg.element("text", &[
+                ("x", x.to_string()),
+                ("y", y.to_string()),
+                ("text-anchor", "middle".to_string()),
+                ("transform", format!("rotate(-90 {} {})", x, y)),
+            ], Some(&format!("{:#}", expr)));
+        }
+
+        svg.open("g", &[("clip-path", "url(#area)".to_string()), ("fill", "none".to_string()), ("stroke-width", "2".to_string())]);
+        for s in 0..self.series.len() {
+            for run in self.trace(s, high - low) {
+                let mut points: Vec<String> = run.iter().map(|&(x, y)| format!("{:.2},{:.2}", to_x(x), to_y(y))).collect();
+                points.dedup();
+                svg.element("polyline", &[("points", points.join(" ")), ("stroke", COLORS[s % COLORS.len()].to_string())], None);
+            }
+        }
+        svg.close("g");
+
+        if self.series.len() > 1 {
+            let names: Vec<String> = self.series.iter().map(|(expr, _)| format!("{:#}", expr)).collect();
+            let longest = names.iter().map(|name| name.chars().count()).max().unwrap_or(0);
+            let (width, height) = (longest as f64 * CHAR_WIDTH + 48.0, names.len() as f64 * 20.0 + 12.0);
+            let (x, y) = (right - width - 10.0, TOP + 10.0);
+            svg.element("rect", &[
+                ("x", x.to_string()),
+                ("y", y.to_string()),
+                ("width", width.to_string()),
+                ("height", height.to_string()),
+                ("fill", "white".to_string()),
+                ("stroke", "#bbbbbb".to_string()),
+            ], None);
+            for (s, name) in names.iter().enumerate() {
+                let row = y + 16.0 + s as f64 * 20.0;
+                svg.element("line", &[
+                    ("x1", (x + 10.0).to_string()),
+                    ("y1", (row - 4.5).to_string()),
+                    ("x2", (x + 34.0).to_string()),
+                    ("y2", (row - 4.5).to_string()),
+                    ("stroke", COLORS[s % COLORS.len()].to_string()),
+                    ("stroke-width", "2".to_string()),
+                ], None);
+                svg.text((x + 40.0, row), "start", name);
+            }
+        }
+
+        svg.finish()
+    }
+
+    /// Writes the plot as an SVG image to `path`.
+    pub fn write_svg(&self, path: &str, span: Span) -> Result<()> {
+        match std::fs::write(path, self.to_svg()) {
+            Ok(()) => Ok(()),
+            Err(e) => err!(InvalidCall, "cannot write {}: {}", span; path, e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::escape;
+    use crate::{prelude::*, testing};
+
+    #[test]
+    fn text_is_escaped() {
+        assert_eq!(escape("a < b & \"c\" > d"), "a &lt; b &amp; &quot;c&quot; &gt; d");
+    }
+
+    #[test]
+    fn graphs_split_at_poles() {
+        let Ok(plot) = testing::plot("plot:x:-1:1[x]") else { panic!("cannot plot") };
+        assert_eq!(plot.trace(0, 2.0).len(), 1);
+        let Ok(plot) = testing::plot("plot:x:-1:1[1/x]") else { panic!("cannot plot") };
+        let runs = plot.trace(0, plot.range.1 - plot.range.0);
+        assert_eq!(runs.len(), 2);
+        assert!(runs[0].iter().all(|&(x, _)| x < 0.0) && runs[1].iter().all(|&(x, _)| x > 0.0));
+    }
+
+    #[test]
+    fn documents() {
+        let Ok(plot) = testing::plot("plot:x:-1:1:\"out.svg\"[x]") else { panic!("cannot plot") };
+        assert_eq!(plot.file.as_deref(), Some("out.svg"));
+        let svg = plot.to_svg();
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\"") && svg.ends_with("</svg>\n"));
+        assert_eq!(svg.matches("<polyline").count(), 1);
+        // A single graph is named along the vertical axis rather than in a legend.
+        assert!(svg.contains("rotate(-90"));
+
+        let Ok(plot) = testing::plot("plot:x:-1:1[x, x^2]") else { panic!("cannot plot") };
+        let svg = plot.to_svg();
+        assert_eq!(svg.matches("<polyline").count(), 2);
+        assert!(!svg.contains("rotate(-90") && svg.contains(">(x ^ 2)</text>"));
+    }
+
+    #[test]
+    fn write_errors_are_reported() {
+        let Ok(plot) = testing::plot("plot:x:0:1[x]") else { panic!("cannot plot") };
+        let path = std::env::temp_dir().join("no such directory").join("out.svg");
+        let Err(Error::InvalidCall(details, _)) = plot.write_svg(path.to_str().unwrap(), Span::new_single(Position::default())) else {
+            panic!("wrote to {}", path.display())
+        };
+        assert!(details.starts_with(&format!("cannot write {}: ", path.display())), "{}", details);
+    }
+}
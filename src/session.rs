@@ -0,0 +1,204 @@
+use std::collections::BTreeMap;
+
+use num_bigint::BigInt;
+
+use crate::{expr::{assumptions::Assumptions, egraph::{self, Cost, Limits}, interned::Interned, rules::Rule, Expr}, lexer::token::{Token, TokenType}, parser::node::Node};
+use crate::prelude::*;
+
+
+/// Passes of the user's rewrite rules made before giving up on reaching a fixed point, in case the
+/// rules undo one another.
+pub const MAX_REWRITE_PASSES: usize = 64;
+
+/// Variables bound by assignments over the lifetime of the REPL, along with its settings.
+#[derive(Clone, Default)]
+pub struct Session {
+    vars: BTreeMap<String, Expr>,
+    /// Whether results are rewritten without radicals in their denominators.
+    pub rationalize: bool,
+    pub assumptions: Assumptions,
+    pub rules: Vec<Rule>,
+    /// How to rank the forms found by an e-graph, or `None` to keep the result of the greedy passes.
+    pub egraph: Option<Cost>,
+}
+
+impl Session {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Binds `name` to `value`, after substituting the variables bound so far into it.
+    ///
+    /// Returns the value that was bound, and whether the user's rules settled on it.
+    pub fn bind(&mut self, name: String, value: Expr) -> (&Expr, bool) {
+        let (value, settled) = self.simplify_settled(self.substitute(value));
+        self.vars.insert(name.clone(), value);
+        (&self.vars[&name], settled)
+    }
+
+    /// Simplifies `expr` under the assumptions made so far, applying the builtin simplifications and
+    /// the user's rules in turn until neither changes it, then as the settings ask for.
+    ///
+    /// With an e-graph cost set, the cheapest form equivalent to either the result or `expr` itself
+    /// is taken instead.
+    pub fn simplify(&self, expr: Expr) -> Expr {
+        self.simplify_settled(expr).0
+    }
+
+    /// Simplifies `expr` as [`Session::simplify`] does, along with whether the user's rules reached a
+    /// fixed point within [`MAX_REWRITE_PASSES`] rather than being cut off.
+    pub fn simplify_settled(&self, expr: Expr) -> (Expr, bool) {
+        let builtin = |expr: Expr| expr.simplify().refine(&self.assumptions).simplify();
+        let original = expr.clone();
+        let mut expr = builtin(expr);
+        let mut settled = false;
+        for _ in 0..MAX_REWRITE_PASSES {
+            let rewritten = expr.clone().rewrite(&self.rules);
+            if rewritten == expr {
+                settled = true;
+                break;
+            }
+            expr = builtin(rewritten);
+        }
+
+        if let Some(cost) = self.egraph {
+            expr = egraph::simplify(&[expr, original], &self.rules, cost, &Limits::default());
+        }
+
+        if self.rationalize {
+            expr = expr.rationalize();
+        }
+        (expr, settled)
+    }
+
+    /// Records the arguments of an `assume[...]` statement, either relations like `x > 0` or a
+    /// variable followed by properties like `n, integer`.
+    ///
+    /// Returns the facts that were assumed, leaving the assumptions as they were on an error.
+    pub fn assume(&mut self, args: Vec<Node>, span: Span) -> Result<Vec<String>> {
+        if args.is_empty() { return err!(InvalidCall, "expected at least 1 argument", span) };
+        let mut assumptions = self.assumptions.clone();
+        let mut facts = Vec::new();
+
+        if let (Node::Variable { name }, true) = (&args[0], args.len() > 1) {
+            let var = format!("{}", name.ty);
+            for property in &args[1..] {
+                let Node::Variable { name } = property else { return err!(InvalidCall, "expected a property like integer or positive", span) };
+                let relation = |relation: fn(Interned, Interned) -> Expr| relation(Expr::Variable(var.clone()).boxed(), Expr::Integer(BigInt::from(0)).boxed());
+                let relation = match format!("{}", name.ty).as_str() {
+                    "integer" => {
+                        assumptions.assume_integer(var.clone());
+                        facts.push(format!("{} ∈ ℤ", var));
+                        continue;
+                    },
+                    "positive" => relation(Expr::greaterthan),
+                    "negative" => relation(Expr::lessthan),
+                    "nonnegative" => relation(Expr::greaterthaneq),
+                    "nonpositive" => relation(Expr::lessthaneq),
+                    "nonzero" => relation(Expr::notequals),
+                    other => return err!(InvalidCall, "unknown property '{}', expected integer, positive, negative, nonnegative, nonpositive or nonzero", span; other),
+                };
+                facts.push(relation.to_string());
+                assumptions.assume(relation);
+            }
+        } else {
+            for arg in args {
+                let relation = Expr::convert(arg, self)?.simplify();
+                facts.push(relation.to_string());
+                if !assumptions.assume(relation) {
+                    return err!(InvalidCall, "expected a relation between an unbound variable and a constant, like x > 0", span);
+                }
+            }
+        }
+
+        if let Some(var) = assumptions.contradiction() {
+            return err!(Domain, "the assumptions about {} contradict each other", span; var);
+        }
+        self.assumptions = assumptions;
+        Ok(facts)
+    }
+
+    /// Records the rule of a `rule[lhs -> rhs]` statement, where the variables of `lhs` are wildcards.
+    ///
+    /// Both sides are converted without the bound variables, which would otherwise turn wildcards into
+    /// constants.
+    pub fn add_rule(&mut self, mut args: Vec<Node>, span: Span) -> Result<&Rule> {
+        let (lhs, rhs) = match args.pop() {
+            Some(Node::BinaryOp { token: Token { ty: TokenType::Arrow, .. }, left, right }) if args.is_empty() => (*left, *right),
+            _ => return err!(InvalidCall, "expected a single rule like sin[a]^2 + cos[a]^2 -> 1", span),
+        };
+
+        let mut scope = self.clone();
+        scope.clear();
+        let Some(rule) = Rule::new(Expr::convert(lhs, &scope)?, Expr::convert(rhs, &scope)?) else {
+            return err!(InvalidCall, "the left side of a rule cannot be a lone variable, as it would match everything", span);
+        };
+        self.rules.push(rule);
+        Ok(self.rules.last().unwrap())
+    }
+
+    pub fn unbind(&mut self, name: &str) -> Option<Expr> {
+        self.vars.remove(name)
+    }
+
+    pub fn clear(&mut self) {
+        self.vars.clear();
+    }
+
+    pub fn vars(&self) -> impl Iterator<Item = (&String, &Expr)> {
+        self.vars.iter()
+    }
+
+    /// Replaces every bound variable in `expr` with its value.
+    pub fn substitute(&self, expr: Expr) -> Expr {
+        self.resolve(expr, &mut Vec::new())
+    }
+
+    /// Substitutes bound variables, including those appearing in the values of other variables.
+    ///
+    /// `visiting` holds the variables currently being expanded, so a value like `x + 1` bound to `x`
+    /// while `x` itself was unbound does not expand forever.
+    fn resolve(&self, expr: Expr, visiting: &mut Vec<String>) -> Expr {
+        match expr {
+            Expr::Variable(name) if !visiting.contains(&name) => match self.vars.get(&name) {
+                Some(value) => {
+                    visiting.push(name);
+                    let value = self.resolve(value.clone(), visiting);
+                    visiting.pop();
+                    value
+                },
+                None => Expr::Variable(name),
+            },
+            other => other.map_children(|x| self.resolve(x, visiting)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Session;
+    use crate::testing::{eval_in, run};
+
+    #[test]
+    fn assignments_persist() {
+        let mut session = Session::new();
+        assert_eq!(eval_in(&mut session, "x := 2; let y = x + 1"), "3");
+        assert_eq!(eval_in(&mut session, "x y"), "6");
+        assert_eq!(eval_in(&mut session, "x := 5; y"), "3");
+        session.unbind("x");
+        assert_eq!(eval_in(&mut session, "x + y"), "(x + 3)");
+    }
+
+    #[test]
+    fn bound_variables_of_calls_are_not_substituted() {
+        let mut session = Session::new();
+        eval_in(&mut session, "i := 10");
+        assert_eq!(eval_in(&mut session, "sum:i:1:3[i]"), "6");
+        assert_eq!(eval_in(&mut session, "prod:i:1:3[i]"), "6");
+    }
+
+    #[test]
+    fn let_is_not_a_variable_name() {
+        assert_eq!(run(&mut Session::new(), "let let = 3"), Err("'let' is a keyword and cannot be a variable name".to_string()));
+    }
+}
@@ -10,6 +10,8 @@ pub enum RunStrategies {
     ShowAST,
     /// Tokenize
     Tokenize,
+    /// Show steps
+    ShowSteps,
 }
 
 pub fn print_runstrats() {
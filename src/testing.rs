@@ -0,0 +1,65 @@
+use crate::{expr::Expr, lexer::{token::{Token, TokenType}, Lexer}, parser::{node::Node, Parser}, plot::Plot, session::Session};
+use crate::prelude::*;
+
+
+fn details(err: Error) -> String {
+    match err {
+        Error::UnknownCharacter(details, _) | Error::Syntax(details, _) | Error::InvalidCall(details, _) | Error::Domain(details, _) => details,
+    }
+}
+
+fn run_stmt(session: &mut Session, stmt: Node) -> Result<String> {
+    Ok(match stmt {
+        Node::Assign { name, value, .. } => {
+            let value = Expr::convert(*value, session)?;
+            format!("{:#}", session.bind(format!("{}", name.ty), value).0)
+        },
+        Node::Call { name: Token { ty: TokenType::Identifier(name), .. }, args, span, .. } if name == "assume" => session.assume(args, span)?.join(", "),
+        Node::Call { name: Token { ty: TokenType::Identifier(name), .. }, args, span, .. } if name == "rule" => session.add_rule(args, span)?.to_string(),
+        stmt => format!("{:#}", session.simplify(Expr::convert(stmt, session)?)),
+    })
+}
+
+/// Runs each statement of `input` in `session` as the REPL does, returning the last result as it is
+/// printed, or the details of the first error.
+pub fn run(session: &mut Session, input: &str) -> std::result::Result<String, String> {
+    let tokens = Lexer::new(input).tokenize().map_err(details)?;
+    let mut last = String::new();
+    for stmt in Parser::new(tokens).parse() {
+        last = stmt.and_then(|stmt| run_stmt(session, stmt)).map_err(details)?;
+    }
+    Ok(last)
+}
+
+/// Runs `input` in `session`, which must succeed.
+pub fn eval_in(session: &mut Session, input: &str) -> String {
+    run(session, input).unwrap_or_else(|err| panic!("{}: {}", input, err))
+}
+
+/// Runs `input` in a fresh session, which must succeed.
+pub fn eval(input: &str) -> String {
+    eval_in(&mut Session::new(), input)
+}
+
+/// The details of the error from running `input` in a fresh session, which must fail.
+pub fn error(input: &str) -> String {
+    match run(&mut Session::new(), input) {
+        Ok(out) => panic!("{} gave {} rather than an error", input, out),
+        Err(err) => err,
+    }
+}
+
+/// Converts a single statement in a fresh session, without simplifying it.
+pub fn parse(input: &str) -> Expr {
+    let Ok(tokens) = Lexer::new(input).tokenize() else { panic!("cannot tokenize {}", input) };
+    let Some(Ok(node)) = Parser::new(tokens).parse().pop() else { panic!("cannot parse {}", input) };
+    let Ok(expr) = Expr::convert(node, &Session::new()) else { panic!("cannot convert {}", input) };
+    expr
+}
+
+/// Reads a call like `plot:x:-1:1[x]` in a fresh session, or the details of the error.
+pub fn plot(input: &str) -> std::result::Result<Plot, String> {
+    let Ok(tokens) = Lexer::new(input).tokenize() else { panic!("cannot tokenize {}", input) };
+    let Some(Ok(Node::Call { params, args, span, .. })) = Parser::new(tokens).parse().pop() else { panic!("{} is not a call", input) };
+    Plot::new(&params, &args, span, &Session::new()).map_err(details)
+}
@@ -67,3 +67,11 @@ pub fn superscript(s: &str) -> String {
         })
     .collect()
 }
+
+/// Formats an error estimate or residual to two significant digits, like `1.2e-15`.
+pub fn magnitude(v: f64) -> String {
+    match v {
+        0.0 => "0".to_string(),
+        v => format!("{:.1e}", v),
+    }
+}